@@ -0,0 +1,454 @@
+use serde::Serialize;
+use std::fs::{File, OpenOptions};
+use std::io::{Read, Seek};
+use std::path::{Component, Path, PathBuf};
+use thiserror::Error;
+use tracing::warn;
+
+#[derive(Debug, Error)]
+pub enum ArchiveError {
+    #[error("IO error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("ZIP error: {0}")]
+    Zip(#[from] zip::result::ZipError),
+    #[error("Archive has more than {0} entries")]
+    TooManyEntries(usize),
+    #[error("Archive expands to more than {0} bytes")]
+    TooLarge(u64),
+    #[error("Extraction target {0} is not empty")]
+    DestinationNotEmpty(PathBuf),
+}
+
+/// Bounds for extracting archives that come from the network.
+#[derive(Debug, Clone, Copy)]
+pub struct ExtractLimits {
+    pub max_entries: usize,
+    pub max_total_bytes: u64,
+    /// Whether entries marked executable keep that bit (as `0o755`). No
+    /// other mode bits from the archive are ever applied.
+    pub allow_executables: bool,
+}
+
+impl ExtractLimits {
+    /// The config zip a WordPress site serves for its project folder.
+    pub const SITE_CONFIG: Self = Self {
+        max_entries: 2_000,
+        max_total_bytes: 64 * 1024 * 1024,
+        allow_executables: false,
+    };
+
+    /// An OpenCode release archive.
+    pub const RELEASE: Self = Self {
+        max_entries: 1_000,
+        max_total_bytes: 1024 * 1024 * 1024,
+        allow_executables: true,
+    };
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct RejectedEntry {
+    pub name: String,
+    pub reason: String,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ExtractReport {
+    pub extracted: usize,
+    pub total_bytes: u64,
+    pub rejected: Vec<RejectedEntry>,
+}
+
+impl ExtractReport {
+    fn reject(&mut self, name: &str, reason: &str) {
+        warn!("Skipped archive entry {:?}: {}", name, reason);
+        self.rejected.push(RejectedEntry {
+            name: name.to_string(),
+            reason: reason.to_string(),
+        });
+    }
+}
+
+/// Checks that `path` stays inside the destination: relative, and made of
+/// plain names only.
+pub(crate) fn safe_relative_path(path: &Path) -> Option<PathBuf> {
+    let mut clean = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::Normal(part) => clean.push(part),
+            Component::CurDir => {}
+            Component::ParentDir | Component::RootDir | Component::Prefix(_) => return None,
+        }
+    }
+    (!clean.as_os_str().is_empty()).then_some(clean)
+}
+
+/// Copies at most the remaining byte budget, failing once the entry turns
+/// out to be larger than that regardless of what its header claims.
+fn copy_limited(
+    reader: &mut impl Read,
+    out: &mut File,
+    report: &mut ExtractReport,
+    limits: &ExtractLimits,
+) -> Result<(), ArchiveError> {
+    let remaining = limits.max_total_bytes.saturating_sub(report.total_bytes);
+    let written = std::io::copy(&mut reader.take(remaining + 1), out)?;
+    if written > remaining {
+        return Err(ArchiveError::TooLarge(limits.max_total_bytes));
+    }
+    report.total_bytes += written;
+    Ok(())
+}
+
+/// Creates the file for an entry. Never opens an existing path, so a
+/// duplicate entry or anything already at `path`, symlinks included, is
+/// left alone.
+fn create_entry_file(path: &Path) -> std::io::Result<File> {
+    OpenOptions::new().write(true).create_new(true).open(path)
+}
+
+/// Extracts into a fresh staging folder next to `dest` and moves it into
+/// place only once every entry made it, so a failed extraction leaves
+/// nothing behind. `dest` must be missing or empty.
+fn extract_staged(
+    dest: &Path,
+    extract: impl FnOnce(&Path) -> Result<ExtractReport, ArchiveError>,
+) -> Result<ExtractReport, ArchiveError> {
+    if std::fs::read_dir(dest).is_ok_and(|mut entries| entries.next().is_some()) {
+        return Err(ArchiveError::DestinationNotEmpty(dest.to_path_buf()));
+    }
+
+    let mut name = dest.file_name().unwrap_or_default().to_os_string();
+    name.push(format!(".extract-{}", uuid::Uuid::new_v4()));
+    let staging = dest.with_file_name(name);
+    if let Some(parent) = staging.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    std::fs::create_dir(&staging)?;
+
+    let result = extract(&staging).and_then(|report| {
+        if dest.exists() {
+            std::fs::remove_dir(dest)?;
+        }
+        std::fs::rename(&staging, dest)?;
+        Ok(report)
+    });
+    if result.is_err() {
+        std::fs::remove_dir_all(&staging).ok();
+    }
+    result
+}
+
+#[cfg(unix)]
+fn apply_mode(path: &Path, executable: bool, limits: &ExtractLimits) -> std::io::Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+    if executable && limits.allow_executables {
+        std::fs::set_permissions(path, std::fs::Permissions::from_mode(0o755))?;
+    }
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn apply_mode(_path: &Path, _executable: bool, _limits: &ExtractLimits) -> std::io::Result<()> {
+    Ok(())
+}
+
+pub fn extract_zip<R: Read + Seek>(
+    reader: R,
+    dest: &Path,
+    limits: &ExtractLimits,
+) -> Result<ExtractReport, ArchiveError> {
+    extract_staged(dest, |staging| extract_zip_into(reader, staging, limits))
+}
+
+fn extract_zip_into<R: Read + Seek>(
+    reader: R,
+    dest: &Path,
+    limits: &ExtractLimits,
+) -> Result<ExtractReport, ArchiveError> {
+    let mut archive = zip::ZipArchive::new(reader)?;
+    if archive.len() > limits.max_entries {
+        return Err(ArchiveError::TooManyEntries(limits.max_entries));
+    }
+
+    let mut report = ExtractReport::default();
+    for i in 0..archive.len() {
+        let mut entry = archive.by_index(i)?;
+        let name = entry.name().to_string();
+
+        if entry.is_symlink() {
+            report.reject(&name, "symbolic links are not allowed");
+            continue;
+        }
+        let Some(relative) = entry
+            .enclosed_name()
+            .as_deref()
+            .and_then(safe_relative_path)
+        else {
+            report.reject(&name, "path escapes the destination");
+            continue;
+        };
+
+        let outpath = dest.join(&relative);
+        if entry.is_dir() {
+            std::fs::create_dir_all(&outpath)?;
+            continue;
+        }
+
+        if let Some(parent) = outpath.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let mut outfile = match create_entry_file(&outpath) {
+            Ok(file) => file,
+            Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => {
+                report.reject(&name, "duplicate entry");
+                continue;
+            }
+            Err(e) => return Err(e.into()),
+        };
+        copy_limited(&mut entry, &mut outfile, &mut report, limits)?;
+
+        let executable = entry.unix_mode().is_some_and(|mode| mode & 0o111 != 0);
+        apply_mode(&outpath, executable, limits)?;
+        report.extracted += 1;
+    }
+
+    Ok(report)
+}
+
+pub fn extract_tar_gz(
+    archive: &Path,
+    dest: &Path,
+    limits: &ExtractLimits,
+) -> Result<ExtractReport, ArchiveError> {
+    extract_staged(dest, |staging| {
+        extract_tar_gz_into(archive, staging, limits)
+    })
+}
+
+fn extract_tar_gz_into(
+    archive: &Path,
+    dest: &Path,
+    limits: &ExtractLimits,
+) -> Result<ExtractReport, ArchiveError> {
+    let decoder = flate2::read::GzDecoder::new(File::open(archive)?);
+    let mut archive = tar::Archive::new(decoder);
+
+    let mut report = ExtractReport::default();
+    let mut entries = 0;
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        entries += 1;
+        if entries > limits.max_entries {
+            return Err(ArchiveError::TooManyEntries(limits.max_entries));
+        }
+
+        let name = entry.path()?.to_string_lossy().to_string();
+        let kind = entry.header().entry_type();
+        if !kind.is_file() && !kind.is_dir() {
+            report.reject(&name, "only regular files and directories are allowed");
+            continue;
+        }
+        let Some(relative) = safe_relative_path(&entry.path()?) else {
+            report.reject(&name, "path escapes the destination");
+            continue;
+        };
+
+        let outpath = dest.join(&relative);
+        if kind.is_dir() {
+            std::fs::create_dir_all(&outpath)?;
+            continue;
+        }
+
+        if let Some(parent) = outpath.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let executable = entry.header().mode().is_ok_and(|mode| mode & 0o111 != 0);
+        let mut outfile = match create_entry_file(&outpath) {
+            Ok(file) => file,
+            Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => {
+                report.reject(&name, "duplicate entry");
+                continue;
+            }
+            Err(e) => return Err(e.into()),
+        };
+        copy_limited(&mut entry, &mut outfile, &mut report, limits)?;
+        apply_mode(&outpath, executable, limits)?;
+        report.extracted += 1;
+    }
+
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{Cursor, Write};
+    use zip::write::SimpleFileOptions;
+
+    const LIMITS: ExtractLimits = ExtractLimits {
+        max_entries: 100,
+        max_total_bytes: 1024,
+        allow_executables: false,
+    };
+
+    fn temp_dir() -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("wordforge-archive-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn zip_with(build: impl FnOnce(&mut zip::ZipWriter<Cursor<Vec<u8>>>)) -> Cursor<Vec<u8>> {
+        let mut writer = zip::ZipWriter::new(Cursor::new(Vec::new()));
+        build(&mut writer);
+        let mut cursor = writer.finish().unwrap();
+        cursor.set_position(0);
+        cursor
+    }
+
+    fn add_file(writer: &mut zip::ZipWriter<Cursor<Vec<u8>>>, name: &str, content: &[u8]) {
+        writer
+            .start_file(name, SimpleFileOptions::default())
+            .unwrap();
+        writer.write_all(content).unwrap();
+    }
+
+    /// Writes a tar.gz whose entry names are stored verbatim, bypassing the
+    /// path checks of `tar::Builder`.
+    fn tar_gz_with(path: &Path, entries: &[(&str, tar::EntryType, &[u8])]) {
+        let encoder =
+            flate2::write::GzEncoder::new(File::create(path).unwrap(), flate2::Compression::fast());
+        let mut builder = tar::Builder::new(encoder);
+        for (name, kind, content) in entries {
+            let mut header = tar::Header::new_old();
+            header.as_old_mut().name[..name.len()].copy_from_slice(name.as_bytes());
+            header.set_entry_type(*kind);
+            header.set_mode(0o644);
+            header.set_size(content.len() as u64);
+            if kind.is_symlink() {
+                header.set_link_name("/etc/passwd").unwrap();
+            }
+            header.set_cksum();
+            builder.append(&header, *content).unwrap();
+        }
+        builder.into_inner().unwrap().finish().unwrap();
+    }
+
+    fn leftovers(parent: &Path) -> Vec<String> {
+        std::fs::read_dir(parent)
+            .unwrap()
+            .flatten()
+            .map(|entry| entry.file_name().to_string_lossy().to_string())
+            .filter(|name| name.contains(".extract-"))
+            .collect()
+    }
+
+    #[test]
+    fn zip_rejects_escaping_and_symlink_entries() {
+        let root = temp_dir();
+        let dest = root.join("out");
+        let archive = zip_with(|writer| {
+            add_file(writer, "ok/file.txt", b"hello");
+            add_file(writer, "../escape.txt", b"nope");
+            add_file(writer, "/abs.txt", b"nope");
+            writer
+                .add_symlink("link", "/etc/passwd", SimpleFileOptions::default())
+                .unwrap();
+        });
+
+        let report = extract_zip(archive, &dest, &LIMITS).unwrap();
+        assert_eq!(report.extracted, 1);
+        assert_eq!(report.rejected.len(), 3);
+        assert_eq!(
+            std::fs::read_to_string(dest.join("ok/file.txt")).unwrap(),
+            "hello"
+        );
+        assert!(!root.join("escape.txt").exists());
+        assert!(!dest.join("link").exists());
+        assert!(leftovers(&root).is_empty());
+
+        std::fs::remove_dir_all(root).ok();
+    }
+
+    #[test]
+    fn tar_rejects_escaping_and_symlink_entries() {
+        let root = temp_dir();
+        let dest = root.join("out");
+        let archive = root.join("release.tar.gz");
+        tar_gz_with(
+            &archive,
+            &[
+                ("opencode", tar::EntryType::Regular, b"binary"),
+                ("../escape", tar::EntryType::Regular, b"nope"),
+                ("/abs", tar::EntryType::Regular, b"nope"),
+                ("link", tar::EntryType::Symlink, b""),
+            ],
+        );
+
+        let report = extract_tar_gz(&archive, &dest, &LIMITS).unwrap();
+        assert_eq!(report.extracted, 1);
+        assert_eq!(report.rejected.len(), 3);
+        assert_eq!(
+            std::fs::read_to_string(dest.join("opencode")).unwrap(),
+            "binary"
+        );
+        assert!(!root.join("escape").exists());
+        assert!(std::fs::symlink_metadata(dest.join("link")).is_err());
+
+        std::fs::remove_dir_all(root).ok();
+    }
+
+    #[test]
+    fn size_cap_leaves_nothing_behind() {
+        let root = temp_dir();
+        let dest = root.join("out");
+        let archive = zip_with(|writer| {
+            add_file(writer, "small.txt", b"fits");
+            add_file(writer, "big.bin", &[0u8; 2048]);
+        });
+
+        let result = extract_zip(archive, &dest, &LIMITS);
+        assert!(matches!(result, Err(ArchiveError::TooLarge(1024))));
+        assert!(!dest.exists());
+        assert!(leftovers(&root).is_empty());
+
+        std::fs::remove_dir_all(root).ok();
+    }
+
+    #[test]
+    fn duplicate_entries_do_not_overwrite() {
+        let root = temp_dir();
+        let dest = root.join("out");
+        let archive = root.join("dup.tar.gz");
+        tar_gz_with(
+            &archive,
+            &[
+                ("file", tar::EntryType::Regular, b"first"),
+                ("file", tar::EntryType::Regular, b"second"),
+            ],
+        );
+
+        let report = extract_tar_gz(&archive, &dest, &LIMITS).unwrap();
+        assert_eq!(report.rejected.len(), 1);
+        assert_eq!(std::fs::read_to_string(dest.join("file")).unwrap(), "first");
+
+        std::fs::remove_dir_all(root).ok();
+    }
+
+    #[test]
+    fn refuses_non_empty_destination() {
+        let root = temp_dir();
+        let dest = root.join("out");
+        std::fs::create_dir_all(&dest).unwrap();
+        std::fs::write(dest.join("existing"), "keep").unwrap();
+
+        let archive = zip_with(|writer| add_file(writer, "existing", b"replaced"));
+        let result = extract_zip(archive, &dest, &LIMITS);
+        assert!(matches!(result, Err(ArchiveError::DestinationNotEmpty(_))));
+        assert_eq!(
+            std::fs::read_to_string(dest.join("existing")).unwrap(),
+            "keep"
+        );
+
+        std::fs::remove_dir_all(root).ok();
+    }
+}
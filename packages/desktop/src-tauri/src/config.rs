@@ -0,0 +1,658 @@
+use crate::persist;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+use thiserror::Error;
+use tracing::{info, warn};
+
+pub const SCHEMA_URL: &str = "https://opencode.ai/config.json";
+const HISTORY_DIR: &str = "history";
+const HISTORY_SETTINGS_FILE: &str = "settings.json";
+const DEFAULT_RETENTION: usize = 50;
+
+#[derive(Debug, Error)]
+pub enum ConfigError {
+    #[error("IO error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("{file} is not valid JSON: {message}")]
+    Parse { file: String, message: String },
+    #[error("Invalid OpenCode config: {}", .0.join("; "))]
+    Invalid(Vec<String>),
+    #[error("Config snapshot not found: {0}")]
+    SnapshotNotFound(String),
+}
+
+/// The global `opencode.json`. Only the keys the app works with are typed;
+/// everything else is carried through `extra` untouched.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct GlobalConfig {
+    #[serde(rename = "$schema", default, skip_serializing_if = "Option::is_none")]
+    pub schema: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub theme: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub model: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub small_model: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub username: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub share: Option<String>,
+    /// `true`, `false` or `"notify"`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub autoupdate: Option<Value>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub snapshot: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub plugin: Option<Vec<String>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub instructions: Option<Vec<String>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub disabled_providers: Option<Vec<String>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub enabled_providers: Option<Vec<String>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub provider: Option<BTreeMap<String, ProviderConfig>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mcp: Option<BTreeMap<String, McpConfig>>,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ProviderConfig {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub npm: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub models: Option<Map<String, Value>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub options: Option<Map<String, Value>>,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct McpConfig {
+    #[serde(rename = "type")]
+    pub kind: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub command: Option<Vec<String>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub url: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub enabled: Option<bool>,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+/// The OpenCode config schema, vendored so that saving works offline.
+const SCHEMA: &str = include_str!("opencode-config.schema.json");
+
+fn schema_validator() -> &'static jsonschema::Validator {
+    static VALIDATOR: OnceLock<jsonschema::Validator> = OnceLock::new();
+    VALIDATOR.get_or_init(|| {
+        let schema: Value = serde_json::from_str(SCHEMA).expect("vendored schema is valid JSON");
+        jsonschema::options()
+            .should_validate_formats(true)
+            .build(&schema)
+            .expect("vendored schema compiles")
+    })
+}
+
+impl GlobalConfig {
+    /// Checks the config against the vendored OpenCode config schema.
+    /// Returns every problem found, not just the first one, each prefixed
+    /// with the dotted path of the offending key.
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        let instance = serde_json::to_value(self).map_err(std::io::Error::from)?;
+        let issues: Vec<String> = schema_validator()
+            .iter_errors(&instance)
+            .map(|error| {
+                let pointer = error.instance_path().to_string();
+                let key = pointer.trim_start_matches('/').replace('/', ".");
+                if key.is_empty() {
+                    error.to_string()
+                } else {
+                    format!("{}: {}", key, error)
+                }
+            })
+            .collect();
+
+        if issues.is_empty() {
+            Ok(())
+        } else {
+            Err(ConfigError::Invalid(issues))
+        }
+    }
+}
+
+/// Reads the config, treating a missing file as empty. A file that exists
+/// but cannot be parsed is an error, so that saving never clobbers it.
+pub async fn load(path: &Path) -> Result<GlobalConfig, ConfigError> {
+    let content = match tokio::fs::read_to_string(path).await {
+        Ok(content) => content,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(GlobalConfig::default()),
+        Err(e) => return Err(e.into()),
+    };
+
+    parse(path, &content)
+}
+
+fn parse(path: &Path, content: &str) -> Result<GlobalConfig, ConfigError> {
+    serde_json::from_str(content).map_err(|e| match e.classify() {
+        serde_json::error::Category::Data => ConfigError::Invalid(vec![e.to_string()]),
+        _ => ConfigError::Parse {
+            file: path
+                .file_name()
+                .map(|n| n.to_string_lossy().to_string())
+                .unwrap_or_else(|| path.display().to_string()),
+            message: e.to_string(),
+        },
+    })
+}
+
+pub async fn save(path: &Path, config: &GlobalConfig) -> Result<(), ConfigError> {
+    config.validate()?;
+
+    let mut config = config.clone();
+    if config.schema.is_none() {
+        config.schema = Some(SCHEMA_URL.to_string());
+    }
+
+    if let Some(parent) = path.parent() {
+        tokio::fs::create_dir_all(parent).await?;
+    }
+    let content = serde_json::to_string_pretty(&config).map_err(std::io::Error::from)?;
+    write_config(path, &content).await
+}
+
+/// Replaces the config file and records the new content in the history.
+/// A file the history does not know yet, such as the config that predates
+/// the app or one edited by hand, is snapshotted first so it can be
+/// restored.
+async fn write_config(path: &Path, content: &str) -> Result<(), ConfigError> {
+    if let Err(e) = preserve_current(path).await {
+        warn!("Failed to snapshot the existing OpenCode config: {}", e);
+    }
+
+    persist::write_atomic(path, content.as_bytes())?;
+
+    if let Err(e) = record_snapshot(path, content).await {
+        warn!("Failed to snapshot OpenCode config: {}", e);
+    }
+
+    info!("Updated global OpenCode config at {:?}", path);
+    Ok(())
+}
+
+async fn preserve_current(path: &Path) -> Result<(), ConfigError> {
+    let current = match tokio::fs::read_to_string(path).await {
+        Ok(current) => current,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(()),
+        Err(e) => return Err(e.into()),
+    };
+
+    record_snapshot(path, &current).await
+}
+
+/// Applies an RFC 7396 JSON merge patch to the saved config and returns the
+/// result. Type errors in the patched document are reported as invalid
+/// config rather than as a parse error of the file.
+pub async fn patch(path: &Path, patch: &Value) -> Result<GlobalConfig, ConfigError> {
+    let current = load(path).await?;
+    let mut document = serde_json::to_value(&current).map_err(std::io::Error::from)?;
+    merge_patch(&mut document, patch);
+
+    let config: GlobalConfig =
+        serde_json::from_value(document).map_err(|e| ConfigError::Invalid(vec![e.to_string()]))?;
+    save(path, &config).await?;
+    Ok(config)
+}
+
+fn merge_patch(target: &mut Value, patch: &Value) {
+    let Value::Object(patch) = patch else {
+        *target = patch.clone();
+        return;
+    };
+
+    if !target.is_object() {
+        *target = Value::Object(Map::new());
+    }
+    if let Value::Object(target) = target {
+        for (key, value) in patch {
+            if value.is_null() {
+                target.remove(key);
+            } else {
+                merge_patch(target.entry(key.clone()).or_insert(Value::Null), value);
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HistorySettings {
+    /// Number of snapshots kept; older ones are deleted on the next write.
+    pub retention: usize,
+}
+
+impl Default for HistorySettings {
+    fn default() -> Self {
+        Self {
+            retention: DEFAULT_RETENTION,
+        }
+    }
+}
+
+/// A copy of the config as it was written at `created_at` (milliseconds
+/// since the Unix epoch). The id is the file stem under `history/`.
+#[derive(Debug, Clone, Serialize)]
+pub struct ConfigSnapshot {
+    pub id: String,
+    pub created_at: u64,
+    pub size: u64,
+}
+
+#[derive(Debug, Clone, Copy, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ChangeKind {
+    Added,
+    Removed,
+    Changed,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ConfigChange {
+    /// Dotted path of the key, e.g. `provider.anthropic.models`.
+    pub path: String,
+    pub kind: ChangeKind,
+    pub before: Option<Value>,
+    pub after: Option<Value>,
+}
+
+fn history_dir(path: &Path) -> PathBuf {
+    path.parent().unwrap_or(Path::new(".")).join(HISTORY_DIR)
+}
+
+pub async fn load_history_settings(path: &Path) -> HistorySettings {
+    tokio::fs::read_to_string(history_dir(path).join(HISTORY_SETTINGS_FILE))
+        .await
+        .ok()
+        .and_then(|content| serde_json::from_str(&content).ok())
+        .unwrap_or_default()
+}
+
+pub async fn save_history_settings(
+    path: &Path,
+    settings: &HistorySettings,
+) -> Result<(), ConfigError> {
+    let dir = history_dir(path);
+    let content = serde_json::to_string_pretty(settings).map_err(std::io::Error::from)?;
+    persist::write_atomic(&dir.join(HISTORY_SETTINGS_FILE), content.as_bytes())?;
+    prune_snapshots(path, settings.retention).await
+}
+
+/// Snapshots, newest first.
+pub async fn list_snapshots(path: &Path) -> Result<Vec<ConfigSnapshot>, ConfigError> {
+    let mut entries = match tokio::fs::read_dir(history_dir(path)).await {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e.into()),
+    };
+
+    let mut snapshots = Vec::new();
+    while let Some(entry) = entries.next_entry().await? {
+        let file = entry.path();
+        if file.extension().is_none_or(|ext| ext != "json") {
+            continue;
+        }
+        let Some(created_at) = file
+            .file_stem()
+            .and_then(|s| s.to_str())
+            .and_then(|s| s.parse::<u64>().ok())
+        else {
+            continue;
+        };
+        snapshots.push(ConfigSnapshot {
+            id: created_at.to_string(),
+            created_at,
+            size: entry.metadata().await.map(|m| m.len()).unwrap_or(0),
+        });
+    }
+
+    snapshots.sort_by_key(|s| std::cmp::Reverse(s.created_at));
+    Ok(snapshots)
+}
+
+/// Adds `content` to the history unless it is what the newest snapshot
+/// already holds, so saving unchanged content does not fill the history.
+async fn record_snapshot(path: &Path, content: &str) -> Result<(), ConfigError> {
+    if let Some(latest) = list_snapshots(path).await?.first() {
+        if read_snapshot(path, &latest.id).await? == content {
+            return Ok(());
+        }
+    }
+
+    let dir = history_dir(path);
+    tokio::fs::create_dir_all(&dir).await?;
+
+    let mut id = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0);
+    while tokio::fs::try_exists(dir.join(format!("{}.json", id)))
+        .await
+        .unwrap_or(false)
+    {
+        id += 1;
+    }
+    persist::write_atomic(&dir.join(format!("{}.json", id)), content.as_bytes())?;
+
+    let settings = load_history_settings(path).await;
+    prune_snapshots(path, settings.retention).await
+}
+
+async fn prune_snapshots(path: &Path, retention: usize) -> Result<(), ConfigError> {
+    let dir = history_dir(path);
+    for snapshot in list_snapshots(path)
+        .await?
+        .into_iter()
+        .skip(retention.max(1))
+    {
+        tokio::fs::remove_file(dir.join(format!("{}.json", snapshot.id)))
+            .await
+            .ok();
+    }
+    Ok(())
+}
+
+async fn read_snapshot(path: &Path, id: &str) -> Result<String, ConfigError> {
+    if id.parse::<u64>().is_err() {
+        return Err(ConfigError::SnapshotNotFound(id.to_string()));
+    }
+    match tokio::fs::read_to_string(history_dir(path).join(format!("{}.json", id))).await {
+        Ok(content) => Ok(content),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            Err(ConfigError::SnapshotNotFound(id.to_string()))
+        }
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Changes from snapshot `from` to snapshot `to`, or to the current config
+/// when `to` is `None`.
+pub async fn diff_snapshots(
+    path: &Path,
+    from: &str,
+    to: Option<&str>,
+) -> Result<Vec<ConfigChange>, ConfigError> {
+    let before = parse_value(path, &read_snapshot(path, from).await?)?;
+    let after = match to {
+        Some(to) => parse_value(path, &read_snapshot(path, to).await?)?,
+        None => serde_json::to_value(load(path).await?).map_err(std::io::Error::from)?,
+    };
+
+    let mut changes = Vec::new();
+    diff_values("", &before, &after, &mut changes);
+    Ok(changes)
+}
+
+/// Writes the snapshot back as the current config, byte for byte. It is not
+/// run through [`GlobalConfig::validate`]: the snapshot was a working config
+/// once, and rules added since must not make history unrestorable. The
+/// restore itself is recorded as a new snapshot, so it can be undone too.
+pub async fn restore_snapshot(path: &Path, id: &str) -> Result<GlobalConfig, ConfigError> {
+    let content = read_snapshot(path, id).await?;
+    let config = parse(path, &content)?;
+    write_config(path, &content).await?;
+    Ok(config)
+}
+
+fn parse_value(path: &Path, content: &str) -> Result<Value, ConfigError> {
+    serde_json::from_str(content).map_err(|e| ConfigError::Parse {
+        file: path.display().to_string(),
+        message: e.to_string(),
+    })
+}
+
+fn diff_values(prefix: &str, before: &Value, after: &Value, changes: &mut Vec<ConfigChange>) {
+    let (Value::Object(before), Value::Object(after)) = (before, after) else {
+        if before != after {
+            changes.push(ConfigChange {
+                path: prefix.to_string(),
+                kind: ChangeKind::Changed,
+                before: Some(before.clone()),
+                after: Some(after.clone()),
+            });
+        }
+        return;
+    };
+
+    let keys: BTreeSet<&String> = before.keys().chain(after.keys()).collect();
+    for key in keys {
+        let path = if prefix.is_empty() {
+            key.clone()
+        } else {
+            format!("{}.{}", prefix, key)
+        };
+        match (before.get(key), after.get(key)) {
+            (Some(b), Some(a)) => diff_values(&path, b, a, changes),
+            (Some(b), None) => changes.push(ConfigChange {
+                path,
+                kind: ChangeKind::Removed,
+                before: Some(b.clone()),
+                after: None,
+            }),
+            (None, Some(a)) => changes.push(ConfigChange {
+                path,
+                kind: ChangeKind::Added,
+                before: None,
+                after: Some(a.clone()),
+            }),
+            (None, None) => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn temp_config() -> PathBuf {
+        std::env::temp_dir()
+            .join(format!("wordforge-config-{}", uuid::Uuid::new_v4()))
+            .join("opencode.json")
+    }
+
+    fn patched(target: Value, patch: Value) -> Value {
+        let mut target = target;
+        merge_patch(&mut target, &patch);
+        target
+    }
+
+    #[test]
+    fn merge_patch_follows_rfc_7396_examples() {
+        let cases = [
+            (json!({"a": "b"}), json!({"a": "c"}), json!({"a": "c"})),
+            (
+                json!({"a": "b"}),
+                json!({"b": "c"}),
+                json!({"a": "b", "b": "c"}),
+            ),
+            (json!({"a": "b"}), json!({"a": null}), json!({})),
+            (
+                json!({"a": "b", "b": "c"}),
+                json!({"a": null}),
+                json!({"b": "c"}),
+            ),
+            (json!({"a": ["b"]}), json!({"a": "c"}), json!({"a": "c"})),
+            (json!({"a": "c"}), json!({"a": ["b"]}), json!({"a": ["b"]})),
+            (
+                json!({"a": {"b": "c"}}),
+                json!({"a": {"b": "d", "c": null}}),
+                json!({"a": {"b": "d"}}),
+            ),
+            (
+                json!({"a": [{"b": "c"}]}),
+                json!({"a": [1]}),
+                json!({"a": [1]}),
+            ),
+            (json!(["a", "b"]), json!(["c", "d"]), json!(["c", "d"])),
+            (json!({"a": "b"}), json!(["c"]), json!(["c"])),
+            (json!({"a": "foo"}), json!(null), json!(null)),
+            (json!({"a": "foo"}), json!("bar"), json!("bar")),
+            (
+                json!({"e": null}),
+                json!({"a": 1}),
+                json!({"e": null, "a": 1}),
+            ),
+            (
+                json!([1, 2]),
+                json!({"a": "b", "c": null}),
+                json!({"a": "b"}),
+            ),
+            (
+                json!({}),
+                json!({"a": {"bb": {"ccc": null}}}),
+                json!({"a": {"bb": {}}}),
+            ),
+        ];
+
+        for (target, patch, expected) in cases {
+            assert_eq!(
+                patched(target.clone(), patch.clone()),
+                expected,
+                "{} + {}",
+                target,
+                patch
+            );
+        }
+    }
+
+    #[test]
+    fn merge_patch_keeps_untouched_provider_keys() {
+        let target = json!({
+            "model": "anthropic/claude",
+            "provider": {"anthropic": {"options": {"apiKey": "k"}, "models": {}}}
+        });
+        let patch = json!({"provider": {"anthropic": {"options": {"baseURL": "http://x"}}}});
+
+        assert_eq!(
+            patched(target, patch),
+            json!({
+                "model": "anthropic/claude",
+                "provider": {"anthropic": {"options": {"apiKey": "k", "baseURL": "http://x"}, "models": {}}}
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn save_keeps_the_config_it_replaces() {
+        let path = temp_config();
+        let original = "{\n  \"theme\": \"hand-written\"\n}";
+        persist::write_atomic(&path, original.as_bytes()).unwrap();
+
+        let config = GlobalConfig {
+            theme: Some("app".into()),
+            ..GlobalConfig::default()
+        };
+        save(&path, &config).await.unwrap();
+        save(&path, &config).await.unwrap();
+
+        let snapshots = list_snapshots(&path).await.unwrap();
+        assert_eq!(snapshots.len(), 2);
+        let newest = read_snapshot(&path, &snapshots[0].id).await.unwrap();
+        assert_eq!(newest, std::fs::read_to_string(&path).unwrap());
+        let oldest = snapshots.last().unwrap();
+        assert_eq!(read_snapshot(&path, &oldest.id).await.unwrap(), original);
+        assert_eq!(load(&path).await.unwrap().theme.as_deref(), Some("app"));
+
+        std::fs::remove_dir_all(path.parent().unwrap()).ok();
+    }
+
+    fn issues(config: Value) -> Vec<String> {
+        let config: GlobalConfig = serde_json::from_value(config).unwrap();
+        match config.validate() {
+            Ok(()) => Vec::new(),
+            Err(ConfigError::Invalid(issues)) => issues,
+            Err(e) => panic!("unexpected error: {}", e),
+        }
+    }
+
+    #[test]
+    fn validate_accepts_valid_and_unknown_keys() {
+        let config = json!({
+            "$schema": SCHEMA_URL,
+            "model": "anthropic/claude-sonnet-4",
+            "share": "manual",
+            "autoupdate": "notify",
+            "plugin": ["opencode-wordforge"],
+            "mcp": {
+                "wordpress": {"type": "remote", "url": "https://example.com/wp-json/mcp"},
+                "files": {"type": "local", "command": ["npx", "files-mcp"]}
+            },
+            "some_future_key": {"anything": true}
+        });
+        assert_eq!(issues(config), Vec::<String>::new());
+    }
+
+    #[test]
+    fn validate_reports_every_schema_violation() {
+        let config = json!({
+            "model": "claude",
+            "share": "sometimes",
+            "autoupdate": "yes",
+            "plugin": [""],
+            "mcp": {
+                "a": {"type": "local", "command": []},
+                "b": {"type": "remote", "url": "not a url"},
+                "c": {"type": "stdio"}
+            },
+            "tools": {"bash": "on"}
+        });
+        let issues = issues(config);
+        for key in [
+            "model:",
+            "share:",
+            "autoupdate:",
+            "plugin.0:",
+            "mcp.a.command:",
+            "mcp.b.url:",
+            "mcp.c.type:",
+            "tools.bash:",
+        ] {
+            assert!(
+                issues.iter().any(|issue| issue.starts_with(key)),
+                "no issue for {} in {:?}",
+                key,
+                issues
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn restores_snapshots_that_fail_current_rules() {
+        let path = temp_config();
+        let legacy = "{\n  \"share\": \"legacy-mode\"\n}";
+        persist::write_atomic(&path, legacy.as_bytes()).unwrap();
+        save(&path, &GlobalConfig::default()).await.unwrap();
+
+        let legacy_id = list_snapshots(&path)
+            .await
+            .unwrap()
+            .last()
+            .unwrap()
+            .id
+            .clone();
+        let restored = restore_snapshot(&path, &legacy_id).await.unwrap();
+        assert_eq!(restored.share.as_deref(), Some("legacy-mode"));
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), legacy);
+
+        std::fs::remove_dir_all(path.parent().unwrap()).ok();
+    }
+}
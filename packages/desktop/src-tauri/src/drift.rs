@@ -0,0 +1,249 @@
+use crate::reauth;
+use crate::sites::{ConfigHashComponents, ConfigHashResponse, SiteManager};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use std::time::Duration;
+use tauri::{AppHandle, Emitter, Manager};
+use tokio::sync::{watch, Mutex};
+use tracing::debug;
+
+const DEFAULT_INTERVAL_MINUTES: u64 = 15;
+pub const MIN_INTERVAL_MINUTES: u64 = 1;
+
+/// How often every site's config hash is checked in the background.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DriftSettings {
+    pub enabled: bool,
+    pub interval_minutes: u64,
+}
+
+impl Default for DriftSettings {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            interval_minutes: DEFAULT_INTERVAL_MINUTES,
+        }
+    }
+}
+
+/// Where a site's config now differs from what the last refresh applied.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ConfigDrift {
+    pub remote_hash: String,
+    /// Component names, e.g. `theme`. Both lists are empty when there was
+    /// no recorded baseline to compare against.
+    pub changed: Vec<String>,
+    pub unchanged: Vec<String>,
+    pub detected_at: u64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct DriftPayload<'a> {
+    site_id: &'a str,
+    #[serde(flatten)]
+    drift: &'a ConfigDrift,
+    summary: String,
+}
+
+impl ConfigDrift {
+    pub fn between(baseline: Option<&ConfigHashComponents>, remote: &ConfigHashResponse) -> Self {
+        let mut drift = Self {
+            remote_hash: remote.hash.clone(),
+            changed: Vec::new(),
+            unchanged: Vec::new(),
+            detected_at: std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .map(|d| d.as_secs())
+                .unwrap_or(0),
+        };
+        let Some(baseline) = baseline else {
+            return drift;
+        };
+
+        let now = &remote.components;
+        let components = [
+            ("plugins", baseline.plugins_hash != now.plugins_hash),
+            ("theme", baseline.theme_hash != now.theme_hash),
+            ("agents", baseline.agents_hash != now.agents_hash),
+            ("providers", baseline.providers_hash != now.providers_hash),
+            ("woocommerce", baseline.woo_active != now.woo_active),
+        ];
+        for (name, changed) in components {
+            if changed {
+                drift.changed.push(name.to_string());
+            } else {
+                drift.unchanged.push(name.to_string());
+            }
+        }
+        drift
+    }
+
+    /// E.g. "theme changed, plugins unchanged, agents unchanged".
+    pub fn summary(&self) -> String {
+        if self.changed.is_empty() && self.unchanged.is_empty() {
+            return "config changed".to_string();
+        }
+        self.changed
+            .iter()
+            .map(|name| format!("{} changed", name))
+            .chain(
+                self.unchanged
+                    .iter()
+                    .map(|name| format!("{} unchanged", name)),
+            )
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+}
+
+/// Handle for retiming the background drift poller.
+#[derive(Clone)]
+pub struct DriftSchedule {
+    tx: Arc<watch::Sender<DriftSettings>>,
+}
+
+impl DriftSchedule {
+    pub fn new(settings: DriftSettings) -> Self {
+        Self {
+            tx: Arc::new(watch::channel(settings).0),
+        }
+    }
+
+    pub fn update(&self, settings: DriftSettings) {
+        self.tx.send_replace(settings);
+    }
+}
+
+/// Polls every site on the schedule's interval, emitting `config:drift` for
+/// each site whose config moved on since the last poll. A settings change
+/// restarts the wait.
+pub fn spawn_scheduler(app: AppHandle, schedule: &DriftSchedule) {
+    let mut settings_rx = schedule.tx.subscribe();
+    tauri::async_runtime::spawn(async move {
+        loop {
+            let settings = settings_rx.borrow_and_update().clone();
+            if !settings.enabled {
+                if settings_rx.changed().await.is_err() {
+                    break;
+                }
+                continue;
+            }
+
+            let interval =
+                Duration::from_secs(settings.interval_minutes.max(MIN_INTERVAL_MINUTES) * 60);
+            tokio::select! {
+                _ = tokio::time::sleep(interval) => poll_sites(&app).await,
+                changed = settings_rx.changed() => {
+                    if changed.is_err() {
+                        break;
+                    }
+                }
+            }
+        }
+    });
+}
+
+async fn poll_sites(app: &AppHandle) {
+    let site_manager = app.state::<Arc<Mutex<SiteManager>>>();
+    // Sites waiting to be paired again would only fail the same way.
+    let site_ids: Vec<String> = {
+        let manager = site_manager.lock().await;
+        manager
+            .list_sites()
+            .iter()
+            .filter(|s| !s.needs_reauth)
+            .map(|s| s.id.clone())
+            .collect()
+    };
+
+    // The request is made without the lock, so a slow site does not hold
+    // up commands; the lock is only taken to record the result.
+    for site_id in site_ids {
+        let client = match site_manager.lock().await.site_client(&site_id) {
+            Ok(client) => client,
+            Err(e) => {
+                debug!("Skipping drift check for site {}: {}", site_id, e);
+                continue;
+            }
+        };
+        let response = client.config_hash().await;
+
+        let mut manager = site_manager.lock().await;
+        let result = match response {
+            Ok(response) => manager.record_config_hash(&site_id, &response).await,
+            Err(e) => Err(e),
+        };
+        match result {
+            Ok(Some(drift)) => {
+                let payload = DriftPayload {
+                    site_id: &site_id,
+                    drift: &drift,
+                    summary: drift.summary(),
+                };
+                tracing::info!("Config drift on site {}: {}", site_id, payload.summary);
+                app.emit("config:drift", &payload).ok();
+            }
+            Ok(None) => {}
+            Err(e) => {
+                debug!("Drift check failed for site {}: {}", site_id, e);
+                reauth::note_error(app, &mut manager, &site_id, &e).await;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn components(theme: &str, woo_active: bool) -> ConfigHashComponents {
+        ConfigHashComponents {
+            plugins_hash: "plugins".to_string(),
+            theme_hash: theme.to_string(),
+            agents_hash: "agents".to_string(),
+            providers_hash: "providers".to_string(),
+            woo_active,
+        }
+    }
+
+    fn remote(components: ConfigHashComponents) -> ConfigHashResponse {
+        ConfigHashResponse {
+            hash: "remote-hash".to_string(),
+            components,
+            generated: 0,
+        }
+    }
+
+    #[test]
+    fn between_splits_changed_and_unchanged_components() {
+        let baseline = components("old-theme", false);
+        let drift = ConfigDrift::between(Some(&baseline), &remote(components("new-theme", true)));
+
+        assert_eq!(drift.remote_hash, "remote-hash");
+        assert_eq!(drift.changed, ["theme", "woocommerce"]);
+        assert_eq!(drift.unchanged, ["plugins", "agents", "providers"]);
+        assert_eq!(
+            drift.summary(),
+            "theme changed, woocommerce changed, plugins unchanged, agents unchanged, providers unchanged"
+        );
+    }
+
+    #[test]
+    fn summary_reads_theme_changed_agents_unchanged() {
+        let drift = ConfigDrift {
+            remote_hash: "remote-hash".to_string(),
+            changed: vec!["theme".to_string()],
+            unchanged: vec!["agents".to_string()],
+            detected_at: 0,
+        };
+        assert_eq!(drift.summary(), "theme changed, agents unchanged");
+    }
+
+    #[test]
+    fn drift_without_baseline_has_no_components() {
+        let drift = ConfigDrift::between(None, &remote(components("theme", false)));
+        assert!(drift.changed.is_empty());
+        assert!(drift.unchanged.is_empty());
+        assert_eq!(drift.summary(), "config changed");
+    }
+}
@@ -0,0 +1,419 @@
+use crate::sites::WordPressSite;
+use reqwest::{Client, StatusCode};
+use serde::Serialize;
+use std::error::Error as _;
+use std::time::{Duration, Instant};
+
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Decides which HTTP statuses count as a pass for a check.
+type Accept = fn(StatusCode) -> bool;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CheckStatus {
+    Pass,
+    Fail,
+    /// Not attempted because an earlier check it depends on failed.
+    Skipped,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct HealthCheck {
+    pub name: &'static str,
+    pub target: String,
+    pub status: CheckStatus,
+    pub latency_ms: Option<u64>,
+    pub http_status: Option<u16>,
+    pub cause: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SiteHealthReport {
+    pub site_id: String,
+    pub checked_at: u64,
+    pub healthy: bool,
+    pub checks: Vec<HealthCheck>,
+}
+
+impl HealthCheck {
+    fn new(name: &'static str, target: impl Into<String>) -> Self {
+        Self {
+            name,
+            target: target.into(),
+            status: CheckStatus::Skipped,
+            latency_ms: None,
+            http_status: None,
+            cause: None,
+        }
+    }
+
+    fn skipped(mut self, cause: &str) -> Self {
+        self.cause = Some(cause.to_string());
+        self
+    }
+
+    fn passed(&self) -> bool {
+        self.status == CheckStatus::Pass
+    }
+}
+
+/// Names the part of the connection that failed, which reqwest only
+/// exposes through the error's source chain.
+fn describe_request_error(e: &reqwest::Error) -> String {
+    let mut detail = e.to_string();
+    let mut source = e.source();
+    while let Some(inner) = source {
+        detail = format!("{}: {}", detail, inner);
+        source = inner.source();
+    }
+
+    let lower = detail.to_lowercase();
+    if e.is_timeout() {
+        format!("Timed out after {}s", REQUEST_TIMEOUT.as_secs())
+    } else if lower.contains("certificate") || lower.contains("tls") || lower.contains("ssl") {
+        format!("TLS handshake failed: {}", detail)
+    } else if lower.contains("dns") || lower.contains("resolve") {
+        format!("Name resolution failed: {}", detail)
+    } else if e.is_connect() {
+        format!("Connection failed: {}", detail)
+    } else {
+        detail
+    }
+}
+
+fn describe_status(status: StatusCode) -> String {
+    match status.as_u16() {
+        401 | 403 => format!("HTTP {}: credentials were rejected", status.as_u16()),
+        404 => "HTTP 404: endpoint not found (is the WordForge plugin active?)".to_string(),
+        500..=599 => format!("HTTP {}: the site reported a server error", status.as_u16()),
+        _ => format!("HTTP {}", status),
+    }
+}
+
+/// Sends a GET and records the outcome.
+async fn probe(
+    client: &Client,
+    mut check: HealthCheck,
+    auth: Option<&str>,
+    accept: Accept,
+) -> HealthCheck {
+    let mut request = client.get(&check.target).timeout(REQUEST_TIMEOUT);
+    if let Some(auth) = auth {
+        request = request.header("Authorization", format!("Basic {}", auth));
+    }
+
+    let started = Instant::now();
+    let result = request.send().await;
+    check.latency_ms = Some(started.elapsed().as_millis() as u64);
+
+    match result {
+        Ok(response) => {
+            let status = response.status();
+            check.http_status = Some(status.as_u16());
+            if accept(status) {
+                check.status = CheckStatus::Pass;
+            } else {
+                check.status = CheckStatus::Fail;
+                check.cause = Some(describe_status(status));
+            }
+        }
+        Err(e) => {
+            check.status = CheckStatus::Fail;
+            check.cause = Some(describe_request_error(&e));
+        }
+    }
+    check
+}
+
+async fn resolve(url: &str) -> HealthCheck {
+    let mut check = HealthCheck::new("dns", url);
+    let parsed = match url::Url::parse(url) {
+        Ok(parsed) => parsed,
+        Err(e) => {
+            check.status = CheckStatus::Fail;
+            check.cause = Some(format!("Invalid URL: {}", e));
+            return check;
+        }
+    };
+    let (Some(host), Some(port)) = (parsed.host_str(), parsed.port_or_known_default()) else {
+        check.status = CheckStatus::Fail;
+        check.cause = Some("URL has no host".to_string());
+        return check;
+    };
+    check.target = host.to_string();
+
+    let started = Instant::now();
+    let result = tokio::time::timeout(REQUEST_TIMEOUT, tokio::net::lookup_host((host, port))).await;
+    check.latency_ms = Some(started.elapsed().as_millis() as u64);
+
+    match result.map(|lookup| lookup.map(|mut addrs| addrs.next().is_some())) {
+        Ok(Ok(true)) => check.status = CheckStatus::Pass,
+        Ok(Ok(false)) => {
+            check.status = CheckStatus::Fail;
+            check.cause = Some(format!("{} has no addresses", host));
+        }
+        Ok(Err(e)) => {
+            check.status = CheckStatus::Fail;
+            check.cause = Some(format!("Name resolution failed: {}", e));
+        }
+        Err(_) => {
+            check.status = CheckStatus::Fail;
+            check.cause = Some(format!(
+                "Name resolution timed out after {}s",
+                REQUEST_TIMEOUT.as_secs()
+            ));
+        }
+    }
+    check
+}
+
+fn success(status: StatusCode) -> bool {
+    status.is_success()
+}
+
+/// Any answer short of "not here" or a server error. MCP endpoints only
+/// speak JSON-RPC over POST, so a plain GET often gets 405 or 400 back.
+fn responds(status: StatusCode) -> bool {
+    status != StatusCode::NOT_FOUND && !status.is_server_error()
+}
+
+/// Runs every check against `site` in dependency order: name resolution,
+/// then plain HTTP(S), then the REST API, then everything that needs the
+/// stored credentials.
+pub async fn check_site(client: &Client, site: &WordPressSite) -> SiteHealthReport {
+    let base = site.url.trim_end_matches('/');
+    let mut checks = Vec::new();
+
+    let dns = resolve(&site.url).await;
+    let reachable = if dns.passed() {
+        let check = probe(client, HealthCheck::new("http", &site.url), None, |s| {
+            !s.is_server_error()
+        })
+        .await;
+        let passed = check.passed();
+        checks.extend([dns, check]);
+        passed
+    } else {
+        checks.extend([
+            dns,
+            HealthCheck::new("http", &site.url).skipped("Host name did not resolve"),
+        ]);
+        false
+    };
+
+    let rest = if reachable {
+        probe(
+            client,
+            HealthCheck::new("rest", &site.rest_url),
+            None,
+            success,
+        )
+        .await
+    } else {
+        HealthCheck::new("rest", &site.rest_url).skipped("Site is unreachable")
+    };
+    let rest_ok = rest.passed();
+    checks.push(rest);
+
+    let authenticated: [(&'static str, String, Accept); 5] = [
+        ("auth", format!("{}/wp-json/wp/v2/users/me", base), success),
+        ("mcp", site.mcp_endpoint.clone(), responds),
+        ("abilities", site.abilities_url.clone(), success),
+        (
+            "config_hash",
+            format!("{}/wp-json/wordforge/v1/desktop/config-hash", base),
+            success,
+        ),
+        (
+            "local_settings",
+            format!("{}/wp-json/wordforge/v1/opencode/local-settings", base),
+            success,
+        ),
+    ];
+
+    let skip_reason = if !rest_ok {
+        Some("REST API is unavailable")
+    } else if site.auth.is_empty() {
+        Some("Credentials are locked in the vault")
+    } else {
+        None
+    };
+
+    let mut auth_ok = true;
+    for (name, target, accept) in authenticated {
+        let check = HealthCheck::new(name, target);
+        let check = match skip_reason {
+            Some(reason) => check.skipped(reason),
+            None if !auth_ok => check.skipped("Credentials were rejected"),
+            None => probe(client, check, Some(&site.auth), accept).await,
+        };
+        if name == "auth" {
+            auth_ok = check.passed();
+        }
+        checks.push(check);
+    }
+
+    SiteHealthReport {
+        site_id: site.id.clone(),
+        checked_at: std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0),
+        healthy: checks.iter().all(HealthCheck::passed),
+        checks,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use std::collections::HashMap;
+
+    fn site(url: &str, auth: &str) -> WordPressSite {
+        serde_json::from_value(json!({
+            "id": "site",
+            "name": "Site",
+            "url": url,
+            "rest_url": format!("{}/wp-json", url),
+            "mcp_endpoint": format!("{}/wp-json/mcp/mcp-adapter-default-server", url),
+            "abilities_url": format!("{}/wp-json/wp-abilities/v1/abilities", url),
+            "username": "admin",
+            "auth": auth,
+            "project_dir": "unused",
+            "created_at": 0,
+            "last_used_at": 0,
+        }))
+        .unwrap()
+    }
+
+    /// Serves every request with the status listed for its path, 200 for
+    /// paths not listed, and returns the base URL to connect to.
+    async fn site_stub(statuses: &[(&str, u16)]) -> String {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let statuses: HashMap<String, u16> = statuses
+            .iter()
+            .map(|(path, status)| (path.to_string(), *status))
+            .collect();
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let base_url = format!("http://{}", listener.local_addr().unwrap());
+
+        tokio::spawn(async move {
+            while let Ok((mut socket, _)) = listener.accept().await {
+                let statuses = statuses.clone();
+                tokio::spawn(async move {
+                    let mut request = Vec::new();
+                    let mut buf = [0u8; 4096];
+                    while !String::from_utf8_lossy(&request).contains("\r\n\r\n") {
+                        match socket.read(&mut buf).await {
+                            Ok(0) | Err(_) => return,
+                            Ok(n) => request.extend_from_slice(&buf[..n]),
+                        }
+                    }
+                    let request = String::from_utf8_lossy(&request).to_string();
+                    let path = request.split_whitespace().nth(1).unwrap_or("/");
+                    let status = statuses.get(path).copied().unwrap_or(200);
+                    let response = format!(
+                        "HTTP/1.1 {} Stub\r\nContent-Length: 2\r\nConnection: close\r\n\r\n{{}}",
+                        status
+                    );
+                    socket.write_all(response.as_bytes()).await.ok();
+                });
+            }
+        });
+
+        base_url
+    }
+
+    fn check<'a>(report: &'a SiteHealthReport, name: &str) -> &'a HealthCheck {
+        report.checks.iter().find(|c| c.name == name).unwrap()
+    }
+
+    #[tokio::test]
+    async fn healthy_site_passes_every_check() {
+        let base_url = site_stub(&[]).await;
+        let report = check_site(&Client::new(), &site(&base_url, "auth")).await;
+
+        assert!(report.healthy, "{:?}", report.checks);
+        assert_eq!(report.checks.len(), 8);
+    }
+
+    #[tokio::test]
+    async fn unresolvable_host_skips_everything_after_dns() {
+        let report = check_site(
+            &Client::new(),
+            &site("http://wordforge-health-check.invalid", "auth"),
+        )
+        .await;
+
+        assert!(!report.healthy);
+        assert_eq!(check(&report, "dns").status, CheckStatus::Fail);
+        let http = check(&report, "http");
+        assert_eq!(http.status, CheckStatus::Skipped);
+        assert_eq!(http.cause.as_deref(), Some("Host name did not resolve"));
+        assert_eq!(
+            check(&report, "rest").cause.as_deref(),
+            Some("Site is unreachable")
+        );
+        assert_eq!(
+            check(&report, "auth").cause.as_deref(),
+            Some("REST API is unavailable")
+        );
+    }
+
+    #[tokio::test]
+    async fn rejected_credentials_skip_the_dependent_checks() {
+        let base_url = site_stub(&[("/wp-json/wp/v2/users/me", 401)]).await;
+        let report = check_site(&Client::new(), &site(&base_url, "stale")).await;
+
+        let auth = check(&report, "auth");
+        assert_eq!(auth.status, CheckStatus::Fail);
+        assert_eq!(auth.http_status, Some(401));
+        for name in ["mcp", "abilities", "config_hash", "local_settings"] {
+            let dependent = check(&report, name);
+            assert_eq!(dependent.status, CheckStatus::Skipped, "{}", name);
+            assert_eq!(
+                dependent.cause.as_deref(),
+                Some("Credentials were rejected")
+            );
+        }
+        assert_eq!(check(&report, "rest").status, CheckStatus::Pass);
+    }
+
+    #[tokio::test]
+    async fn locked_vault_skips_the_authenticated_checks() {
+        let base_url = site_stub(&[]).await;
+        let report = check_site(&Client::new(), &site(&base_url, "")).await;
+
+        assert_eq!(check(&report, "rest").status, CheckStatus::Pass);
+        for name in ["auth", "mcp", "abilities", "config_hash", "local_settings"] {
+            let skipped = check(&report, name);
+            assert_eq!(skipped.status, CheckStatus::Skipped, "{}", name);
+            assert_eq!(
+                skipped.cause.as_deref(),
+                Some("Credentials are locked in the vault")
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn mcp_passes_on_any_answer_but_not_found_or_server_error() {
+        let mcp_path = "/wp-json/mcp/mcp-adapter-default-server";
+        for (status, expected) in [
+            (405, CheckStatus::Pass),
+            (400, CheckStatus::Pass),
+            (200, CheckStatus::Pass),
+            (404, CheckStatus::Fail),
+            (502, CheckStatus::Fail),
+        ] {
+            let base_url = site_stub(&[(mcp_path, status)]).await;
+            let report = check_site(&Client::new(), &site(&base_url, "auth")).await;
+
+            let mcp = check(&report, "mcp");
+            assert_eq!(mcp.status, expected, "HTTP {}", status);
+            assert_eq!(mcp.http_status, Some(status));
+        }
+    }
+}
@@ -0,0 +1,415 @@
+use futures_util::StreamExt;
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tauri::{AppHandle, Emitter, Manager};
+use tokio::sync::watch;
+use tracing::{debug, info};
+
+const DEFAULT_TIMEOUT_MINUTES: u64 = 30;
+const CHECK_INTERVAL: Duration = Duration::from_secs(15);
+const WARNING_LEAD: Duration = Duration::from_secs(120);
+const RECONNECT_DELAY: Duration = Duration::from_secs(5);
+
+/// When a site's OpenCode server is shut down for being idle.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "mode", rename_all = "snake_case")]
+pub enum IdlePolicy {
+    /// After `minutes` without activity.
+    Timeout {
+        minutes: u64,
+    },
+    Never,
+    /// After `minutes` without activity, counted only while no OpenCode
+    /// window of the site is open.
+    NoWindowOpen {
+        minutes: u64,
+    },
+}
+
+impl IdlePolicy {
+    /// How long the server may sit idle, or `None` if it is never shut down.
+    fn timeout(&self) -> Option<Duration> {
+        match self {
+            IdlePolicy::Never => None,
+            IdlePolicy::Timeout { minutes } | IdlePolicy::NoWindowOpen { minutes } => {
+                Some(Duration::from_secs((*minutes).max(1) * 60))
+            }
+        }
+    }
+
+    /// Whether idle time is being counted right now.
+    fn counts(&self, window_open: bool) -> bool {
+        match self {
+            IdlePolicy::Never => false,
+            IdlePolicy::Timeout { .. } => true,
+            IdlePolicy::NoWindowOpen { .. } => !window_open,
+        }
+    }
+}
+
+impl Default for IdlePolicy {
+    fn default() -> Self {
+        IdlePolicy::Timeout {
+            minutes: DEFAULT_TIMEOUT_MINUTES,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IdleShutdownPayload {
+    pub site_id: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct IdleWarningPayload<'a> {
+    site_id: &'a str,
+    shutdown_in_secs: u64,
+}
+
+pub fn site_window_label(site_id: &str) -> String {
+    format!("opencode-{}", site_id)
+}
+
+struct Activity {
+    last: Instant,
+    /// Sessions OpenCode reports as busy, e.g. during a long tool call that
+    /// emits nothing else for a while.
+    busy: HashSet<String>,
+}
+
+impl Activity {
+    fn touch(&mut self) {
+        self.last = Instant::now();
+    }
+}
+
+/// Where a server stands in its idle countdown.
+#[derive(Debug, PartialEq, Eq)]
+enum Countdown {
+    Running,
+    Warning { shutdown_in: Duration },
+    Expired,
+}
+
+fn countdown(idle: Duration, timeout: Duration) -> Countdown {
+    let lead = WARNING_LEAD.min(timeout / 2);
+    if idle >= timeout {
+        Countdown::Expired
+    } else if idle + lead >= timeout {
+        Countdown::Warning {
+            shutdown_in: timeout - idle,
+        }
+    } else {
+        Countdown::Running
+    }
+}
+
+#[derive(Deserialize)]
+struct ServerEvent {
+    #[serde(rename = "type")]
+    kind: String,
+    #[serde(default)]
+    properties: serde_json::Value,
+}
+
+/// Watches a site's server for activity on its `/event` stream and emits
+/// `opencode:idle-warning` ahead of `opencode:idle-shutdown` according to
+/// the site's policy. Activity after a warning emits
+/// `opencode:idle-warning-cleared`.
+pub fn spawn_monitor(
+    app: AppHandle,
+    client: Client,
+    site_id: String,
+    port: u16,
+    mut policy_rx: watch::Receiver<IdlePolicy>,
+    mut stop_rx: watch::Receiver<bool>,
+) {
+    let activity = Arc::new(Mutex::new(Activity {
+        last: Instant::now(),
+        busy: HashSet::new(),
+    }));
+    let events = tokio::spawn(follow_events(client, port, activity.clone()));
+
+    tokio::spawn(async move {
+        info!("Idle monitor started for site {} on port {}", site_id, port);
+        let mut interval = tokio::time::interval(CHECK_INTERVAL);
+        let mut warned = false;
+
+        loop {
+            tokio::select! {
+                _ = interval.tick() => {}
+                changed = policy_rx.changed() => {
+                    if changed.is_err() {
+                        break;
+                    }
+                }
+                _ = stop_rx.changed() => break,
+            }
+
+            let policy = policy_rx.borrow().clone();
+            let window_open = app
+                .get_webview_window(&site_window_label(&site_id))
+                .is_some();
+            let idle = {
+                let Ok(mut activity) = activity.lock() else {
+                    break;
+                };
+                if !policy.counts(window_open) || !activity.busy.is_empty() {
+                    activity.touch();
+                }
+                activity.last.elapsed()
+            };
+
+            let Some(timeout) = policy.timeout() else {
+                continue;
+            };
+
+            match countdown(idle, timeout) {
+                Countdown::Expired => {
+                    info!(
+                        "OpenCode for site {} idle for {}s, requesting shutdown",
+                        site_id,
+                        idle.as_secs()
+                    );
+                    app.emit(
+                        "opencode:idle-shutdown",
+                        IdleShutdownPayload {
+                            site_id: site_id.clone(),
+                        },
+                    )
+                    .ok();
+                    break;
+                }
+                Countdown::Warning { shutdown_in } => {
+                    if !warned {
+                        warned = true;
+                        app.emit(
+                            "opencode:idle-warning",
+                            IdleWarningPayload {
+                                site_id: &site_id,
+                                shutdown_in_secs: shutdown_in.as_secs(),
+                            },
+                        )
+                        .ok();
+                    }
+                }
+                Countdown::Running => {
+                    if warned {
+                        warned = false;
+                        app.emit(
+                            "opencode:idle-warning-cleared",
+                            IdleShutdownPayload {
+                                site_id: site_id.clone(),
+                            },
+                        )
+                        .ok();
+                    }
+                }
+            }
+        }
+
+        events.abort();
+        info!("Idle monitor stopped for site {}", site_id);
+    });
+}
+
+/// Follows the server's event stream, reconnecting whenever it drops.
+async fn follow_events(client: Client, port: u16, activity: Arc<Mutex<Activity>>) {
+    let url = format!("http://localhost:{}/event", port);
+
+    loop {
+        match client.get(&url).send().await {
+            Ok(response) => {
+                // Sessions that were busy before a reconnect may never report
+                // going idle, e.g. when the server restarted in between.
+                if let Ok(mut activity) = activity.lock() {
+                    activity.busy.clear();
+                }
+                let mut stream = response.bytes_stream();
+                let mut buffer = String::new();
+
+                while let Some(Ok(chunk)) = stream.next().await {
+                    buffer.push_str(&String::from_utf8_lossy(&chunk));
+                    while let Some(end) = buffer.find('\n') {
+                        let line: String = buffer.drain(..=end).collect();
+                        if let Some(data) = line.trim_end().strip_prefix("data:") {
+                            record_event(&activity, data.trim());
+                        }
+                    }
+                }
+            }
+            Err(e) => debug!("Event stream on port {} unavailable: {}", port, e),
+        }
+
+        tokio::time::sleep(RECONNECT_DELAY).await;
+    }
+}
+
+fn record_event(activity: &Mutex<Activity>, data: &str) {
+    let Ok(event) = serde_json::from_str::<ServerEvent>(data) else {
+        return;
+    };
+    let Ok(mut activity) = activity.lock() else {
+        return;
+    };
+
+    let session_id = event
+        .properties
+        .get("sessionID")
+        .and_then(|v| v.as_str())
+        .map(String::from);
+
+    match event.kind.as_str() {
+        "server.connected" => {
+            activity.busy.clear();
+            return;
+        }
+        "server.heartbeat" => return,
+        "session.status" => {
+            let busy = event
+                .properties
+                .pointer("/status/type")
+                .and_then(|v| v.as_str())
+                .is_some_and(|status| status != "idle");
+            if let Some(session_id) = session_id {
+                if busy {
+                    activity.busy.insert(session_id);
+                } else {
+                    activity.busy.remove(&session_id);
+                }
+            }
+        }
+        "session.idle" => {
+            if let Some(session_id) = session_id {
+                activity.busy.remove(&session_id);
+            }
+        }
+        _ => {}
+    }
+
+    activity.touch();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stale_activity() -> Mutex<Activity> {
+        Mutex::new(Activity {
+            last: Instant::now() - Duration::from_secs(60),
+            busy: HashSet::new(),
+        })
+    }
+
+    fn idle_for(activity: &Mutex<Activity>) -> Duration {
+        activity.lock().unwrap().last.elapsed()
+    }
+
+    fn busy(activity: &Mutex<Activity>) -> Vec<String> {
+        let mut busy: Vec<String> = activity.lock().unwrap().busy.iter().cloned().collect();
+        busy.sort();
+        busy
+    }
+
+    #[test]
+    fn session_status_tracks_busy_sessions() {
+        let activity = stale_activity();
+        record_event(
+            &activity,
+            r#"{"type":"session.status","properties":{"sessionID":"s1","status":{"type":"busy"}}}"#,
+        );
+        record_event(
+            &activity,
+            r#"{"type":"session.status","properties":{"sessionID":"s2","status":{"type":"retry"}}}"#,
+        );
+        assert_eq!(busy(&activity), ["s1", "s2"]);
+        assert!(idle_for(&activity) < Duration::from_secs(5));
+
+        record_event(
+            &activity,
+            r#"{"type":"session.status","properties":{"sessionID":"s1","status":{"type":"idle"}}}"#,
+        );
+        record_event(
+            &activity,
+            r#"{"type":"session.idle","properties":{"sessionID":"s2"}}"#,
+        );
+        assert!(busy(&activity).is_empty());
+    }
+
+    #[test]
+    fn heartbeats_and_garbage_are_not_activity() {
+        let activity = stale_activity();
+        record_event(&activity, r#"{"type":"server.heartbeat","properties":{}}"#);
+        record_event(&activity, "not json");
+        assert!(idle_for(&activity) >= Duration::from_secs(60));
+
+        record_event(
+            &activity,
+            r#"{"type":"message.part.updated","properties":{}}"#,
+        );
+        assert!(idle_for(&activity) < Duration::from_secs(5));
+    }
+
+    #[test]
+    fn server_connected_forgets_busy_sessions() {
+        let activity = stale_activity();
+        record_event(
+            &activity,
+            r#"{"type":"session.status","properties":{"sessionID":"s1","status":{"type":"busy"}}}"#,
+        );
+        record_event(&activity, r#"{"type":"server.connected","properties":{}}"#);
+        assert!(busy(&activity).is_empty());
+    }
+
+    #[test]
+    fn countdown_warns_ahead_of_shutdown() {
+        let timeout = Duration::from_secs(30 * 60);
+        let minutes = |m: u64| Duration::from_secs(m * 60);
+
+        assert_eq!(countdown(minutes(10), timeout), Countdown::Running);
+        assert_eq!(
+            countdown(minutes(28), timeout),
+            Countdown::Warning {
+                shutdown_in: minutes(2)
+            }
+        );
+        assert_eq!(
+            countdown(minutes(29), timeout),
+            Countdown::Warning {
+                shutdown_in: minutes(1)
+            }
+        );
+        assert_eq!(countdown(minutes(30), timeout), Countdown::Expired);
+    }
+
+    #[test]
+    fn short_timeouts_warn_halfway() {
+        let timeout = IdlePolicy::Timeout { minutes: 0 }.timeout().unwrap();
+        assert_eq!(timeout, Duration::from_secs(60));
+        assert_eq!(
+            countdown(Duration::from_secs(29), timeout),
+            Countdown::Running
+        );
+        assert_eq!(
+            countdown(Duration::from_secs(30), timeout),
+            Countdown::Warning {
+                shutdown_in: Duration::from_secs(30)
+            }
+        );
+    }
+
+    #[test]
+    fn policies_decide_when_idle_time_counts() {
+        assert_eq!(IdlePolicy::Never.timeout(), None);
+        assert!(!IdlePolicy::Never.counts(false));
+        assert!(IdlePolicy::default().counts(true));
+
+        let no_window = IdlePolicy::NoWindowOpen { minutes: 5 };
+        assert!(no_window.counts(false));
+        assert!(!no_window.counts(true));
+        assert_eq!(no_window.timeout(), Some(Duration::from_secs(300)));
+    }
+}
@@ -0,0 +1,291 @@
+use crate::persist;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use tracing::{info, warn};
+
+const METADATA_FILE: &str = "installs.json";
+const VERSIONS_DIR: &str = "versions";
+const STAGING_DIR: &str = "staging";
+const BACKUP_DIR: &str = "backup";
+const LEGACY_VERSION_FILE: &str = ".version";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct InstalledVersion {
+    pub version: String,
+    pub tag: String,
+    pub installed_at: u64,
+    /// SHA-256 of the release archive, verified before extraction.
+    #[serde(default)]
+    pub sha256: Option<String>,
+}
+
+/// Tracks every OpenCode version installed side by side under `versions/`,
+/// which one is active and which one to fall back to on rollback.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct InstallMetadata {
+    #[serde(default)]
+    pub active: Option<String>,
+    #[serde(default)]
+    pub previous: Option<String>,
+    #[serde(default)]
+    pub pinned: bool,
+    /// Freshly installed version that has not started successfully yet.
+    #[serde(default)]
+    pub unverified: Option<String>,
+    #[serde(default)]
+    pub versions: BTreeMap<String, InstalledVersion>,
+}
+
+impl InstallMetadata {
+    pub fn load(install_dir: &Path) -> Self {
+        let path = install_dir.join(METADATA_FILE);
+
+        match std::fs::read_to_string(&path) {
+            Ok(content) => serde_json::from_str(&content).unwrap_or_else(|e| {
+                warn!(
+                    "Failed to parse {:?}, starting from empty metadata: {}",
+                    path, e
+                );
+                Self::default()
+            }),
+            Err(_) => Self::migrate_legacy_install(install_dir),
+        }
+    }
+
+    pub async fn save(&self, install_dir: &Path) -> Result<(), std::io::Error> {
+        let content = serde_json::to_string_pretty(self)?;
+        persist::write_atomic(&install_dir.join(METADATA_FILE), content.as_bytes())
+    }
+
+    pub fn register(&mut self, version: &str, tag: &str, sha256: Option<&str>) {
+        let installed_at = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0);
+
+        self.versions.insert(
+            version.to_string(),
+            InstalledVersion {
+                version: version.to_string(),
+                tag: tag.to_string(),
+                installed_at,
+                sha256: sha256.map(String::from),
+            },
+        );
+    }
+
+    /// Makes `version` the active install, remembering the current one as the
+    /// rollback target.
+    pub fn activate(&mut self, version: &str) {
+        if self.active.as_deref() == Some(version) {
+            return;
+        }
+        self.previous = self.active.take();
+        self.active = Some(version.to_string());
+    }
+
+    /// Makes `version` the active install and pins it or releases the pin.
+    pub fn select(&mut self, version: &str, pinned: bool) {
+        self.activate(version);
+        self.pinned = pinned;
+    }
+
+    /// Swaps the active and previous versions. Returns the newly active version.
+    pub fn rollback(&mut self) -> Option<String> {
+        let previous = self
+            .previous
+            .clone()
+            .filter(|v| self.versions.contains_key(v))?;
+        self.previous = self.active.take();
+        self.active = Some(previous.clone());
+        Some(previous)
+    }
+
+    pub fn is_installed(&self, version: &str) -> bool {
+        self.versions.contains_key(version)
+    }
+
+    /// Installs made before versioned directories existed put the binary
+    /// straight into `install_dir`. Move it under `versions/` so it shows up
+    /// as a regular, rollback-able install.
+    fn migrate_legacy_install(install_dir: &Path) -> Self {
+        let mut metadata = Self::default();
+        let legacy_binary = install_dir.join(binary_name());
+        if !legacy_binary.exists() {
+            return metadata;
+        }
+
+        let version_file = install_dir.join(LEGACY_VERSION_FILE);
+        let version = std::fs::read_to_string(&version_file)
+            .map(|v| normalize_version(v.trim()))
+            .ok()
+            .filter(|v| !v.is_empty())
+            .unwrap_or_else(|| "unknown".to_string());
+
+        let target_dir = version_dir(install_dir, &version);
+        let migrated = std::fs::create_dir_all(&target_dir)
+            .and_then(|_| std::fs::rename(&legacy_binary, target_dir.join(binary_name())));
+
+        if let Err(e) = migrated {
+            warn!("Failed to migrate legacy OpenCode install: {}", e);
+            return metadata;
+        }
+
+        std::fs::remove_file(&version_file).ok();
+        metadata.register(&version, &format!("v{}", version), None);
+        metadata.activate(&version);
+
+        if let Ok(content) = serde_json::to_string_pretty(&metadata) {
+            if let Err(e) =
+                persist::write_atomic(&install_dir.join(METADATA_FILE), content.as_bytes())
+            {
+                warn!("Failed to persist migrated install metadata: {}", e);
+            }
+        }
+
+        info!("Migrated legacy OpenCode install to version {}", version);
+        metadata
+    }
+}
+
+pub fn normalize_version(tag: &str) -> String {
+    tag.trim().trim_start_matches('v').to_string()
+}
+
+pub fn version_dir(install_dir: &Path, version: &str) -> PathBuf {
+    install_dir.join(VERSIONS_DIR).join(version)
+}
+
+pub fn staging_dir(install_dir: &Path, version: &str) -> PathBuf {
+    install_dir.join(STAGING_DIR).join(version)
+}
+
+pub fn backup_dir(install_dir: &Path, version: &str) -> PathBuf {
+    install_dir.join(BACKUP_DIR).join(version)
+}
+
+pub fn binary_name() -> &'static str {
+    #[cfg(target_os = "windows")]
+    let name = "opencode.exe";
+    #[cfg(not(target_os = "windows"))]
+    let name = "opencode";
+
+    name
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_dir() -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("wordforge-installs-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn with_versions(versions: &[&str]) -> InstallMetadata {
+        let mut metadata = InstallMetadata::default();
+        for version in versions {
+            metadata.register(version, &format!("v{}", version), None);
+        }
+        metadata
+    }
+
+    #[test]
+    fn activate_remembers_the_version_it_replaces() {
+        let mut metadata = with_versions(&["1.0.0", "1.1.0"]);
+        metadata.activate("1.0.0");
+        assert_eq!(metadata.previous, None);
+
+        metadata.activate("1.1.0");
+        metadata.activate("1.1.0");
+        assert_eq!(metadata.active.as_deref(), Some("1.1.0"));
+        assert_eq!(metadata.previous.as_deref(), Some("1.0.0"));
+    }
+
+    #[test]
+    fn rollback_swaps_active_and_previous() {
+        let mut metadata = with_versions(&["1.0.0", "1.1.0"]);
+        metadata.activate("1.0.0");
+        metadata.activate("1.1.0");
+
+        assert_eq!(metadata.rollback().as_deref(), Some("1.0.0"));
+        assert_eq!(metadata.active.as_deref(), Some("1.0.0"));
+        assert_eq!(metadata.previous.as_deref(), Some("1.1.0"));
+
+        assert_eq!(metadata.rollback().as_deref(), Some("1.1.0"));
+        assert_eq!(metadata.active.as_deref(), Some("1.1.0"));
+    }
+
+    #[test]
+    fn rollback_needs_an_installed_previous_version() {
+        let mut metadata = with_versions(&["1.0.0"]);
+        metadata.activate("1.0.0");
+        assert_eq!(metadata.rollback(), None);
+
+        metadata.previous = Some("0.9.0".to_string());
+        assert_eq!(metadata.rollback(), None);
+        assert_eq!(metadata.active.as_deref(), Some("1.0.0"));
+    }
+
+    #[test]
+    fn select_sets_and_releases_the_pin() {
+        let mut metadata = with_versions(&["1.0.0", "1.1.0"]);
+        metadata.select("1.0.0", true);
+        assert!(metadata.pinned);
+        assert_eq!(metadata.active.as_deref(), Some("1.0.0"));
+
+        metadata.select("1.1.0", false);
+        assert!(!metadata.pinned);
+        assert_eq!(metadata.previous.as_deref(), Some("1.0.0"));
+    }
+
+    #[tokio::test]
+    async fn save_and_load_round_trip() {
+        let dir = temp_dir();
+        let mut metadata = with_versions(&["1.0.0", "1.1.0"]);
+        metadata.activate("1.0.0");
+        metadata.select("1.1.0", true);
+        metadata.save(&dir).await.unwrap();
+
+        let loaded = InstallMetadata::load(&dir);
+        assert_eq!(loaded.active.as_deref(), Some("1.1.0"));
+        assert_eq!(loaded.previous.as_deref(), Some("1.0.0"));
+        assert!(loaded.pinned);
+        assert!(loaded.is_installed("1.0.0"));
+
+        std::fs::remove_dir_all(dir).ok();
+    }
+
+    #[test]
+    fn migrates_legacy_install_into_versions() {
+        let dir = temp_dir();
+        std::fs::write(dir.join(binary_name()), b"binary").unwrap();
+        std::fs::write(dir.join(LEGACY_VERSION_FILE), "v0.9.1\n").unwrap();
+
+        let metadata = InstallMetadata::load(&dir);
+        assert_eq!(metadata.active.as_deref(), Some("0.9.1"));
+        assert_eq!(metadata.versions["0.9.1"].tag, "v0.9.1");
+        assert!(!dir.join(binary_name()).exists());
+        assert!(!dir.join(LEGACY_VERSION_FILE).exists());
+        assert!(version_dir(&dir, "0.9.1").join(binary_name()).exists());
+
+        let reloaded = InstallMetadata::load(&dir);
+        assert_eq!(reloaded.active.as_deref(), Some("0.9.1"));
+
+        std::fs::remove_dir_all(dir).ok();
+    }
+
+    #[test]
+    fn legacy_install_without_version_file_is_unknown() {
+        let dir = temp_dir();
+        std::fs::write(dir.join(binary_name()), b"binary").unwrap();
+
+        let metadata = InstallMetadata::load(&dir);
+        assert_eq!(metadata.active.as_deref(), Some("unknown"));
+        assert!(version_dir(&dir, "unknown").join(binary_name()).exists());
+
+        std::fs::remove_dir_all(dir).ok();
+    }
+}
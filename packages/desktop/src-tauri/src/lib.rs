@@ -1,16 +1,42 @@
+mod archive;
+mod config;
+mod drift;
+mod health;
+mod idle;
+mod installs;
+mod logs;
+mod manifest;
 mod opencode;
+mod persist;
+mod pidfile;
+mod presence;
+mod reauth;
 mod sites;
+mod snapshot;
 mod state;
+mod supervisor;
+mod vault;
 
-use opencode::GlobalConfig;
-use sites::{ConfigSyncStatus, SiteManager, WordPressSite};
+use config::{ConfigChange, ConfigSnapshot, GlobalConfig, HistorySettings};
+use drift::{DriftSchedule, DriftSettings};
+use health::SiteHealthReport;
+use idle::{IdlePolicy, IdleShutdownPayload};
+use installs::InstallMetadata;
+use logs::{LogEntry, LogFilter, LogStore};
+use manifest::{RefreshPlan, Resolution};
+use opencode::{DownloadCancel, ReleaseInfo, ServerInfo};
+use sites::{ConfigSyncStatus, SiteDevice, SiteManager, StoreRecovery, WordPressSite};
+use snapshot::SiteSnapshot;
 use state::AppState;
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
+use std::path::PathBuf;
 use std::sync::Arc;
+use supervisor::{RestartPolicy, ShutdownOutcome};
 use tauri::{Emitter, Listener, Manager, RunEvent};
 use tauri_plugin_deep_link::DeepLinkExt;
 use tokio::sync::Mutex;
 use tracing::info;
+use vault::VaultStatus;
 
 #[derive(Clone, serde::Serialize)]
 struct DeepLinkPayload {
@@ -18,6 +44,8 @@ struct DeepLinkPayload {
     site_url: String,
     token: String,
     name: String,
+    /// Sent by the site's re-pairing page for a site that is already connected.
+    reauthenticate: bool,
 }
 
 struct ProcessedTokens {
@@ -36,10 +64,70 @@ impl ProcessedTokens {
     }
 }
 
+fn resolve_site(manager: &SiteManager, site_id: &str) -> Result<WordPressSite, String> {
+    manager
+        .get_site(site_id)
+        .cloned()
+        .ok_or_else(|| "Site not found".to_string())
+}
+
+async fn start_site_server(
+    app: &tauri::AppHandle,
+    state: &Mutex<AppState>,
+    site_manager: &Mutex<SiteManager>,
+    site: &WordPressSite,
+) -> Result<u16, String> {
+    let port = {
+        let mut state = state.lock().await;
+        state
+            .start_opencode_with_config(
+                &site.id,
+                site.url.clone(),
+                site.project_dir.clone(),
+                site.idle_policy.clone(),
+            )
+            .await
+            .map_err(|e| e.to_string())?
+    };
+
+    // The site is told about the port without holding the lock.
+    let (device_id, client) = {
+        let mut manager = site_manager.lock().await;
+        (manager.get_device_id().await, manager.site_client(&site.id))
+    };
+    tracing::info!("Syncing port {} (device: {}) to WordPress", port, device_id);
+    let synced = match client {
+        Ok(client) => client.report_presence(&device_id, Some(port)).await,
+        Err(e) => Err(e),
+    };
+    if let Err(e) = synced {
+        tracing::warn!(
+            "Failed to sync port to WordPress for site {}: {}",
+            site.id,
+            e
+        );
+        let mut manager = site_manager.lock().await;
+        reauth::note_error(app, &mut manager, &site.id, &e).await;
+    }
+
+    Ok(port)
+}
+
 #[tauri::command]
-async fn get_status(state: tauri::State<'_, Arc<Mutex<AppState>>>) -> Result<opencode::Status, String> {
+async fn get_status(
+    state: tauri::State<'_, Arc<Mutex<AppState>>>,
+    site_manager: tauri::State<'_, Arc<Mutex<SiteManager>>>,
+    site_id: Option<String>,
+) -> Result<opencode::Status, String> {
+    // Without a site, only whether OpenCode is installed is reported.
+    let site_id = {
+        let manager = site_manager.lock().await;
+        site_id
+            .and_then(|id| resolve_site(&manager, &id).ok())
+            .map(|s| s.id)
+    };
     let state = state.lock().await;
-    Ok(state.get_status().await)
+    Ok(state.get_status(site_id.as_deref()).await)
 }
 
 #[tauri::command]
@@ -64,70 +152,308 @@ async fn download_opencode(
     state: tauri::State<'_, Arc<Mutex<AppState>>>,
 ) -> Result<(), String> {
     let mut state = state.lock().await;
-    state.download_opencode(&app).await.map_err(|e| e.to_string())
+    state
+        .download_opencode(&app)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+async fn cancel_opencode_download(
+    download_cancel: tauri::State<'_, DownloadCancel>,
+) -> Result<(), String> {
+    download_cancel.cancel();
+    Ok(())
+}
+
+#[tauri::command]
+async fn list_opencode_releases(
+    state: tauri::State<'_, Arc<Mutex<AppState>>>,
+) -> Result<Vec<ReleaseInfo>, String> {
+    let state = state.lock().await;
+    state
+        .list_opencode_releases()
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+async fn get_opencode_installs(
+    state: tauri::State<'_, Arc<Mutex<AppState>>>,
+) -> Result<InstallMetadata, String> {
+    let state = state.lock().await;
+    Ok(state.get_opencode_installs())
+}
+
+#[tauri::command]
+async fn install_opencode_version(
+    app: tauri::AppHandle,
+    state: tauri::State<'_, Arc<Mutex<AppState>>>,
+    tag: String,
+) -> Result<(), String> {
+    let mut state = state.lock().await;
+    state
+        .install_opencode_version(&app, &tag)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+async fn set_active_opencode_version(
+    state: tauri::State<'_, Arc<Mutex<AppState>>>,
+    version: String,
+    pinned: bool,
+) -> Result<(), String> {
+    let mut state = state.lock().await;
+    state
+        .set_active_opencode_version(&version, pinned)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+async fn rollback_opencode(
+    state: tauri::State<'_, Arc<Mutex<AppState>>>,
+) -> Result<String, String> {
+    let mut state = state.lock().await;
+    state.rollback_opencode().await.map_err(|e| e.to_string())
 }
 
 #[tauri::command]
 async fn start_opencode(
+    app: tauri::AppHandle,
     state: tauri::State<'_, Arc<Mutex<AppState>>>,
     site_manager: tauri::State<'_, Arc<Mutex<SiteManager>>>,
+    site_id: String,
 ) -> Result<u16, String> {
-    let mut state = state.lock().await;
-    let mut site_manager = site_manager.lock().await;
-    
-    let device_id = site_manager.get_device_id().await;
-    let active_site = site_manager.get_active_site().cloned();
-    
-    let cors_origin = active_site.as_ref().map(|s| s.url.clone());
-    let project_dir = active_site.as_ref().map(|s| s.project_dir.clone());
-    
-    let port = state.start_opencode_with_config(cors_origin, project_dir).await.map_err(|e| e.to_string())?;
-    
-    if let Some(site) = active_site {
-        if let Err(e) = site_manager.sync_port_to_wordpress(&site, port, &device_id).await {
-            tracing::warn!("Failed to sync port to WordPress: {}", e);
-        }
-    }
-    
-    Ok(port)
+    let site = {
+        let manager = site_manager.lock().await;
+        resolve_site(&manager, &site_id)?
+    };
+
+    start_site_server(&app, &state, &site_manager, &site).await
 }
 
 #[tauri::command]
 async fn stop_opencode(
+    app: tauri::AppHandle,
     state: tauri::State<'_, Arc<Mutex<AppState>>>,
-) -> Result<(), String> {
-    let mut state = state.lock().await;
-    state.stop_opencode().await.map_err(|e| e.to_string())
+    site_manager: tauri::State<'_, Arc<Mutex<SiteManager>>>,
+    site_id: String,
+) -> Result<Option<ShutdownOutcome>, String> {
+    let site = {
+        let manager = site_manager.lock().await;
+        resolve_site(&manager, &site_id)?
+    };
+    let outcome = {
+        let mut state = state.lock().await;
+        state
+            .stop_opencode(&site.id)
+            .await
+            .map_err(|e| e.to_string())?
+    };
+    presence::report_stopped(&app, &[site.id]).await;
+    Ok(outcome)
 }
 
 #[tauri::command]
 async fn get_opencode_port(
     state: tauri::State<'_, Arc<Mutex<AppState>>>,
+    site_manager: tauri::State<'_, Arc<Mutex<SiteManager>>>,
+    site_id: Option<String>,
 ) -> Result<Option<u16>, String> {
+    let site_id = {
+        let manager = site_manager.lock().await;
+        site_id
+            .and_then(|id| resolve_site(&manager, &id).ok())
+            .map(|s| s.id)
+    };
+    let state = state.lock().await;
+    Ok(site_id.and_then(|id| state.get_port(&id)))
+}
+
+#[tauri::command]
+async fn list_opencode_servers(
+    state: tauri::State<'_, Arc<Mutex<AppState>>>,
+) -> Result<Vec<ServerInfo>, String> {
     let state = state.lock().await;
-    Ok(state.get_port())
+    Ok(state.list_servers())
+}
+
+#[tauri::command]
+async fn get_opencode_logs(
+    logs: tauri::State<'_, Arc<LogStore>>,
+    site_manager: tauri::State<'_, Arc<Mutex<SiteManager>>>,
+    site_id: String,
+    limit: Option<usize>,
+) -> Result<Vec<LogEntry>, String> {
+    let site = {
+        let manager = site_manager.lock().await;
+        resolve_site(&manager, &site_id)?
+    };
+    Ok(logs.tail(&site.id, limit.unwrap_or(200)))
+}
+
+#[tauri::command]
+async fn search_opencode_logs(
+    logs: tauri::State<'_, Arc<LogStore>>,
+    site_manager: tauri::State<'_, Arc<Mutex<SiteManager>>>,
+    site_id: String,
+    filter: LogFilter,
+) -> Result<Vec<LogEntry>, String> {
+    let site = {
+        let manager = site_manager.lock().await;
+        resolve_site(&manager, &site_id)?
+    };
+    let logs = logs.inner().clone();
+    tokio::task::spawn_blocking(move || logs.search(&site.id, &filter))
+        .await
+        .map_err(|e| e.to_string())?
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+async fn export_opencode_logs(
+    logs: tauri::State<'_, Arc<LogStore>>,
+    site_manager: tauri::State<'_, Arc<Mutex<SiteManager>>>,
+    site_id: String,
+    from: Option<u64>,
+    to: Option<u64>,
+    destination: String,
+) -> Result<usize, String> {
+    let site = {
+        let manager = site_manager.lock().await;
+        resolve_site(&manager, &site_id)?
+    };
+    let logs = logs.inner().clone();
+    tokio::task::spawn_blocking(move || {
+        logs.export(&site.id, from, to, std::path::Path::new(&destination))
+    })
+    .await
+    .map_err(|e| e.to_string())?
+    .map_err(|e| e.to_string())
+}
+
+/// Reports, once, that the site store was unreadable at startup and whether
+/// it could be restored from a backup.
+#[tauri::command]
+async fn take_sites_store_recovery(
+    site_manager: tauri::State<'_, Arc<Mutex<SiteManager>>>,
+) -> Result<Option<StoreRecovery>, String> {
+    Ok(site_manager.lock().await.take_store_recovery())
+}
+
+#[tauri::command]
+async fn get_vault_status(
+    site_manager: tauri::State<'_, Arc<Mutex<SiteManager>>>,
+) -> Result<VaultStatus, String> {
+    Ok(site_manager.lock().await.vault_status())
+}
+
+#[tauri::command]
+async fn unlock_vault(
+    site_manager: tauri::State<'_, Arc<Mutex<SiteManager>>>,
+    passphrase: String,
+) -> Result<(), String> {
+    let mut manager = site_manager.lock().await;
+    manager
+        .unlock_vault(&passphrase)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+async fn lock_vault(
+    site_manager: tauri::State<'_, Arc<Mutex<SiteManager>>>,
+) -> Result<VaultStatus, String> {
+    let mut manager = site_manager.lock().await;
+    manager.lock_vault();
+    Ok(manager.vault_status())
+}
+
+/// Sets the vault passphrase, or hands the key to the OS keychain when
+/// `passphrase` is omitted.
+#[tauri::command]
+async fn set_vault_passphrase(
+    site_manager: tauri::State<'_, Arc<Mutex<SiteManager>>>,
+    passphrase: Option<String>,
+) -> Result<(), String> {
+    let mut manager = site_manager.lock().await;
+    manager
+        .set_vault_passphrase(passphrase.as_deref())
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+async fn set_site_idle_policy(
+    state: tauri::State<'_, Arc<Mutex<AppState>>>,
+    site_manager: tauri::State<'_, Arc<Mutex<SiteManager>>>,
+    site_id: String,
+    policy: IdlePolicy,
+) -> Result<(), String> {
+    let site = {
+        let mut manager = site_manager.lock().await;
+        let site = resolve_site(&manager, &site_id)?;
+        manager
+            .set_idle_policy(&site.id, policy.clone())
+            .await
+            .map_err(|e| e.to_string())?;
+        site
+    };
+    state.lock().await.set_idle_policy(&site.id, policy);
+    Ok(())
+}
+
+#[tauri::command]
+async fn get_restart_policy(
+    state: tauri::State<'_, Arc<Mutex<AppState>>>,
+) -> Result<RestartPolicy, String> {
+    let state = state.lock().await;
+    Ok(state.get_restart_policy())
+}
+
+#[tauri::command]
+async fn set_restart_policy(
+    state: tauri::State<'_, Arc<Mutex<AppState>>>,
+    policy: RestartPolicy,
+) -> Result<(), String> {
+    let mut state = state.lock().await;
+    state
+        .set_restart_policy(policy)
+        .await
+        .map_err(|e| e.to_string())
 }
 
 #[tauri::command]
 async fn open_opencode_view(
     app: tauri::AppHandle,
     state: tauri::State<'_, Arc<Mutex<AppState>>>,
+    site_manager: tauri::State<'_, Arc<Mutex<SiteManager>>>,
+    site_id: String,
     url: Option<String>,
 ) -> Result<(), String> {
+    let site = {
+        let manager = site_manager.lock().await;
+        resolve_site(&manager, &site_id)?
+    };
     let state = state.lock().await;
-    let port = state.get_port().ok_or("OpenCode is not running")?;
+    let port = state.get_port(&site.id).ok_or("OpenCode is not running")?;
 
     let target_url = url.unwrap_or_else(|| format!("http://localhost:{}", port));
-    let parsed_url: url::Url = target_url.parse().map_err(|e| format!("Invalid URL: {e}"))?;
+    let parsed_url: url::Url = target_url
+        .parse()
+        .map_err(|e| format!("Invalid URL: {e}"))?;
 
-    if let Some(window) = app.get_webview_window("opencode") {
+    let label = idle::site_window_label(&site.id);
+    if let Some(window) = app.get_webview_window(&label) {
         window.navigate(parsed_url).map_err(|e| e.to_string())?;
         window.set_focus().map_err(|e| e.to_string())?;
         return Ok(());
     }
 
-    tauri::WebviewWindowBuilder::new(&app, "opencode", tauri::WebviewUrl::External(parsed_url))
-        .title("OpenCode - WordForge")
+    tauri::WebviewWindowBuilder::new(&app, &label, tauri::WebviewUrl::External(parsed_url))
+        .title(format!("OpenCode - {}", site.name))
         .inner_size(1400.0, 900.0)
         .min_inner_size(1000.0, 700.0)
         .center()
@@ -142,7 +468,10 @@ async fn check_update_available(
     state: tauri::State<'_, Arc<Mutex<AppState>>>,
 ) -> Result<bool, String> {
     let state = state.lock().await;
-    state.check_update_available().await.map_err(|e| e.to_string())
+    state
+        .check_update_available()
+        .await
+        .map_err(|e| e.to_string())
 }
 
 #[tauri::command]
@@ -150,16 +479,91 @@ async fn get_global_config(
     state: tauri::State<'_, Arc<Mutex<AppState>>>,
 ) -> Result<GlobalConfig, String> {
     let state = state.lock().await;
-    Ok(state.get_global_config().await)
+    state.get_global_config().await.map_err(|e| e.to_string())
 }
 
 #[tauri::command]
 async fn set_global_config(
     state: tauri::State<'_, Arc<Mutex<AppState>>>,
-    config: serde_json::Value,
+    config: GlobalConfig,
 ) -> Result<(), String> {
     let state = state.lock().await;
-    state.set_global_config(config).await.map_err(|e| e.to_string())
+    state
+        .set_global_config(&config)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// Applies an RFC 7396 merge patch: keys set to `null` are removed, objects
+/// are merged recursively and everything else replaces the saved value.
+#[tauri::command]
+async fn patch_global_config(
+    state: tauri::State<'_, Arc<Mutex<AppState>>>,
+    patch: serde_json::Value,
+) -> Result<GlobalConfig, String> {
+    let state = state.lock().await;
+    state
+        .patch_global_config(&patch)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+async fn list_global_config_snapshots(
+    state: tauri::State<'_, Arc<Mutex<AppState>>>,
+) -> Result<Vec<ConfigSnapshot>, String> {
+    let state = state.lock().await;
+    state
+        .list_config_snapshots()
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// Diffs two snapshots, or a snapshot against the current config when `to`
+/// is omitted.
+#[tauri::command]
+async fn diff_global_config_snapshots(
+    state: tauri::State<'_, Arc<Mutex<AppState>>>,
+    from: String,
+    to: Option<String>,
+) -> Result<Vec<ConfigChange>, String> {
+    let state = state.lock().await;
+    state
+        .diff_config_snapshots(&from, to.as_deref())
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+async fn restore_global_config_snapshot(
+    state: tauri::State<'_, Arc<Mutex<AppState>>>,
+    id: String,
+) -> Result<GlobalConfig, String> {
+    let state = state.lock().await;
+    state
+        .restore_config_snapshot(&id)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+async fn get_config_history_settings(
+    state: tauri::State<'_, Arc<Mutex<AppState>>>,
+) -> Result<HistorySettings, String> {
+    let state = state.lock().await;
+    Ok(state.get_config_history_settings().await)
+}
+
+#[tauri::command]
+async fn set_config_history_settings(
+    state: tauri::State<'_, Arc<Mutex<AppState>>>,
+    settings: HistorySettings,
+) -> Result<(), String> {
+    let state = state.lock().await;
+    state
+        .set_config_history_settings(&settings)
+        .await
+        .map_err(|e| e.to_string())
 }
 
 #[tauri::command]
@@ -184,7 +588,10 @@ async fn set_active_site(
     id: String,
 ) -> Result<(), String> {
     let mut manager = site_manager.lock().await;
-    manager.set_active_site(&id).await.map_err(|e| e.to_string())
+    manager
+        .set_active_site(&id)
+        .await
+        .map_err(|e| e.to_string())
 }
 
 #[tauri::command]
@@ -193,23 +600,32 @@ async fn remove_site(
     site_manager: tauri::State<'_, Arc<Mutex<SiteManager>>>,
     id: String,
 ) -> Result<(), String> {
-    // First, check if this is the active site (minimal lock time)
-    let is_active_site = {
-        let manager = site_manager.lock().await;
-        manager.get_active_site().map(|s| s.id.as_str()) == Some(id.as_str())
-    };
-    
-    // Stop opencode if removing the active site (separate lock scope)
-    if is_active_site {
+    // Stop the site's OpenCode server if it is running (separate lock scope)
+    {
         let mut app_state = state.lock().await;
-        if let Err(e) = app_state.stop_opencode().await {
-            tracing::warn!("Failed to stop OpenCode while removing active site: {}", e);
+        if let Err(e) = app_state.stop_opencode(&id).await {
+            tracing::warn!("Failed to stop OpenCode while removing site: {}", e);
         }
     }
-    
-    // Now remove the site (final lock scope)
-    let mut manager = site_manager.lock().await;
-    manager.remove_site(&id).await.map_err(|e| e.to_string())
+
+    let job = site_manager
+        .lock()
+        .await
+        .remove_site(&id)
+        .await
+        .map_err(|e| e.to_string())?;
+
+    // Revoke without holding the lock; the revocation is already queued.
+    if let Some(job) = job {
+        let attempt = job.run().await;
+        site_manager
+            .lock()
+            .await
+            .record_revocations(vec![attempt])
+            .await
+            .map_err(|e| e.to_string())?;
+    }
+    Ok(())
 }
 
 #[tauri::command]
@@ -218,9 +634,10 @@ async fn open_site_folder(
     id: String,
 ) -> Result<(), String> {
     let manager = site_manager.lock().await;
-    let folder = manager.get_site_folder(&id)
+    let folder = manager
+        .get_site_folder(&id)
         .ok_or_else(|| "Site not found".to_string())?;
-    
+
     open::that(&folder).map_err(|e| e.to_string())
 }
 
@@ -229,110 +646,271 @@ async fn connect_site(
     site_manager: tauri::State<'_, Arc<Mutex<SiteManager>>>,
     site_url: String,
     token: String,
+    reauthenticate: Option<bool>,
 ) -> Result<WordPressSite, String> {
     let mut manager = site_manager.lock().await;
-    manager.exchange_token(&site_url, &token).await.map_err(|e| e.to_string())
+    manager
+        .exchange_token(&site_url, &token, reauthenticate.unwrap_or(false))
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+async fn find_site_by_url(
+    site_manager: tauri::State<'_, Arc<Mutex<SiteManager>>>,
+    site_url: String,
+) -> Result<Option<WordPressSite>, String> {
+    Ok(site_manager
+        .lock()
+        .await
+        .find_site_by_url(&site_url)
+        .cloned())
 }
 
 #[tauri::command]
 async fn check_config_update(
+    app: tauri::AppHandle,
     site_manager: tauri::State<'_, Arc<Mutex<SiteManager>>>,
-    site_id: Option<String>,
+    site_id: String,
 ) -> Result<ConfigSyncStatus, String> {
-    let manager = site_manager.lock().await;
-    
-    let site = match site_id {
-        Some(id) => manager.get_site(&id).cloned(),
-        None => manager.get_active_site().cloned(),
+    let mut manager = site_manager.lock().await;
+
+    let site = resolve_site(&manager, &site_id)?;
+
+    let remote_hash = match manager.check_config_hash(&site).await {
+        Ok(response) => Some(response.hash),
+        Err(e) => {
+            reauth::note_error(&app, &mut manager, &site.id, &e).await;
+            None
+        }
     };
-    
-    let site = site.ok_or_else(|| "No site found".to_string())?;
-    
-    let remote_hash = manager.check_config_hash(&site)
-        .await
-        .map(|r| r.hash)
-        .ok();
-    
+
     Ok(manager.get_config_sync_status(&site, remote_hash.as_deref()))
 }
 
+#[tauri::command]
+async fn preview_site_config_refresh(
+    app: tauri::AppHandle,
+    site_manager: tauri::State<'_, Arc<Mutex<SiteManager>>>,
+    site_id: String,
+) -> Result<RefreshPlan, String> {
+    let mut manager = site_manager.lock().await;
+    let site = resolve_site(&manager, &site_id)?;
+    let result = manager.preview_config_refresh(&site.id).await;
+    reauth::check(&app, &mut manager, &site.id, result).await
+}
+
 #[tauri::command]
 async fn refresh_site_config(
     app: tauri::AppHandle,
     state: tauri::State<'_, Arc<Mutex<AppState>>>,
     site_manager: tauri::State<'_, Arc<Mutex<SiteManager>>>,
-    site_id: Option<String>,
+    site_id: String,
     restart_opencode: bool,
+    resolutions: Option<HashMap<String, Resolution>>,
 ) -> Result<String, String> {
+    let resolutions = resolutions.unwrap_or_default();
+
     // Phase 1: Resolve site ID (minimal lock)
     let id = {
         let manager = site_manager.lock().await;
-        match site_id {
-            Some(id) => id,
-            None => manager.get_active_site()
-                .map(|s| s.id.clone())
-                .ok_or_else(|| "No active site".to_string())?,
-        }
+        resolve_site(&manager, &site_id)?.id
     };
-    
+
     if !restart_opencode {
         let mut manager = site_manager.lock().await;
-        let new_hash = manager.refresh_site_config(&id).await.map_err(|e| e.to_string())?;
+        let result = manager.refresh_site_config(&id, &resolutions).await;
+        let new_hash = reauth::check(&app, &mut manager, &id, result).await?;
         if let Err(e) = app.emit("config:updated", &new_hash) {
             tracing::warn!("Failed to emit config:updated event: {}", e);
         }
         return Ok(new_hash);
     }
-    
-    // Phase 2: Stop OpenCode if running
+
+    // Phase 2: Stop the site's OpenCode server if running
     let was_running = {
         let mut app_state = state.lock().await;
-        let running = app_state.get_port().is_some();
+        let running = app_state.has_server(&id);
         if running {
-            app_state.stop_opencode().await.map_err(|e| e.to_string())?;
+            app_state
+                .stop_opencode(&id)
+                .await
+                .map_err(|e| e.to_string())?;
         }
         running
     };
-    
-    // Phase 3: Refresh config
-    let new_hash = {
+
+    // Phase 3: Refresh config. A refusal over unresolved conflicts still
+    // falls through so the server comes back up.
+    let refreshed = {
         let mut manager = site_manager.lock().await;
-        manager.refresh_site_config(&id).await.map_err(|e| e.to_string())?
+        let result = manager.refresh_site_config(&id, &resolutions).await;
+        reauth::check(&app, &mut manager, &id, result).await
     };
-    
-    // Phase 4: Restart OpenCode if it was running
+
+    // Phase 4: Restart the site's OpenCode server if it was running. If it
+    // does not come back, the site is told it stopped.
     if was_running {
-        let (cors_origin, project_dir, device_id, active_site) = {
-            let mut site_mgr = site_manager.lock().await;
-            let device_id = site_mgr.get_device_id().await;
-            let active_site = site_mgr.get_active_site().cloned();
-            let cors_origin = active_site.as_ref().map(|s| s.url.clone());
-            let project_dir = active_site.as_ref().map(|s| s.project_dir.clone());
-            (cors_origin, project_dir, device_id, active_site)
+        let site = {
+            let manager = site_manager.lock().await;
+            resolve_site(&manager, &id)
         };
-        
-        let port = {
-            let mut app_state = state.lock().await;
-            app_state.start_opencode_with_config(cors_origin, project_dir)
-                .await
-                .map_err(|e| e.to_string())?
+        let restarted = match site {
+            Ok(site) => start_site_server(&app, &state, &site_manager, &site).await,
+            Err(e) => Err(e),
         };
-        
-        if let Some(site) = active_site {
-            let site_mgr = site_manager.lock().await;
-            if let Err(e) = site_mgr.sync_port_to_wordpress(&site, port, &device_id).await {
-                tracing::warn!("Failed to sync port to WordPress: {}", e);
-            }
+        if let Err(e) = restarted {
+            presence::report_stopped(&app, std::slice::from_ref(&id)).await;
+            return Err(e);
         }
     }
-    
+
+    let new_hash = refreshed?;
     if let Err(e) = app.emit("config:updated", &new_hash) {
         tracing::warn!("Failed to emit config:updated event: {}", e);
     }
     Ok(new_hash)
 }
 
-fn handle_deep_link(app: &tauri::AppHandle, processed: &Arc<std::sync::Mutex<ProcessedTokens>>, urls: Vec<url::Url>) {
+#[tauri::command]
+async fn list_site_devices(
+    app: tauri::AppHandle,
+    site_manager: tauri::State<'_, Arc<Mutex<SiteManager>>>,
+    site_id: String,
+) -> Result<Vec<SiteDevice>, String> {
+    let mut manager = site_manager.lock().await;
+    let site = resolve_site(&manager, &site_id)?;
+    let result = manager.list_devices(&site.id).await;
+    reauth::check(&app, &mut manager, &site.id, result).await
+}
+
+#[tauri::command]
+async fn get_device_name(
+    site_manager: tauri::State<'_, Arc<Mutex<SiteManager>>>,
+) -> Result<String, String> {
+    Ok(site_manager.lock().await.device_name())
+}
+
+#[tauri::command]
+async fn rename_device(
+    app: tauri::AppHandle,
+    site_manager: tauri::State<'_, Arc<Mutex<SiteManager>>>,
+    site_id: String,
+    name: String,
+) -> Result<Vec<SiteDevice>, String> {
+    let mut manager = site_manager.lock().await;
+    let site = resolve_site(&manager, &site_id)?;
+    let result = manager.rename_device(&site.id, &name).await;
+    reauth::check(&app, &mut manager, &site.id, result).await
+}
+
+#[tauri::command]
+async fn revoke_site_device(
+    app: tauri::AppHandle,
+    site_manager: tauri::State<'_, Arc<Mutex<SiteManager>>>,
+    site_id: String,
+    device_id: String,
+) -> Result<Vec<SiteDevice>, String> {
+    let mut manager = site_manager.lock().await;
+    let site = resolve_site(&manager, &site_id)?;
+    let result = manager.revoke_device(&site.id, &device_id).await;
+    reauth::check(&app, &mut manager, &site.id, result).await
+}
+
+#[tauri::command]
+async fn repair_site(
+    site_manager: tauri::State<'_, Arc<Mutex<SiteManager>>>,
+    site_id: String,
+    site_url: String,
+    token: String,
+) -> Result<WordPressSite, String> {
+    let mut manager = site_manager.lock().await;
+    manager
+        .repair_site(&site_id, &site_url, &token)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+async fn get_site_pairing_url(
+    site_manager: tauri::State<'_, Arc<Mutex<SiteManager>>>,
+    site_id: String,
+) -> Result<String, String> {
+    let manager = site_manager.lock().await;
+    let site = resolve_site(&manager, &site_id)?;
+    Ok(reauth::pairing_url(&site))
+}
+
+#[tauri::command]
+async fn check_site_health(
+    site_manager: tauri::State<'_, Arc<Mutex<SiteManager>>>,
+    site_id: String,
+) -> Result<SiteHealthReport, String> {
+    let (site, client) = {
+        let manager = site_manager.lock().await;
+        (resolve_site(&manager, &site_id)?, manager.http_client())
+    };
+    Ok(health::check_site(&client, &site).await)
+}
+
+#[tauri::command]
+async fn get_config_drift_settings(
+    site_manager: tauri::State<'_, Arc<Mutex<SiteManager>>>,
+) -> Result<DriftSettings, String> {
+    Ok(site_manager.lock().await.drift_settings())
+}
+
+#[tauri::command]
+async fn set_config_drift_settings(
+    site_manager: tauri::State<'_, Arc<Mutex<SiteManager>>>,
+    schedule: tauri::State<'_, DriftSchedule>,
+    settings: DriftSettings,
+) -> Result<DriftSettings, String> {
+    let mut manager = site_manager.lock().await;
+    let settings = manager
+        .set_drift_settings(settings)
+        .await
+        .map_err(|e| e.to_string())?;
+    schedule.update(settings.clone());
+    Ok(settings)
+}
+
+#[tauri::command]
+async fn list_site_config_snapshots(
+    site_manager: tauri::State<'_, Arc<Mutex<SiteManager>>>,
+    site_id: String,
+) -> Result<Vec<SiteSnapshot>, String> {
+    let manager = site_manager.lock().await;
+    let site = resolve_site(&manager, &site_id)?;
+    manager
+        .list_config_snapshots(&site.id)
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+async fn restore_site_config_snapshot(
+    app: tauri::AppHandle,
+    site_manager: tauri::State<'_, Arc<Mutex<SiteManager>>>,
+    site_id: String,
+    snapshot_id: String,
+) -> Result<SiteSnapshot, String> {
+    let mut manager = site_manager.lock().await;
+    let site = resolve_site(&manager, &site_id)?;
+    let restored = manager
+        .restore_config_snapshot(&site.id, &snapshot_id)
+        .await
+        .map_err(|e| e.to_string())?;
+    if let Err(e) = app.emit("config:updated", &restored.config_hash) {
+        tracing::warn!("Failed to emit config:updated event: {}", e);
+    }
+    Ok(restored)
+}
+
+fn handle_deep_link(
+    app: &tauri::AppHandle,
+    processed: &Arc<std::sync::Mutex<ProcessedTokens>>,
+    urls: Vec<url::Url>,
+) {
     for url in urls {
         let url_str = url.to_string();
         info!("Received deep link: {}", url_str);
@@ -341,21 +919,31 @@ fn handle_deep_link(app: &tauri::AppHandle, processed: &Arc<std::sync::Mutex<Pro
             Ok((site_url, token, name)) => {
                 let mut processed = processed.lock().unwrap();
                 if !processed.is_new(&token) {
-                    info!("Token already processed, skipping: {}", &token[..8.min(token.len())]);
+                    info!(
+                        "Token already processed, skipping: {}",
+                        &token[..8.min(token.len())]
+                    );
                     continue;
                 }
                 drop(processed);
 
                 info!("Processing new token for site: {}", site_url);
-                if let Err(e) = app.emit("deep-link:connect", DeepLinkPayload {
-                    url: url_str,
-                    site_url,
-                    token,
-                    name,
-                }) {
+                let reauthenticate = url
+                    .query_pairs()
+                    .any(|(key, value)| key == "reauth" && value == "1");
+                if let Err(e) = app.emit(
+                    "deep-link:connect",
+                    DeepLinkPayload {
+                        url: url_str,
+                        site_url,
+                        token,
+                        name,
+                        reauthenticate,
+                    },
+                ) {
                     tracing::warn!("Failed to emit deep-link:connect event: {}", e);
                 }
-                
+
                 if let Some(window) = app.get_webview_window("main") {
                     window.set_focus().ok();
                 }
@@ -367,7 +955,11 @@ fn handle_deep_link(app: &tauri::AppHandle, processed: &Arc<std::sync::Mutex<Pro
     }
 }
 
-fn handle_cli_deep_link(app: &tauri::AppHandle, processed: &Arc<std::sync::Mutex<ProcessedTokens>>, args: &[String]) {
+fn handle_cli_deep_link(
+    app: &tauri::AppHandle,
+    processed: &Arc<std::sync::Mutex<ProcessedTokens>>,
+    args: &[String],
+) {
     for arg in args {
         if arg.starts_with("wordforge://") {
             if let Ok(url) = url::Url::parse(arg) {
@@ -392,7 +984,7 @@ pub fn run() {
             tauri_plugin_single_instance::init(move |app, argv, _cwd| {
                 info!("Single instance callback: {:?}", argv);
                 handle_cli_deep_link(app, &processed, &argv);
-                
+
                 if let Some(window) = app.get_webview_window("main") {
                     window.set_focus().ok();
                     window.unminimize().ok();
@@ -400,11 +992,56 @@ pub fn run() {
             })
         })
         .setup(move |app| {
-            let app_state = Arc::new(Mutex::new(AppState::new(app.handle().clone())));
-            app.manage(app_state);
-            
-            let site_manager = Arc::new(Mutex::new(SiteManager::new()));
-            app.manage(site_manager);
+            let app_state = AppState::new(app.handle().clone());
+            app.manage(app_state.download_cancel_handle());
+            app.manage(app_state.log_store());
+            app.manage(Arc::new(Mutex::new(app_state)));
+
+            let data_dir = dirs::data_local_dir()
+                .unwrap_or_else(|| PathBuf::from("."))
+                .join("wordforge");
+            let site_manager = SiteManager::new(data_dir);
+            let drift_schedule = DriftSchedule::new(site_manager.drift_settings());
+            app.manage(Arc::new(Mutex::new(site_manager)));
+            drift::spawn_scheduler(app.handle().clone(), &drift_schedule);
+            app.manage(drift_schedule);
+
+            presence::spawn_heartbeat(app.handle().clone());
+
+            // Credentials of sites removed while offline are revoked later.
+            let app_handle = app.handle().clone();
+            tauri::async_runtime::spawn(async move {
+                let site_manager = app_handle.state::<Arc<Mutex<SiteManager>>>();
+                loop {
+                    let jobs = site_manager.lock().await.revocation_jobs();
+                    let mut attempts = Vec::with_capacity(jobs.len());
+                    for job in jobs {
+                        attempts.push(job.run().await);
+                    }
+                    if let Err(e) = site_manager.lock().await.record_revocations(attempts).await {
+                        tracing::warn!("Failed to retry credential revocations: {}", e);
+                    }
+                    tokio::time::sleep(sites::REVOCATION_RETRY_INTERVAL).await;
+                }
+            });
+
+            let app_handle = app.handle().clone();
+            tauri::async_runtime::spawn(async move {
+                let state = app_handle.state::<Arc<Mutex<AppState>>>();
+                let site_manager = app_handle.state::<Arc<Mutex<SiteManager>>>();
+                let idle_policies = site_manager
+                    .lock()
+                    .await
+                    .list_sites()
+                    .into_iter()
+                    .map(|site| (site.id.clone(), site.idle_policy.clone()))
+                    .collect();
+                state
+                    .lock()
+                    .await
+                    .reclaim_orphaned_servers(&idle_policies)
+                    .await;
+            });
 
             #[cfg(any(target_os = "linux", target_os = "windows"))]
             {
@@ -427,15 +1064,33 @@ pub fn run() {
             });
 
             let app_handle = app.handle().clone();
-            app.listen("opencode:idle-shutdown", move |_| {
-                info!("Received idle-shutdown event, stopping OpenCode");
+            app.listen("opencode:idle-shutdown", move |event| {
+                let payload: IdleShutdownPayload = match serde_json::from_str(event.payload()) {
+                    Ok(payload) => payload,
+                    Err(e) => {
+                        tracing::warn!("Invalid idle-shutdown payload: {}", e);
+                        return;
+                    }
+                };
+                info!(
+                    "Received idle-shutdown event, stopping OpenCode for site {}",
+                    payload.site_id
+                );
                 let app = app_handle.clone();
                 tauri::async_runtime::spawn(async move {
                     let state = app.state::<Arc<Mutex<AppState>>>();
-                    let mut state = state.lock().await;
-                    if let Err(e) = state.stop_opencode().await {
-                        tracing::warn!("Failed to stop OpenCode on idle shutdown: {}", e);
+                    let stopped = state.lock().await.stop_opencode(&payload.site_id).await;
+                    match stopped {
+                        Ok(Some(outcome)) if !outcome.clean => {
+                            tracing::warn!(
+                                "OpenCode for site {} was killed on idle shutdown",
+                                outcome.site_id
+                            );
+                        }
+                        Ok(_) => {}
+                        Err(e) => tracing::warn!("Failed to stop OpenCode on idle shutdown: {}", e),
                     }
+                    presence::report_stopped(&app, &[payload.site_id]).await;
                 });
             });
 
@@ -446,21 +1101,58 @@ pub fn run() {
             get_installed_version,
             get_latest_version,
             download_opencode,
+            cancel_opencode_download,
+            list_opencode_releases,
+            get_opencode_installs,
+            install_opencode_version,
+            set_active_opencode_version,
+            rollback_opencode,
             start_opencode,
             stop_opencode,
             get_opencode_port,
+            list_opencode_servers,
+            set_site_idle_policy,
+            take_sites_store_recovery,
+            get_vault_status,
+            unlock_vault,
+            lock_vault,
+            set_vault_passphrase,
+            get_restart_policy,
+            set_restart_policy,
+            get_opencode_logs,
+            search_opencode_logs,
+            export_opencode_logs,
             open_opencode_view,
             check_update_available,
             get_global_config,
             set_global_config,
+            patch_global_config,
+            list_global_config_snapshots,
+            diff_global_config_snapshots,
+            restore_global_config_snapshot,
+            get_config_history_settings,
+            set_config_history_settings,
             list_sites,
             get_active_site,
             set_active_site,
             remove_site,
             connect_site,
+            find_site_by_url,
             open_site_folder,
             check_config_update,
+            preview_site_config_refresh,
             refresh_site_config,
+            check_site_health,
+            get_site_pairing_url,
+            repair_site,
+            list_site_devices,
+            get_device_name,
+            rename_device,
+            revoke_site_device,
+            get_config_drift_settings,
+            set_config_drift_settings,
+            list_site_config_snapshots,
+            restore_site_config_snapshot,
         ])
         .build(tauri::generate_context!())
         .expect("error while building tauri application")
@@ -469,9 +1161,28 @@ pub fn run() {
                 info!("App exiting, stopping OpenCode");
                 let state = app.state::<Arc<Mutex<AppState>>>();
                 tauri::async_runtime::block_on(async {
-                    let mut state = state.lock().await;
-                    if let Err(e) = state.stop_opencode().await {
-                        tracing::warn!("Failed to stop OpenCode on app exit: {}", e);
+                    let stopped = state.lock().await.stop_all_opencode().await;
+                    let site_ids: Vec<String> = match stopped {
+                        Ok(outcomes) => {
+                            for outcome in outcomes.iter().filter(|o| !o.clean) {
+                                tracing::warn!(
+                                    "OpenCode for site {} was killed on app exit",
+                                    outcome.site_id
+                                );
+                            }
+                            outcomes.into_iter().map(|o| o.site_id).collect()
+                        }
+                        Err(e) => {
+                            tracing::warn!("Failed to stop OpenCode on app exit: {}", e);
+                            Vec::new()
+                        }
+                    };
+                    let report = presence::report_stopped(app, &site_ids);
+                    if tokio::time::timeout(presence::EXIT_REPORT_TIMEOUT, report)
+                        .await
+                        .is_err()
+                    {
+                        tracing::warn!("Timed out telling sites their OpenCode servers stopped");
                     }
                 });
             }
@@ -0,0 +1,634 @@
+use chrono::{DateTime, NaiveDateTime, SecondsFormat};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{mpsc as std_mpsc, Arc, Mutex};
+use tauri::{AppHandle, Emitter};
+use tokio::sync::mpsc;
+use tracing::warn;
+
+const LOG_FILE_NAME: &str = "opencode.log";
+const RING_BUFFER_LINES: usize = 2_000;
+const MAX_LOG_FILE_BYTES: u64 = 5 * 1024 * 1024;
+const MAX_ROTATED_FILES: usize = 5;
+const DEFAULT_SEARCH_LIMIT: usize = 500;
+const FORWARD_QUEUE_CAPACITY: usize = 1_024;
+const FORWARD_BATCH_WINDOW: std::time::Duration = std::time::Duration::from_millis(100);
+const FORWARD_MAX_BATCH: usize = 256;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LogLevel {
+    Debug,
+    Info,
+    Warn,
+    Error,
+}
+
+impl LogLevel {
+    fn label(self) -> &'static str {
+        match self {
+            LogLevel::Debug => "DEBUG",
+            LogLevel::Info => "INFO",
+            LogLevel::Warn => "WARN",
+            LogLevel::Error => "ERROR",
+        }
+    }
+
+    fn parse(s: &str) -> Option<Self> {
+        match s {
+            "DEBUG" => Some(LogLevel::Debug),
+            "INFO" => Some(LogLevel::Info),
+            "WARN" => Some(LogLevel::Warn),
+            "ERROR" => Some(LogLevel::Error),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LogEntry {
+    /// Milliseconds since the Unix epoch.
+    pub timestamp: u64,
+    pub level: LogLevel,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub service: Option<String>,
+    pub message: String,
+    /// Remaining `key=value` tags of the OpenCode log line.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub fields: BTreeMap<String, String>,
+}
+
+impl LogEntry {
+    /// Parses an OpenCode log line such as
+    /// `INFO  2025-06-12T10:15:32 +12ms service=server method=GET request`.
+    /// Lines in any other format are kept verbatim at `default_level`.
+    pub fn parse(line: &str, default_level: LogLevel) -> Self {
+        let raw = Self {
+            timestamp: now_millis(),
+            level: default_level,
+            service: None,
+            message: line.trim_end().to_string(),
+            fields: BTreeMap::new(),
+        };
+
+        let (level, rest) = split_token(line);
+        let Some(level) = LogLevel::parse(level) else {
+            return raw;
+        };
+        let (time, mut rest) = split_token(rest);
+        let Some(timestamp) = parse_timestamp(time) else {
+            return raw;
+        };
+
+        let (elapsed, after_elapsed) = split_token(rest);
+        if elapsed.starts_with('+') && elapsed.ends_with("ms") {
+            rest = after_elapsed;
+        }
+
+        let mut fields = BTreeMap::new();
+        loop {
+            let (token, after) = split_token(rest);
+            let Some((key, value)) = token.split_once('=') else {
+                break;
+            };
+            if key.is_empty()
+                || !key
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '.')
+            {
+                break;
+            }
+            fields.insert(key.to_string(), value.to_string());
+            rest = after;
+        }
+
+        Self {
+            timestamp,
+            level,
+            service: fields.remove("service"),
+            message: rest.trim().to_string(),
+            fields,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct LogRecord {
+    pub site_id: String,
+    #[serde(flatten)]
+    pub entry: LogEntry,
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct LogBatch {
+    records: Vec<LogRecord>,
+    /// Records dropped since the previous batch because the frontend fell
+    /// behind. They are still in the on-disk log.
+    dropped: u64,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct LogFilter {
+    /// Minimum level to include.
+    pub level: Option<LogLevel>,
+    /// Case-insensitive substring match on the message.
+    pub query: Option<String>,
+    pub from: Option<u64>,
+    pub to: Option<u64>,
+    pub limit: Option<usize>,
+}
+
+impl LogFilter {
+    fn matches(&self, entry: &LogEntry, query: Option<&str>) -> bool {
+        self.level.is_none_or(|level| entry.level >= level)
+            && self.from.is_none_or(|from| entry.timestamp >= from)
+            && self.to.is_none_or(|to| entry.timestamp <= to)
+            && query.is_none_or(|q| {
+                entry.message.to_lowercase().contains(q)
+                    || entry
+                        .service
+                        .as_ref()
+                        .is_some_and(|s| s.to_lowercase().contains(q))
+            })
+    }
+}
+
+enum WriterCommand {
+    Append { site_id: String, line: String },
+    Flush(std_mpsc::Sender<()>),
+}
+
+struct SiteFile {
+    writer: BufWriter<File>,
+    size: u64,
+}
+
+/// Owns the open log file of every site. Lines are appended by a single
+/// thread fed through a channel, so OpenCode output never waits on disk and
+/// each file is flushed once per burst instead of once per line.
+struct LogWriter {
+    root: PathBuf,
+    files: HashMap<String, SiteFile>,
+}
+
+impl LogWriter {
+    fn run(mut self, rx: std_mpsc::Receiver<WriterCommand>) {
+        while let Ok(command) = rx.recv() {
+            self.handle(command);
+            while let Ok(command) = rx.try_recv() {
+                self.handle(command);
+            }
+            self.flush();
+        }
+        self.flush();
+    }
+
+    fn handle(&mut self, command: WriterCommand) {
+        match command {
+            WriterCommand::Append { site_id, line } => {
+                if let Err(e) = self.append(&site_id, &line) {
+                    warn!("Failed to write OpenCode log for site {}: {}", site_id, e);
+                    self.files.remove(&site_id);
+                }
+            }
+            WriterCommand::Flush(done) => {
+                self.flush();
+                done.send(()).ok();
+            }
+        }
+    }
+
+    fn append(&mut self, site_id: &str, line: &str) -> std::io::Result<()> {
+        let dir = self.root.join(site_id);
+
+        if self
+            .files
+            .get(site_id)
+            .is_some_and(|f| f.size >= MAX_LOG_FILE_BYTES)
+        {
+            if let Some(mut file) = self.files.remove(site_id) {
+                file.writer.flush()?;
+            }
+            rotate(&dir)?;
+        }
+
+        let file = match self.files.entry(site_id.to_string()) {
+            std::collections::hash_map::Entry::Occupied(entry) => entry.into_mut(),
+            std::collections::hash_map::Entry::Vacant(entry) => {
+                std::fs::create_dir_all(&dir)?;
+                let file = OpenOptions::new()
+                    .create(true)
+                    .append(true)
+                    .open(dir.join(LOG_FILE_NAME))?;
+                let size = file.metadata().map(|m| m.len()).unwrap_or(0);
+                entry.insert(SiteFile {
+                    writer: BufWriter::new(file),
+                    size,
+                })
+            }
+        };
+
+        file.writer.write_all(line.as_bytes())?;
+        file.size += line.len() as u64;
+        Ok(())
+    }
+
+    fn flush(&mut self) {
+        for (site_id, file) in &mut self.files {
+            if let Err(e) = file.writer.flush() {
+                warn!("Failed to flush OpenCode log for site {}: {}", site_id, e);
+            }
+        }
+    }
+}
+
+/// OpenCode output of every site, kept in memory for the live view and in
+/// size-rotated JSON-lines files under `logs/<site_id>/` for later
+/// inspection and support tickets.
+pub struct LogStore {
+    root: PathBuf,
+    buffers: Mutex<HashMap<String, VecDeque<LogEntry>>>,
+    writer: Mutex<std_mpsc::Sender<WriterCommand>>,
+}
+
+impl LogStore {
+    pub fn new(root: PathBuf) -> Self {
+        let (tx, rx) = std_mpsc::channel();
+        let writer = LogWriter {
+            root: root.clone(),
+            files: HashMap::new(),
+        };
+        if let Err(e) = std::thread::Builder::new()
+            .name("opencode-log-writer".into())
+            .spawn(move || writer.run(rx))
+        {
+            warn!("Failed to start the OpenCode log writer: {}", e);
+        }
+
+        Self {
+            root,
+            buffers: Mutex::new(HashMap::new()),
+            writer: Mutex::new(tx),
+        }
+    }
+
+    fn site_dir(&self, site_id: &str) -> PathBuf {
+        self.root.join(site_id)
+    }
+
+    pub fn record(&self, site_id: &str, entry: LogEntry) {
+        match serde_json::to_string(&entry) {
+            Ok(mut line) => {
+                line.push('\n');
+                self.send(WriterCommand::Append {
+                    site_id: site_id.to_string(),
+                    line,
+                });
+            }
+            Err(e) => warn!("Failed to serialize OpenCode log line: {}", e),
+        }
+
+        let Ok(mut buffers) = self.buffers.lock() else {
+            return;
+        };
+        let buffer = buffers
+            .entry(site_id.to_string())
+            .or_insert_with(|| VecDeque::with_capacity(RING_BUFFER_LINES));
+        if buffer.len() >= RING_BUFFER_LINES {
+            buffer.pop_front();
+        }
+        buffer.push_back(entry);
+    }
+
+    fn send(&self, command: WriterCommand) -> bool {
+        self.writer.lock().is_ok_and(|tx| tx.send(command).is_ok())
+    }
+
+    /// Waits until every line recorded so far is on disk.
+    fn flush(&self) {
+        let (done, wait) = std_mpsc::channel();
+        if self.send(WriterCommand::Flush(done)) {
+            wait.recv().ok();
+        }
+    }
+
+    /// The most recent `limit` entries kept in memory.
+    pub fn tail(&self, site_id: &str, limit: usize) -> Vec<LogEntry> {
+        let Ok(buffers) = self.buffers.lock() else {
+            return Vec::new();
+        };
+        buffers
+            .get(site_id)
+            .map(|buffer| {
+                let skip = buffer.len().saturating_sub(limit);
+                buffer.iter().skip(skip).cloned().collect()
+            })
+            .unwrap_or_default()
+    }
+
+    /// Searches the on-disk history, oldest file first, and returns the most
+    /// recent matches.
+    pub fn search(&self, site_id: &str, filter: &LogFilter) -> std::io::Result<Vec<LogEntry>> {
+        let limit = filter.limit.unwrap_or(DEFAULT_SEARCH_LIMIT);
+        let query = filter.query.as_ref().map(|q| q.to_lowercase());
+        let mut matches = VecDeque::new();
+
+        self.for_each_entry(site_id, |entry| {
+            if filter.matches(&entry, query.as_deref()) {
+                if matches.len() >= limit {
+                    matches.pop_front();
+                }
+                matches.push_back(entry);
+            }
+        })?;
+
+        Ok(matches.into())
+    }
+
+    /// Writes every entry between `from` and `to` to `destination` as plain
+    /// text and returns the number of lines written.
+    pub fn export(
+        &self,
+        site_id: &str,
+        from: Option<u64>,
+        to: Option<u64>,
+        destination: &Path,
+    ) -> std::io::Result<usize> {
+        let filter = LogFilter {
+            from,
+            to,
+            ..LogFilter::default()
+        };
+        let mut out = std::io::BufWriter::new(File::create(destination)?);
+        let mut written = 0;
+        let mut write_error = None;
+
+        self.for_each_entry(site_id, |entry| {
+            if write_error.is_some() || !filter.matches(&entry, None) {
+                return;
+            }
+            let mut line = format!(
+                "{} {:<5}",
+                format_timestamp(entry.timestamp),
+                entry.level.label()
+            );
+            if let Some(service) = &entry.service {
+                line.push_str(&format!(" [{}]", service));
+            }
+            line.push(' ');
+            line.push_str(&entry.message);
+            for (key, value) in &entry.fields {
+                line.push_str(&format!(" {}={}", key, value));
+            }
+            line.push('\n');
+            match out.write_all(line.as_bytes()) {
+                Ok(()) => written += 1,
+                Err(e) => write_error = Some(e),
+            }
+        })?;
+
+        if let Some(e) = write_error {
+            return Err(e);
+        }
+        out.flush()?;
+        Ok(written)
+    }
+
+    fn for_each_entry(&self, site_id: &str, mut f: impl FnMut(LogEntry)) -> std::io::Result<()> {
+        self.flush();
+        let dir = self.site_dir(site_id);
+        let mut files: Vec<PathBuf> = (1..=MAX_ROTATED_FILES)
+            .rev()
+            .map(|n| rotated_path(&dir, n))
+            .collect();
+        files.push(dir.join(LOG_FILE_NAME));
+
+        for path in files.into_iter().filter(|p| p.exists()) {
+            let reader = BufReader::new(File::open(&path)?);
+            for line in reader.lines() {
+                if let Ok(entry) = serde_json::from_str::<LogEntry>(&line?) {
+                    f(entry);
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Entry point for OpenCode output: every line is persisted in the
+/// [`LogStore`] and queued for the frontend, which receives it in batches
+/// through `opencode:logs` instead of one IPC call per line. When the queue
+/// is full the line is dropped from the live stream and counted.
+#[derive(Clone)]
+pub struct LogSink {
+    store: Arc<LogStore>,
+    tx: mpsc::Sender<LogRecord>,
+    dropped: Arc<AtomicU64>,
+}
+
+impl LogSink {
+    pub fn new(app: AppHandle, store: Arc<LogStore>) -> Self {
+        let (tx, rx) = mpsc::channel(FORWARD_QUEUE_CAPACITY);
+        let dropped = Arc::new(AtomicU64::new(0));
+        tauri::async_runtime::spawn(forward_batches(app, rx, dropped.clone()));
+
+        Self { store, tx, dropped }
+    }
+
+    pub fn store(&self) -> Arc<LogStore> {
+        self.store.clone()
+    }
+
+    pub fn push(&self, site_id: &str, line: &str, default_level: LogLevel) {
+        let entry = LogEntry::parse(line, default_level);
+        self.store.record(site_id, entry.clone());
+
+        let record = LogRecord {
+            site_id: site_id.to_string(),
+            entry,
+        };
+        if self.tx.try_send(record).is_err() {
+            self.dropped.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+}
+
+async fn forward_batches(
+    app: AppHandle,
+    mut rx: mpsc::Receiver<LogRecord>,
+    dropped: Arc<AtomicU64>,
+) {
+    while let Some(first) = rx.recv().await {
+        let mut records = vec![first];
+        let deadline = tokio::time::Instant::now() + FORWARD_BATCH_WINDOW;
+
+        while records.len() < FORWARD_MAX_BATCH {
+            match tokio::time::timeout_at(deadline, rx.recv()).await {
+                Ok(Some(record)) => records.push(record),
+                Ok(None) | Err(_) => break,
+            }
+        }
+
+        let dropped = dropped.swap(0, Ordering::Relaxed);
+        if dropped > 0 {
+            warn!(
+                "Dropped {} OpenCode log lines, frontend is falling behind",
+                dropped
+            );
+        }
+        app.emit("opencode:logs", LogBatch { records, dropped })
+            .ok();
+    }
+}
+
+fn split_token(s: &str) -> (&str, &str) {
+    let s = s.trim_start();
+    match s.find(char::is_whitespace) {
+        Some(idx) => (&s[..idx], &s[idx..]),
+        None => (s, ""),
+    }
+}
+
+/// Parses the `YYYY-MM-DDTHH:MM:SS` UTC timestamps OpenCode prints into
+/// epoch milliseconds.
+fn parse_timestamp(s: &str) -> Option<u64> {
+    let time =
+        NaiveDateTime::parse_from_str(s.trim_end_matches('Z'), "%Y-%m-%dT%H:%M:%S%.f").ok()?;
+    u64::try_from(time.and_utc().timestamp())
+        .ok()
+        .map(|s| s * 1000)
+}
+
+fn rotated_path(dir: &Path, n: usize) -> PathBuf {
+    dir.join(format!("{}.{}", LOG_FILE_NAME, n))
+}
+
+/// Shifts `opencode.log.N` to `.N+1`, dropping the oldest, and moves the
+/// current file to `.1`.
+fn rotate(dir: &Path) -> std::io::Result<()> {
+    let oldest = rotated_path(dir, MAX_ROTATED_FILES);
+    if oldest.exists() {
+        std::fs::remove_file(&oldest)?;
+    }
+    for n in (1..MAX_ROTATED_FILES).rev() {
+        let from = rotated_path(dir, n);
+        if from.exists() {
+            std::fs::rename(&from, rotated_path(dir, n + 1))?;
+        }
+    }
+    std::fs::rename(dir.join(LOG_FILE_NAME), rotated_path(dir, 1))
+}
+
+fn now_millis() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+/// Formats epoch milliseconds as an ISO 8601 UTC timestamp.
+fn format_timestamp(millis: u64) -> String {
+    i64::try_from(millis)
+        .ok()
+        .and_then(DateTime::from_timestamp_millis)
+        .unwrap_or_default()
+        .to_rfc3339_opts(SecondsFormat::Millis, true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_root() -> PathBuf {
+        std::env::temp_dir().join(format!("wordforge-logs-{}", uuid::Uuid::new_v4()))
+    }
+
+    #[test]
+    fn parses_opencode_line() {
+        let entry = LogEntry::parse(
+            "INFO  2025-06-12T10:15:32 +12ms service=server method=GET path=/session request",
+            LogLevel::Debug,
+        );
+        assert_eq!(entry.level, LogLevel::Info);
+        assert_eq!(entry.timestamp, 1_749_723_332_000);
+        assert_eq!(entry.service.as_deref(), Some("server"));
+        assert_eq!(entry.fields.get("method").map(String::as_str), Some("GET"));
+        assert_eq!(
+            entry.fields.get("path").map(String::as_str),
+            Some("/session")
+        );
+        assert_eq!(entry.message, "request");
+    }
+
+    #[test]
+    fn keeps_unrecognized_lines_verbatim() {
+        let entry = LogEntry::parse("panic: something broke  \n", LogLevel::Error);
+        assert_eq!(entry.level, LogLevel::Error);
+        assert_eq!(entry.message, "panic: something broke");
+        assert!(entry.service.is_none());
+
+        let entry = LogEntry::parse("WARN not-a-time service=x", LogLevel::Info);
+        assert_eq!(entry.level, LogLevel::Info);
+        assert_eq!(entry.message, "WARN not-a-time service=x");
+    }
+
+    #[test]
+    fn parses_timestamp_variants() {
+        assert_eq!(parse_timestamp("1970-01-01T00:00:00"), Some(0));
+        assert_eq!(
+            parse_timestamp("2024-02-29T23:59:59Z"),
+            Some(1_709_251_199_000)
+        );
+        assert_eq!(
+            parse_timestamp("2024-02-29T23:59:59.250"),
+            Some(1_709_251_199_000)
+        );
+        assert_eq!(parse_timestamp("2023-02-29T00:00:00"), None);
+        assert_eq!(parse_timestamp("2024-13-01T00:00:00"), None);
+    }
+
+    #[test]
+    fn formats_timestamp() {
+        assert_eq!(format_timestamp(0), "1970-01-01T00:00:00.000Z");
+        assert_eq!(
+            format_timestamp(1_709_251_199_042),
+            "2024-02-29T23:59:59.042Z"
+        );
+    }
+
+    #[test]
+    fn search_sees_recorded_lines() {
+        let root = temp_root();
+        let store = LogStore::new(root.clone());
+        store.record(
+            "site",
+            LogEntry::parse(
+                "INFO  2025-06-12T10:15:32 service=server hello",
+                LogLevel::Info,
+            ),
+        );
+        store.record(
+            "site",
+            LogEntry::parse(
+                "ERROR 2025-06-12T10:15:33 service=server boom",
+                LogLevel::Info,
+            ),
+        );
+
+        let filter = LogFilter {
+            level: Some(LogLevel::Warn),
+            ..LogFilter::default()
+        };
+        let found = store.search("site", &filter).unwrap();
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].message, "boom");
+        assert_eq!(store.tail("site", 10).len(), 2);
+
+        std::fs::remove_dir_all(root).ok();
+    }
+}
@@ -0,0 +1,515 @@
+use crate::archive::safe_relative_path;
+use crate::persist;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use similar::TextDiff;
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+use thiserror::Error;
+
+/// Directory inside a site's project folder that holds WordForge's own
+/// bookkeeping. Never shipped by the site and never touched by a refresh.
+pub const STATE_DIR: &str = ".wordforge";
+const MANIFEST_FILE: &str = "manifest.json";
+const STAGING_DIR: &str = "staging";
+const RESERVED: [&str; 2] = [STATE_DIR, ".git"];
+/// Diffs longer than this are cut off; the preview is for reading, not patching.
+const MAX_DIFF_BYTES: usize = 64 * 1024;
+
+#[derive(Debug, Error)]
+pub enum ManifestError {
+    #[error("IO error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Invalid manifest: {0}")]
+    Json(#[from] serde_json::Error),
+    #[error("Unresolved conflicts in {}", .0.join(", "))]
+    Unresolved(Vec<String>),
+    #[error("{0} is a symbolic link in the project folder; refusing to touch it")]
+    Symlink(String),
+}
+
+/// The files the last refresh wrote into a project folder, with the hash of
+/// the content the site shipped for each.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Manifest {
+    pub files: BTreeMap<String, String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ConflictKind {
+    /// Changed locally and by the site since the last refresh.
+    Modified,
+    /// Changed locally but no longer shipped by the site.
+    Removed,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct FileConflict {
+    pub path: String,
+    pub kind: ConflictKind,
+    /// Unified diff from the local file to what the refresh would leave.
+    /// `None` for files that are not UTF-8 text.
+    pub diff: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Resolution {
+    KeepLocal,
+    UseRemote,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct RefreshPlan {
+    pub added: Vec<String>,
+    pub updated: Vec<String>,
+    pub deleted: Vec<String>,
+    /// Edited locally while the site's copy stayed the same; left alone.
+    pub kept_local: Vec<String>,
+    pub conflicts: Vec<FileConflict>,
+}
+
+impl RefreshPlan {
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty()
+            && self.updated.is_empty()
+            && self.deleted.is_empty()
+            && self.conflicts.is_empty()
+    }
+
+    /// Fails with the paths of conflicts that have no entry in `resolutions`.
+    pub fn check_resolved(
+        &self,
+        resolutions: &HashMap<String, Resolution>,
+    ) -> Result<(), ManifestError> {
+        let unresolved: Vec<String> = self
+            .conflicts
+            .iter()
+            .filter(|c| !resolutions.contains_key(&c.path))
+            .map(|c| c.path.clone())
+            .collect();
+        if unresolved.is_empty() {
+            Ok(())
+        } else {
+            Err(ManifestError::Unresolved(unresolved))
+        }
+    }
+}
+
+impl Manifest {
+    fn path(project_dir: &Path) -> PathBuf {
+        project_dir.join(STATE_DIR).join(MANIFEST_FILE)
+    }
+
+    /// `None` for project folders populated before refreshes were tracked.
+    pub fn load(project_dir: &Path) -> Result<Option<Self>, ManifestError> {
+        match std::fs::read_to_string(Self::path(project_dir)) {
+            Ok(content) => Ok(Some(serde_json::from_str(&content)?)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    pub fn save(&self, project_dir: &Path) -> Result<(), ManifestError> {
+        let content = serde_json::to_vec_pretty(self)?;
+        persist::write_atomic(&Self::path(project_dir), &content)?;
+        Ok(())
+    }
+
+    /// Hashes every file under `root`, skipping the reserved directories.
+    pub(crate) fn scan(root: &Path) -> Result<Self, ManifestError> {
+        let mut manifest = Self::default();
+        let mut pending = vec![PathBuf::new()];
+        while let Some(relative) = pending.pop() {
+            for entry in std::fs::read_dir(root.join(&relative))? {
+                let entry = entry?;
+                let name = relative.join(entry.file_name());
+                if relative.as_os_str().is_empty()
+                    && RESERVED.iter().any(|r| entry.file_name() == *r)
+                {
+                    continue;
+                }
+                let kind = entry.file_type()?;
+                if kind.is_dir() {
+                    pending.push(name);
+                } else if kind.is_file() {
+                    let hash = hash_file(&entry.path())?.unwrap_or_default();
+                    manifest.files.insert(manifest_key(&name), hash);
+                }
+            }
+        }
+        Ok(manifest)
+    }
+}
+
+pub fn staging_dir(project_dir: &Path) -> PathBuf {
+    project_dir.join(STATE_DIR).join(STAGING_DIR)
+}
+
+fn manifest_key(relative: &Path) -> String {
+    relative
+        .components()
+        .map(|c| c.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+/// Fails if `path`, or any folder between `project_dir` and it, is a
+/// symbolic link, so a refresh never reads or writes outside the project.
+fn ensure_no_symlink(project_dir: &Path, path: &str) -> Result<(), ManifestError> {
+    let mut current = project_dir.to_path_buf();
+    for component in Path::new(path).components() {
+        current.push(component);
+        match std::fs::symlink_metadata(&current) {
+            Ok(meta) if meta.file_type().is_symlink() => {
+                return Err(ManifestError::Symlink(path.to_string()))
+            }
+            Ok(_) => {}
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(()),
+            Err(e) => return Err(e.into()),
+        }
+    }
+    Ok(())
+}
+
+/// Hash of the project's copy of `path`, refusing symbolic links.
+fn hash_local(project_dir: &Path, path: &str) -> Result<Option<String>, ManifestError> {
+    ensure_no_symlink(project_dir, path)?;
+    Ok(hash_file(&project_dir.join(path))?)
+}
+
+fn hash_file(path: &Path) -> std::io::Result<Option<String>> {
+    match std::fs::read(path) {
+        Ok(bytes) => Ok(Some(hex::encode(Sha256::digest(&bytes)))),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+fn unified_diff(path: &str, local: &Path, remote: Option<&Path>) -> Option<String> {
+    let old = std::fs::read_to_string(local).ok()?;
+    let new = match remote {
+        Some(remote) => std::fs::read_to_string(remote).ok()?,
+        None => String::new(),
+    };
+
+    let mut diff = TextDiff::from_lines(&old, &new)
+        .unified_diff()
+        .context_radius(3)
+        .header(&format!("local/{}", path), &format!("site/{}", path))
+        .to_string();
+    if diff.len() > MAX_DIFF_BYTES {
+        let mut end = MAX_DIFF_BYTES;
+        while !diff.is_char_boundary(end) {
+            end -= 1;
+        }
+        diff.truncate(end);
+        diff.push_str("\n... diff truncated\n");
+    }
+    Some(diff)
+}
+
+/// Compares a freshly extracted config in `staging` with the project folder
+/// and the manifest of the previous refresh.
+///
+/// Without a manifest, the files currently on disk are taken to be the ones
+/// the site shipped last, since older refreshes overwrote everything anyway.
+pub fn plan(project_dir: &Path, staging: &Path) -> Result<(RefreshPlan, Manifest), ManifestError> {
+    let previous = Manifest::load(project_dir)?;
+    let remote = Manifest::scan(staging)?;
+    let mut plan = RefreshPlan::default();
+
+    for (path, remote_hash) in &remote.files {
+        let local_hash = hash_local(project_dir, path)?;
+        let base = match &previous {
+            Some(previous) => previous.files.get(path).cloned(),
+            None => local_hash.clone(),
+        };
+
+        match local_hash {
+            None => plan.added.push(path.clone()),
+            Some(local) if &local == remote_hash => {}
+            Some(local) if Some(&local) == base.as_ref() => plan.updated.push(path.clone()),
+            Some(_) if base.as_ref() == Some(remote_hash) => plan.kept_local.push(path.clone()),
+            Some(_) => plan.conflicts.push(FileConflict {
+                path: path.clone(),
+                kind: ConflictKind::Modified,
+                diff: unified_diff(path, &project_dir.join(path), Some(&staging.join(path))),
+            }),
+        }
+    }
+
+    let stale = previous
+        .iter()
+        .flat_map(|previous| previous.files.iter())
+        .filter(|(path, _)| !remote.files.contains_key(*path));
+    for (path, base) in stale {
+        let Some(relative) = safe_relative_path(Path::new(path)) else {
+            continue;
+        };
+        let local = project_dir.join(&relative);
+        match hash_local(project_dir, &manifest_key(&relative))? {
+            None => {}
+            Some(hash) if &hash == base => plan.deleted.push(path.clone()),
+            Some(_) => plan.conflicts.push(FileConflict {
+                path: path.clone(),
+                kind: ConflictKind::Removed,
+                diff: unified_diff(path, &local, None),
+            }),
+        }
+    }
+
+    Ok((plan, remote))
+}
+
+/// Removes `path` and then any parents it leaves empty, up to `root`.
+pub(crate) fn remove_with_empty_parents(root: &Path, path: &Path) -> std::io::Result<()> {
+    std::fs::remove_file(path)?;
+    let mut dir = path.parent();
+    while let Some(current) = dir {
+        if current == root || std::fs::remove_dir(current).is_err() {
+            break;
+        }
+        dir = current.parent();
+    }
+    Ok(())
+}
+
+fn install(staging: &Path, project_dir: &Path, path: &str) -> Result<(), ManifestError> {
+    ensure_no_symlink(project_dir, path)?;
+    let target = project_dir.join(path);
+    if let Some(parent) = target.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    std::fs::copy(staging.join(path), target)?;
+    Ok(())
+}
+
+fn remove_local(project_dir: &Path, path: &str) -> Result<(), ManifestError> {
+    ensure_no_symlink(project_dir, path)?;
+    Ok(remove_with_empty_parents(
+        project_dir,
+        &project_dir.join(path),
+    )?)
+}
+
+/// Carries out `plan`. Every conflict needs an entry in `resolutions`;
+/// nothing is written otherwise.
+pub fn apply(
+    project_dir: &Path,
+    staging: &Path,
+    plan: &RefreshPlan,
+    remote: &Manifest,
+    resolutions: &HashMap<String, Resolution>,
+) -> Result<(), ManifestError> {
+    plan.check_resolved(resolutions)?;
+
+    for path in plan.added.iter().chain(&plan.updated) {
+        install(staging, project_dir, path)?;
+    }
+    for path in &plan.deleted {
+        remove_local(project_dir, path)?;
+    }
+    for conflict in &plan.conflicts {
+        if resolutions[&conflict.path] == Resolution::KeepLocal {
+            continue;
+        }
+        match conflict.kind {
+            ConflictKind::Modified => install(staging, project_dir, &conflict.path)?,
+            ConflictKind::Removed => remove_local(project_dir, &conflict.path)?,
+        }
+    }
+
+    // Record what the site shipped rather than what ended up on disk, so a
+    // kept local edit is only flagged again once the site's copy moves on.
+    remote.save(project_dir)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Fixture {
+        root: PathBuf,
+        project: PathBuf,
+        staging: PathBuf,
+    }
+
+    impl Fixture {
+        fn new() -> Self {
+            let root =
+                std::env::temp_dir().join(format!("wordforge-manifest-{}", uuid::Uuid::new_v4()));
+            let project = root.join("project");
+            let staging = staging_dir(&project);
+            std::fs::create_dir_all(&staging).unwrap();
+            Self {
+                root,
+                project,
+                staging,
+            }
+        }
+
+        fn local(&self, path: &str, content: &str) {
+            write(&self.project.join(path), content);
+        }
+
+        fn remote(&self, path: &str, content: &str) {
+            write(&self.staging.join(path), content);
+        }
+
+        fn read(&self, path: &str) -> Option<String> {
+            std::fs::read_to_string(self.project.join(path)).ok()
+        }
+
+        /// Records the current project files as what the site last shipped.
+        fn record_refresh(&self) {
+            Manifest::scan(&self.project)
+                .unwrap()
+                .save(&self.project)
+                .unwrap();
+        }
+    }
+
+    impl Drop for Fixture {
+        fn drop(&mut self) {
+            std::fs::remove_dir_all(&self.root).ok();
+        }
+    }
+
+    fn write(path: &Path, content: &str) {
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(path, content).unwrap();
+    }
+
+    #[test]
+    fn plans_every_kind_of_change() {
+        let f = Fixture::new();
+        f.local("same.md", "same");
+        f.local("updated.md", "v1");
+        f.local("kept.md", "v1");
+        f.local("conflict.md", "v1");
+        f.local("deleted.md", "v1");
+        f.local("removed-conflict.md", "v1");
+        f.record_refresh();
+
+        f.local("kept.md", "local edit");
+        f.local("conflict.md", "local edit");
+        f.local("removed-conflict.md", "local edit");
+
+        f.remote("same.md", "same");
+        f.remote("updated.md", "v2");
+        f.remote("kept.md", "v1");
+        f.remote("conflict.md", "v2");
+        f.remote("agent/new.md", "new");
+
+        let (plan, _) = plan(&f.project, &f.staging).unwrap();
+        assert_eq!(plan.added, vec!["agent/new.md"]);
+        assert_eq!(plan.updated, vec!["updated.md"]);
+        assert_eq!(plan.deleted, vec!["deleted.md"]);
+        assert_eq!(plan.kept_local, vec!["kept.md"]);
+        let conflicts: Vec<(&str, ConflictKind)> = plan
+            .conflicts
+            .iter()
+            .map(|c| (c.path.as_str(), c.kind))
+            .collect();
+        assert_eq!(
+            conflicts,
+            vec![
+                ("conflict.md", ConflictKind::Modified),
+                ("removed-conflict.md", ConflictKind::Removed)
+            ]
+        );
+        assert!(plan.conflicts[0]
+            .diff
+            .as_deref()
+            .is_some_and(|d| d.contains("-local edit") && d.contains("+v2")));
+    }
+
+    #[test]
+    fn without_manifest_local_files_count_as_shipped() {
+        let f = Fixture::new();
+        f.local("opencode.json", "old");
+        f.remote("opencode.json", "new");
+
+        let (plan, _) = plan(&f.project, &f.staging).unwrap();
+        assert_eq!(plan.updated, vec!["opencode.json"]);
+        assert!(plan.conflicts.is_empty());
+    }
+
+    #[test]
+    fn applies_plan_with_resolutions() {
+        let f = Fixture::new();
+        f.local("updated.md", "v1");
+        f.local("conflict.md", "v1");
+        f.local("keep.md", "v1");
+        f.local("dir/deleted.md", "v1");
+        f.record_refresh();
+        f.local("conflict.md", "local");
+        f.local("keep.md", "local");
+
+        f.remote("updated.md", "v2");
+        f.remote("conflict.md", "v2");
+        f.remote("keep.md", "v2");
+        f.remote("added.md", "new");
+
+        let (plan, remote) = plan(&f.project, &f.staging).unwrap();
+        let unresolved = apply(&f.project, &f.staging, &plan, &remote, &HashMap::new());
+        assert!(matches!(unresolved, Err(ManifestError::Unresolved(paths)) if paths.len() == 2));
+        assert_eq!(f.read("updated.md").as_deref(), Some("v1"));
+
+        let resolutions = HashMap::from([
+            ("conflict.md".to_string(), Resolution::UseRemote),
+            ("keep.md".to_string(), Resolution::KeepLocal),
+        ]);
+        apply(&f.project, &f.staging, &plan, &remote, &resolutions).unwrap();
+        assert_eq!(f.read("updated.md").as_deref(), Some("v2"));
+        assert_eq!(f.read("conflict.md").as_deref(), Some("v2"));
+        assert_eq!(f.read("keep.md").as_deref(), Some("local"));
+        assert_eq!(f.read("added.md").as_deref(), Some("new"));
+        assert!(!f.project.join("dir").exists());
+        assert_eq!(
+            Manifest::load(&f.project).unwrap().unwrap().files,
+            remote.files
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn refuses_symlinks_in_the_project() {
+        let f = Fixture::new();
+        let outside = f.root.join("outside");
+        write(&outside.join("secret.md"), "outside");
+        std::os::unix::fs::symlink(&outside, f.project.join("agent")).unwrap();
+        std::os::unix::fs::symlink(outside.join("secret.md"), f.project.join("file.md")).unwrap();
+
+        f.remote("agent/secret.md", "overwrite");
+        assert!(
+            matches!(plan(&f.project, &f.staging), Err(ManifestError::Symlink(path)) if path == "agent/secret.md")
+        );
+
+        std::fs::remove_dir_all(f.staging.join("agent")).unwrap();
+        f.remote("file.md", "overwrite");
+        assert!(
+            matches!(plan(&f.project, &f.staging), Err(ManifestError::Symlink(path)) if path == "file.md")
+        );
+
+        let forged = RefreshPlan {
+            added: vec!["file.md".to_string()],
+            ..RefreshPlan::default()
+        };
+        let result = apply(
+            &f.project,
+            &f.staging,
+            &forged,
+            &Manifest::default(),
+            &HashMap::new(),
+        );
+        assert!(matches!(result, Err(ManifestError::Symlink(_))));
+        assert_eq!(
+            std::fs::read_to_string(outside.join("secret.md")).unwrap(),
+            "outside"
+        );
+    }
+}
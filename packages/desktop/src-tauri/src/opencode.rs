@@ -1,21 +1,40 @@
+use crate::archive::{self, ArchiveError, ExtractLimits};
+use crate::config::{
+    self, ConfigChange, ConfigError, ConfigSnapshot, GlobalConfig, HistorySettings,
+};
+use crate::idle::{self, IdlePolicy};
+use crate::installs::{self, InstallMetadata};
+use crate::logs::{LogLevel, LogSink, LogStore};
+use crate::pidfile::{self, PidFile};
+use crate::supervisor::{
+    self, RestartPolicy, ServerProcess, ShutdownOutcome, StderrTail, Supervisor, STDERR_TAIL_LINES,
+};
 use futures_util::StreamExt;
 use reqwest::Client;
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
-use std::path::PathBuf;
+use sha2::{Digest, Sha256};
+use std::collections::{HashMap, VecDeque};
+use std::path::{Path, PathBuf};
 use std::process::Stdio;
+use std::sync::Arc;
 use tauri::{AppHandle, Emitter};
 use thiserror::Error;
 use tokio::io::{AsyncBufReadExt, BufReader};
 use tokio::process::{Child, Command};
 use tokio::sync::watch;
-use tracing::{error, info};
+use tracing::{debug, error, info};
 
 const GITHUB_REPO: &str = "sst/opencode";
 const GITHUB_API_URL: &str = "https://api.github.com";
-const GITHUB_LATEST_DOWNLOAD: &str = "https://github.com/sst/opencode/releases/latest/download";
-const IDLE_TIMEOUT_SECS: u64 = 30 * 60;
-const IDLE_CHECK_INTERVAL_SECS: u64 = 60;
+const GITHUB_RELEASE_DOWNLOAD: &str = "https://github.com/sst/opencode/releases/download";
+const RELEASES_PAGE_SIZE: u32 = 30;
+const DOWNLOAD_MAX_ATTEMPTS: u32 = 6;
+const DOWNLOAD_INITIAL_BACKOFF_MS: u64 = 1_000;
+const DOWNLOAD_MAX_BACKOFF_MS: u64 = 30_000;
+const DOWNLOAD_STALL_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(30);
+const DOWNLOAD_PROGRESS_INTERVAL: std::time::Duration = std::time::Duration::from_millis(250);
+const CHECKSUM_FILE_NAMES: &[&str] = &["checksums.txt", "SHA256SUMS", "sha256sums.txt"];
 
 #[derive(Debug, Error)]
 pub enum Error {
@@ -27,8 +46,8 @@ pub enum Error {
     Json(#[from] serde_json::Error),
     #[error("OpenCode is not installed")]
     NotInstalled,
-    #[error("OpenCode is already running")]
-    AlreadyRunning,
+    #[error("OpenCode is already running for site {0}")]
+    AlreadyRunning(String),
     #[error("Failed to find available port")]
     NoAvailablePort,
     #[error("Unsupported platform: {0}")]
@@ -37,9 +56,43 @@ pub enum Error {
     ExtractionFailed(String),
     #[error("Download failed: {0}")]
     DownloadFailed(String),
+    #[error("OpenCode version {0} is not installed")]
+    VersionNotInstalled(String),
+    #[error("No previous OpenCode version to roll back to")]
+    NoPreviousVersion,
+    #[error("Install verification failed: {0}")]
+    InstallVerificationFailed(String),
+    #[error("Download cancelled")]
+    DownloadCancelled,
+    #[error("No published SHA-256 digest for {0}")]
+    ChecksumUnavailable(String),
+    #[error("Checksum mismatch for {archive}: expected {expected}, got {actual}")]
+    ChecksumMismatch {
+        archive: String,
+        expected: String,
+        actual: String,
+    },
+    #[error("Project folder {0} does not exist")]
+    ProjectDirMissing(PathBuf),
+    #[error("Failed to launch OpenCode: {0}")]
+    SpawnFailed(std::io::Error),
+    #[error("OpenCode exited during startup with {}", .0.map_or("a signal".to_string(), |c| format!("code {}", c)))]
+    ExitedEarly(Option<i32>),
+    #[error("OpenCode failed to start within timeout")]
+    StartTimeout,
 }
 
-pub type GlobalConfig = Value;
+impl Error {
+    /// Whether a failed start points at the binary itself rather than at
+    /// the environment it was started in.
+    fn implicates_binary(&self) -> bool {
+        match self {
+            Error::SpawnFailed(_) => true,
+            Error::ExitedEarly(code) => *code != Some(0),
+            _ => false,
+        }
+    }
+}
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "snake_case")]
@@ -54,25 +107,161 @@ pub enum Status {
 #[derive(Debug, Deserialize)]
 struct GitHubRelease {
     tag_name: String,
+    #[serde(default)]
+    name: Option<String>,
+    #[serde(default)]
+    published_at: Option<String>,
+    #[serde(default)]
+    prerelease: bool,
+    #[serde(default)]
+    draft: bool,
+    #[serde(default)]
+    assets: Vec<GitHubAsset>,
+}
+
+#[derive(Debug, Deserialize)]
+struct GitHubAsset {
+    name: String,
+    browser_download_url: String,
+    #[serde(default)]
+    digest: Option<String>,
 }
 
-#[derive(Deserialize)]
-struct SessionTime {
-    updated: Option<u64>,
+#[derive(Debug, Clone, Serialize)]
+pub struct ReleaseInfo {
+    pub tag: String,
+    pub version: String,
+    pub name: Option<String>,
+    pub published_at: Option<String>,
+    pub prerelease: bool,
+    pub installed: bool,
+    pub active: bool,
 }
 
-#[derive(Deserialize)]
-struct SessionInfo {
-    time: SessionTime,
+/// Lets a download be cancelled from another command while the manager is
+/// locked for the duration of the transfer.
+#[derive(Clone)]
+pub struct DownloadCancel {
+    tx: Arc<watch::Sender<bool>>,
+}
+
+impl DownloadCancel {
+    fn new() -> Self {
+        Self {
+            tx: Arc::new(watch::channel(false).0),
+        }
+    }
+
+    pub fn cancel(&self) {
+        self.tx.send_replace(true);
+    }
+
+    fn reset(&self) -> watch::Receiver<bool> {
+        self.tx.send_replace(false);
+        self.tx.subscribe()
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ServerInfo {
+    pub site_id: String,
+    pub port: u16,
+    pub project_dir: PathBuf,
+    pub cors_origin: String,
+    pub status: Status,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct StatusPayload<'a> {
+    pub site_id: &'a str,
+    pub status: &'a Status,
+}
+
+/// What it takes to (re)spawn the OpenCode server of one site.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ServerSpec {
+    pub site_id: String,
+    pub binary: PathBuf,
+    pub port: u16,
+    pub cors_origin: String,
+    pub project_dir: PathBuf,
+    pub state_dir: PathBuf,
+    pub config_dir: PathBuf,
+    /// Where the pidfile goes.
+    #[serde(skip)]
+    pub run_dir: PathBuf,
+}
+
+impl ServerSpec {
+    fn command(&self) -> Command {
+        let mut cmd = Command::new(&self.binary);
+        cmd.args(["serve", "--port", &self.port.to_string()]);
+        cmd.args(["--cors", &self.cors_origin]);
+
+        cmd.env("OPENCODE_CLIENT", "wordforge-desktop");
+        cmd.env("OPENCODE_AUTO_SHARE", "false");
+        cmd.env("OPENCODE_DISABLE_AUTOUPDATE", "true");
+        cmd.env("OPENCODE_DISABLE_LSP_DOWNLOAD", "true");
+        cmd.env("OPENCODE_FAKE_VCS", "git");
+
+        cmd.env(
+            "XDG_DATA_HOME",
+            self.state_dir.join("data").to_string_lossy().to_string(),
+        );
+        cmd.env(
+            "XDG_CONFIG_HOME",
+            self.state_dir.join("config").to_string_lossy().to_string(),
+        );
+        cmd.env(
+            "XDG_STATE_HOME",
+            self.state_dir.join("state").to_string_lossy().to_string(),
+        );
+        cmd.env(
+            "XDG_CACHE_HOME",
+            self.state_dir.join("cache").to_string_lossy().to_string(),
+        );
+        cmd.env(
+            "OPENCODE_CONFIG_DIR",
+            self.config_dir.to_string_lossy().to_string(),
+        );
+
+        cmd.current_dir(&self.project_dir);
+
+        cmd.stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true);
+
+        cmd
+    }
+}
+
+struct ServerInstance {
+    spec: ServerSpec,
+    status: Arc<std::sync::Mutex<Status>>,
+    supervisor_stop: watch::Sender<bool>,
+    supervisor: tokio::task::JoinHandle<Option<ShutdownOutcome>>,
+    idle_monitor_stop: watch::Sender<bool>,
+    idle_policy: watch::Sender<IdlePolicy>,
+}
+
+impl ServerInstance {
+    fn status(&self) -> Status {
+        self.status
+            .lock()
+            .map(|s| s.clone())
+            .unwrap_or(Status::Error("Status unavailable".into()))
+    }
 }
 
 pub struct OpenCodeManager {
     app: AppHandle,
     client: Client,
-    process: Option<Child>,
-    port: Option<u16>,
+    servers: HashMap<String, ServerInstance>,
     install_dir: PathBuf,
-    idle_monitor_stop: Option<watch::Sender<bool>>,
+    installs: InstallMetadata,
+    download_cancel: DownloadCancel,
+    restart_policy: RestartPolicy,
+    logs: LogSink,
 }
 
 impl OpenCodeManager {
@@ -80,34 +269,43 @@ impl OpenCodeManager {
         let base_dir = dirs::data_local_dir()
             .unwrap_or_else(|| PathBuf::from("."))
             .join("wordforge");
-        
+
         let install_dir = base_dir.join("opencode");
+        let installs = InstallMetadata::load(&install_dir);
+        let restart_policy = RestartPolicy::load(&install_dir);
+        let logs = LogSink::new(app.clone(), Arc::new(LogStore::new(base_dir.join("logs"))));
 
         Self {
             app,
             client: Client::new(),
-            process: None,
-            port: None,
+            servers: HashMap::new(),
             install_dir,
-            idle_monitor_stop: None,
+            installs,
+            download_cancel: DownloadCancel::new(),
+            restart_policy,
+            logs,
         }
     }
-    
+
+    fn run_dir(&self) -> PathBuf {
+        self.install_dir.join("run")
+    }
+
     fn isolated_state_dir(&self) -> PathBuf {
-        self.install_dir.parent()
+        self.install_dir
+            .parent()
             .unwrap_or(&self.install_dir)
             .join("opencode-state")
     }
 
-    pub async fn get_status(&self) -> Status {
+    pub async fn get_status(&self, site_id: Option<&str>) -> Status {
         if !self.is_installed().await {
             return Status::NotInstalled;
         }
 
-        if self.process.is_some() && self.port.is_some() {
-            Status::Running
-        } else {
-            Status::Stopped
+        match site_id.and_then(|id| self.servers.get(id)) {
+            Some(server) => server.status(),
+            None => Status::Stopped,
         }
     }
 
@@ -116,8 +314,7 @@ impl OpenCodeManager {
     }
 
     pub async fn get_installed_version(&self) -> Option<String> {
-        let version_file = self.install_dir.join(".version");
-        tokio::fs::read_to_string(version_file).await.ok()
+        self.installs.active.clone()
     }
 
     pub async fn get_latest_version(&self) -> Result<String, Error> {
@@ -126,38 +323,134 @@ impl OpenCodeManager {
     }
 
     pub async fn check_update_available(&self) -> Result<bool, Error> {
+        if self.installs.pinned {
+            return Ok(false);
+        }
+
         let installed = self.get_installed_version().await;
         let latest = self.get_latest_version().await?;
 
         match installed {
-            Some(v) => Ok(v.trim() != latest.trim()),
+            Some(v) => Ok(v != installs::normalize_version(&latest)),
             None => Ok(true),
         }
     }
 
+    pub fn get_installs(&self) -> InstallMetadata {
+        self.installs.clone()
+    }
+
+    pub async fn list_releases(&self) -> Result<Vec<ReleaseInfo>, Error> {
+        let url = format!(
+            "{}/repos/{}/releases?per_page={}",
+            GITHUB_API_URL, GITHUB_REPO, RELEASES_PAGE_SIZE
+        );
+        let releases = self
+            .client
+            .get(&url)
+            .header("User-Agent", "wordforge-desktop")
+            .send()
+            .await?
+            .error_for_status()?
+            .json::<Vec<GitHubRelease>>()
+            .await?;
+
+        Ok(releases
+            .into_iter()
+            .filter(|r| !r.draft)
+            .map(|r| {
+                let version = installs::normalize_version(&r.tag_name);
+                ReleaseInfo {
+                    installed: self.installs.is_installed(&version),
+                    active: self.installs.active.as_deref() == Some(version.as_str()),
+                    tag: r.tag_name,
+                    version,
+                    name: r.name,
+                    published_at: r.published_at,
+                    prerelease: r.prerelease,
+                }
+            })
+            .collect())
+    }
+
+    /// Installs the latest release and makes it active.
     pub async fn download(&mut self, app: &AppHandle) -> Result<(), Error> {
-        info!("Starting OpenCode download");
+        self.emit_progress(app, "Checking latest release...", 0);
+        let release = self.fetch_latest_release().await?;
+        self.install_version(app, &release.tag_name).await
+    }
+
+    /// Installs the release tagged `tag` next to the existing versions and
+    /// makes it active. Already installed versions are only re-activated.
+    pub async fn install_version(&mut self, app: &AppHandle, tag: &str) -> Result<(), Error> {
+        let version = installs::normalize_version(tag);
+        let version_dir = installs::version_dir(&self.install_dir, &version);
+
+        if self.installs.is_installed(&version)
+            && version_dir.join(installs::binary_name()).exists()
+        {
+            info!("OpenCode {} already installed, activating it", version);
+            self.installs.activate(&version);
+            self.installs.save(&self.install_dir).await?;
+            self.emit_progress(app, "Download complete!", 100);
+            return Ok(());
+        }
+
+        info!("Starting OpenCode {} download", tag);
         self.emit_progress(app, "Preparing download...", 0);
 
         let archive_name = get_archive_name()?;
-        let download_url = format!("{}/{}", GITHUB_LATEST_DOWNLOAD, archive_name);
-
-        self.emit_progress(app, "Creating directories...", 10);
-        tokio::fs::create_dir_all(&self.install_dir).await?;
+        let release = self.fetch_release(tag).await?;
+        let expected_digest = self
+            .resolve_expected_digest(&release, &archive_name)
+            .await?;
+        let download_url = release
+            .assets
+            .iter()
+            .find(|a| a.name == archive_name)
+            .map(|a| a.browser_download_url.clone())
+            .unwrap_or_else(|| format!("{}/{}/{}", GITHUB_RELEASE_DOWNLOAD, tag, archive_name));
 
         self.emit_progress(app, "Downloading OpenCode...", 20);
-        let archive_path = self.install_dir.join(&archive_name);
-        self.download_file(&download_url, &archive_path, app).await?;
+        let downloads_dir = self.install_dir.join("downloads").join(&version);
+        tokio::fs::create_dir_all(&downloads_dir).await?;
+        let archive_path = downloads_dir.join(&archive_name);
+        self.download_file(&download_url, &archive_path, app)
+            .await?;
 
-        self.emit_progress(app, "Extracting archive...", 80);
-        self.extract_archive(&archive_path).await?;
+        self.emit_progress(app, "Verifying checksum...", 78);
+        if let Err(e) = verify_checksum(&archive_path, &archive_name, &expected_digest).await {
+            tokio::fs::remove_file(&archive_path).await.ok();
+            return Err(e);
+        }
 
-        self.emit_progress(app, "Detecting version...", 95);
-        let version = self.detect_installed_version().unwrap_or_else(|| "unknown".to_string());
-        let version_file = self.install_dir.join(".version");
-        tokio::fs::write(&version_file, &version).await?;
+        self.emit_progress(app, "Extracting archive...", 80);
+        let staging_dir = installs::staging_dir(&self.install_dir, &version);
+        if staging_dir.exists() {
+            tokio::fs::remove_dir_all(&staging_dir).await?;
+        }
+        tokio::fs::create_dir_all(&staging_dir).await?;
 
+        let staged = self
+            .stage_install(&archive_path, &staging_dir, tag, &version)
+            .await;
         tokio::fs::remove_file(&archive_path).await.ok();
+        if let Err(e) = staged {
+            tokio::fs::remove_dir_all(&staging_dir).await.ok();
+            return Err(e);
+        }
+
+        self.emit_progress(app, "Installing...", 95);
+        if let Err(e) = self.swap_into_place(&staging_dir, &version).await {
+            tokio::fs::remove_dir_all(&staging_dir).await.ok();
+            return Err(e);
+        }
+
+        self.installs
+            .register(&version, tag, Some(&expected_digest));
+        self.installs.activate(&version);
+        self.installs.unverified = Some(version.clone());
+        self.installs.save(&self.install_dir).await?;
 
         self.emit_progress(app, "Download complete!", 100);
         info!("OpenCode {} installed successfully", version);
@@ -165,132 +458,426 @@ impl OpenCodeManager {
         Ok(())
     }
 
-    fn detect_installed_version(&self) -> Option<String> {
-        let binary = self.binary_path();
-        if !binary.exists() {
-            return None;
+    /// Extracts the archive into `staging_dir` and makes sure the binary it
+    /// contains actually runs before anything touches the live install.
+    async fn stage_install(
+        &self,
+        archive_path: &Path,
+        staging_dir: &Path,
+        tag: &str,
+        version: &str,
+    ) -> Result<(), Error> {
+        self.extract_archive(archive_path, staging_dir).await?;
+
+        let staged_binary = staging_dir.join(installs::binary_name());
+        let detected = tokio::task::spawn_blocking(move || detect_binary_version(&staged_binary))
+            .await
+            .map_err(|e| Error::InstallVerificationFailed(e.to_string()))?;
+
+        match detected {
+            Some(detected) if !same_version(&detected, version) => {
+                Err(Error::InstallVerificationFailed(format!(
+                    "release {} reports version {}, expected {}",
+                    tag, detected, version
+                )))
+            }
+            Some(_) => Ok(()),
+            None => Err(Error::InstallVerificationFailed(format!(
+                "`opencode version` failed for release {}",
+                tag
+            ))),
         }
-        
-        let output = std::process::Command::new(&binary)
-            .arg("version")
-            .output()
-            .ok()?;
-        
-        if !output.status.success() {
-            return None;
+    }
+
+    /// Renames the verified staging directory over `versions/<version>`.
+    /// An existing copy of the same version is moved aside and kept until the
+    /// new one has started successfully once.
+    async fn swap_into_place(&self, staging_dir: &Path, version: &str) -> Result<(), Error> {
+        let version_dir = installs::version_dir(&self.install_dir, version);
+        let backup_dir = installs::backup_dir(&self.install_dir, version);
+
+        if let Some(parent) = version_dir.parent() {
+            tokio::fs::create_dir_all(parent).await?;
         }
-        
-        let version_str = String::from_utf8_lossy(&output.stdout);
-        let re = regex::Regex::new(r"(\d+\.\d+\.\d+)").ok()?;
-        re.captures(&version_str)
-            .and_then(|c| c.get(1))
-            .map(|m| m.as_str().to_string())
+
+        let had_existing = version_dir.exists();
+        if had_existing {
+            if backup_dir.exists() {
+                tokio::fs::remove_dir_all(&backup_dir).await?;
+            }
+            if let Some(parent) = backup_dir.parent() {
+                tokio::fs::create_dir_all(parent).await?;
+            }
+            tokio::fs::rename(&version_dir, &backup_dir).await?;
+        }
+
+        if let Err(e) = tokio::fs::rename(staging_dir, &version_dir).await {
+            if had_existing {
+                tokio::fs::rename(&backup_dir, &version_dir).await.ok();
+            }
+            return Err(e.into());
+        }
+
+        Ok(())
     }
 
+    /// Called once the active binary has served a request: the install is
+    /// known good, so the copy kept for recovery can go.
+    async fn confirm_install(&mut self) {
+        let Some(version) = self.installs.unverified.take() else {
+            return;
+        };
+
+        let backup_dir = installs::backup_dir(&self.install_dir, &version);
+        if backup_dir.exists() {
+            tokio::fs::remove_dir_all(&backup_dir).await.ok();
+        }
+
+        if let Err(e) = self.installs.save(&self.install_dir).await {
+            tracing::warn!("Failed to persist install metadata: {}", e);
+        }
+        info!(
+            "OpenCode {} started successfully, install confirmed",
+            version
+        );
+    }
+
+    /// Called when a freshly installed version fails its first start in a
+    /// way that implicates the binary: put back the copy it replaced, or
+    /// fall back to the previous version.
+    async fn revert_unverified_install(&mut self) {
+        let Some(version) = self.installs.unverified.take() else {
+            return;
+        };
+
+        let version_dir = installs::version_dir(&self.install_dir, &version);
+        let backup_dir = installs::backup_dir(&self.install_dir, &version);
+
+        if backup_dir.exists() {
+            tokio::fs::remove_dir_all(&version_dir).await.ok();
+            match tokio::fs::rename(&backup_dir, &version_dir).await {
+                Ok(()) => tracing::warn!(
+                    "OpenCode {} failed to start, restored the previous copy",
+                    version
+                ),
+                Err(e) => error!(
+                    "Failed to restore previous copy of OpenCode {}: {}",
+                    version, e
+                ),
+            }
+        } else if let Some(previous) = self.installs.rollback() {
+            tracing::warn!(
+                "OpenCode {} failed to start, rolled back to {}",
+                version,
+                previous
+            );
+        }
+
+        if let Err(e) = self.installs.save(&self.install_dir).await {
+            tracing::warn!("Failed to persist install metadata: {}", e);
+        }
+    }
+
+    /// Switches the active version. Pinned versions are never reported as
+    /// outdated, so update prompts stay quiet until the pin is released.
+    pub async fn set_active_version(&mut self, version: &str, pinned: bool) -> Result<(), Error> {
+        let version = installs::normalize_version(version);
+        if !self.installs.is_installed(&version) {
+            return Err(Error::VersionNotInstalled(version));
+        }
+
+        self.installs.select(&version, pinned);
+        self.installs.save(&self.install_dir).await?;
+
+        info!(
+            "Active OpenCode version set to {} (pinned: {})",
+            version, pinned
+        );
+        Ok(())
+    }
+
+    /// Goes back to the previously active version and pins it, so the release
+    /// that was rolled back is not offered again as an update.
+    pub async fn rollback(&mut self) -> Result<String, Error> {
+        let version = self.installs.rollback().ok_or(Error::NoPreviousVersion)?;
+        self.installs.pinned = true;
+        self.installs.save(&self.install_dir).await?;
+
+        info!("Rolled back OpenCode to version {}", version);
+        Ok(version)
+    }
+
+    /// Starts the server of `site_id` and hands it to a supervisor task that
+    /// restarts it according to the restart policy if it crashes.
     pub async fn start(
-        &mut self, 
-        cors_origin: Option<String>,
-        project_dir: Option<std::path::PathBuf>,
+        &mut self,
+        site_id: &str,
+        cors_origin: String,
+        project_dir: PathBuf,
+        idle_policy: IdlePolicy,
     ) -> Result<u16, Error> {
-        if self.process.is_some() {
-            return Err(Error::AlreadyRunning);
+        if let Some(server) = self.servers.get(site_id) {
+            if !server.supervisor.is_finished() {
+                return Err(Error::AlreadyRunning(site_id.to_string()));
+            }
+            // The supervisor gave up on a crashed server, start from scratch.
+            self.servers.remove(site_id);
         }
 
         if !self.is_installed().await {
             return Err(Error::NotInstalled);
         }
 
-        let port = self.get_or_assign_port().await?;
-        info!("Starting OpenCode on port {}", port);
-
-        let binary = self.binary_path();
-        let mut cmd = Command::new(&binary);
-        cmd.args(["serve", "--port", &port.to_string()]);
-        
-        if let Some(cors) = cors_origin {
-            cmd.args(["--cors", &cors]);
-        }
-        
-        cmd.env("OPENCODE_CLIENT", "wordforge-desktop");
-        cmd.env("OPENCODE_AUTO_SHARE", "false");
-        cmd.env("OPENCODE_DISABLE_AUTOUPDATE", "true");
-        cmd.env("OPENCODE_DISABLE_LSP_DOWNLOAD", "true");
-        cmd.env("OPENCODE_FAKE_VCS", "git");
-        
-        let state_dir = self.isolated_state_dir();
-        let global_config_dir = self.install_dir.join("config");
-        cmd.env("XDG_DATA_HOME", state_dir.join("data").to_string_lossy().to_string());
-        cmd.env("XDG_CONFIG_HOME", state_dir.join("config").to_string_lossy().to_string());
-        cmd.env("XDG_STATE_HOME", state_dir.join("state").to_string_lossy().to_string());
-        cmd.env("XDG_CACHE_HOME", state_dir.join("cache").to_string_lossy().to_string());
-        cmd.env("OPENCODE_CONFIG_DIR", global_config_dir.to_string_lossy().to_string());
-        
-        if let Some(ref dir) = project_dir {
-            cmd.current_dir(dir);
-        }
-        
-        cmd.stdout(Stdio::piped())
-            .stderr(Stdio::piped())
-            .kill_on_drop(true);
+        let port = self.get_or_assign_port(site_id).await?;
+        info!("Starting OpenCode for site {} on port {}", site_id, port);
+
+        let spec = ServerSpec {
+            site_id: site_id.to_string(),
+            binary: self.binary_path(),
+            port,
+            cors_origin,
+            project_dir,
+            state_dir: self.isolated_state_dir(),
+            config_dir: self.install_dir.join("config"),
+            run_dir: self.run_dir(),
+        };
+        let stderr_tail: StderrTail = Arc::new(std::sync::Mutex::new(VecDeque::new()));
+
+        emit_status(&self.app, site_id, &Status::Starting);
+        let child = match spawn_server(&self.client, &spec, &stderr_tail, &self.logs).await {
+            Ok(child) => child,
+            Err(e) => {
+                if e.implicates_binary() {
+                    self.revert_unverified_install().await;
+                }
+                emit_status(&self.app, site_id, &Status::Error(e.to_string()));
+                return Err(e);
+            }
+        };
+        self.confirm_install().await;
+        self.supervise(
+            spec,
+            ServerProcess::Spawned(child),
+            stderr_tail,
+            idle_policy,
+        );
 
-        let mut child = cmd.spawn()?;
+        Ok(port)
+    }
 
-        self.spawn_log_handler(&mut child);
-        self.process = Some(child);
-        self.port = Some(port);
+    /// Looks for servers that outlived a previous run of the app. A live one
+    /// that runs the active binary and still answers is adopted and
+    /// supervised again; any other one is terminated, which frees its saved
+    /// port for the next start.
+    pub async fn reclaim_orphans(&mut self, idle_policies: &HashMap<String, IdlePolicy>) {
+        let run_dir = self.run_dir();
+
+        for orphan in PidFile::load_all(&run_dir) {
+            let site_id = orphan.spec.site_id.clone();
+            if self.servers.contains_key(&site_id) {
+                continue;
+            }
+            if !pidfile::is_alive(orphan.pid) || !orphan.is_ours() {
+                PidFile::remove(&run_dir, &site_id);
+                continue;
+            }
 
-        self.wait_for_ready(port).await?;
-        self.spawn_idle_monitor(port);
+            let healthy = self
+                .client
+                .get(format!("http://localhost:{}/", orphan.spec.port))
+                .timeout(std::time::Duration::from_secs(2))
+                .send()
+                .await
+                .is_ok();
+
+            // Servers of sites that were removed meanwhile are not adopted.
+            let idle_policy = idle_policies
+                .get(&site_id)
+                .filter(|_| healthy && orphan.spec.binary == self.binary_path())
+                .cloned();
+            if let Some(idle_policy) = idle_policy {
+                info!(
+                    "Adopting orphaned OpenCode for site {} (pid {}, port {})",
+                    site_id, orphan.pid, orphan.spec.port
+                );
+                self.logs.push(
+                    &site_id,
+                    &format!("Adopted OpenCode server left by a previous run (pid {}), its output is not captured", orphan.pid),
+                    LogLevel::Warn,
+                );
+                let stderr_tail: StderrTail = Arc::new(std::sync::Mutex::new(VecDeque::new()));
+                self.supervise(
+                    orphan.spec,
+                    ServerProcess::Adopted(orphan.pid),
+                    stderr_tail,
+                    idle_policy,
+                );
+            } else {
+                info!(
+                    "Terminating orphaned OpenCode for site {} (pid {})",
+                    site_id, orphan.pid
+                );
+                let grace = std::time::Duration::from_secs(self.restart_policy.shutdown_grace_secs);
+                supervisor::terminate(&site_id, &mut ServerProcess::Adopted(orphan.pid), grace)
+                    .await;
+                PidFile::remove(&run_dir, &site_id);
+            }
+        }
+    }
 
-        Ok(port)
+    fn supervise(
+        &mut self,
+        spec: ServerSpec,
+        process: ServerProcess,
+        stderr_tail: StderrTail,
+        idle_policy: IdlePolicy,
+    ) {
+        let site_id = spec.site_id.clone();
+        let port = spec.port;
+        let status = Arc::new(std::sync::Mutex::new(Status::Running));
+        emit_status(&self.app, &site_id, &Status::Running);
+
+        let (supervisor_stop, stop_rx) = watch::channel(false);
+        let supervisor = Supervisor {
+            app: self.app.clone(),
+            client: self.client.clone(),
+            spec: spec.clone(),
+            policy: self.restart_policy.clone(),
+            status: status.clone(),
+            stderr_tail,
+            logs: self.logs.clone(),
+        };
+        let supervisor = tokio::spawn(supervisor.run(process, stop_rx));
+
+        let (idle_monitor_stop, idle_stop_rx) = watch::channel(false);
+        let (idle_policy, idle_policy_rx) = watch::channel(idle_policy);
+        idle::spawn_monitor(
+            self.app.clone(),
+            self.client.clone(),
+            site_id.clone(),
+            port,
+            idle_policy_rx,
+            idle_stop_rx,
+        );
+
+        self.servers.insert(
+            site_id,
+            ServerInstance {
+                spec,
+                status,
+                supervisor_stop,
+                supervisor,
+                idle_monitor_stop,
+                idle_policy,
+            },
+        );
     }
 
-    pub async fn stop(&mut self) -> Result<(), Error> {
-        if let Some(tx) = self.idle_monitor_stop.take() {
-            tx.send(true).ok();
+    /// Applies a changed idle policy to the site's running server, if any.
+    pub fn set_idle_policy(&self, site_id: &str, policy: IdlePolicy) {
+        if let Some(server) = self.servers.get(site_id) {
+            server.idle_policy.send_replace(policy);
         }
-        
-        if let Some(mut process) = self.process.take() {
-            info!("Stopping OpenCode");
-            process.kill().await.ok();
-            self.port = None;
+    }
+
+    /// Stops the site's server, giving it the policy's grace period to exit
+    /// on its own. Returns `None` if no process was running.
+    pub async fn stop(&mut self, site_id: &str) -> Result<Option<ShutdownOutcome>, Error> {
+        match self.servers.remove(site_id) {
+            Some(server) => Ok(shutdown_server(&self.app, site_id, server).await),
+            None => Ok(None),
         }
+    }
+
+    /// Stops every server concurrently, so quitting the app waits for at
+    /// most one grace period.
+    pub async fn stop_all(&mut self) -> Result<Vec<ShutdownOutcome>, Error> {
+        let app = &self.app;
+        let outcomes =
+            futures_util::future::join_all(self.servers.drain().map(
+                |(site_id, server)| async move { shutdown_server(app, &site_id, server).await },
+            ))
+            .await;
+        Ok(outcomes.into_iter().flatten().collect())
+    }
+
+    /// Port of the site's server, as long as it is up and serving.
+    pub fn get_port(&self, site_id: &str) -> Option<u16> {
+        self.servers
+            .get(site_id)
+            .filter(|s| matches!(s.status(), Status::Running))
+            .map(|s| s.spec.port)
+    }
+
+    /// Whether the site has a supervised server, even one that is currently
+    /// restarting after a crash.
+    pub fn has_server(&self, site_id: &str) -> bool {
+        self.servers
+            .get(site_id)
+            .is_some_and(|s| !s.supervisor.is_finished())
+    }
+
+    pub fn get_restart_policy(&self) -> RestartPolicy {
+        self.restart_policy.clone()
+    }
+
+    /// Updates the restart policy. Servers pick it up on their next start.
+    pub async fn set_restart_policy(&mut self, policy: RestartPolicy) -> Result<(), Error> {
+        policy.save(&self.install_dir).await?;
+        self.restart_policy = policy;
         Ok(())
     }
 
-    pub fn get_port(&self) -> Option<u16> {
-        self.port
+    pub fn list_servers(&self) -> Vec<ServerInfo> {
+        self.servers
+            .iter()
+            .map(|(site_id, server)| ServerInfo {
+                site_id: site_id.clone(),
+                port: server.spec.port,
+                project_dir: server.spec.project_dir.clone(),
+                cors_origin: server.spec.cors_origin.clone(),
+                status: server.status(),
+            })
+            .collect()
     }
 
-    async fn get_or_assign_port(&self) -> Result<u16, Error> {
-        let port_file = self.install_dir.join(".port");
-        
+    async fn get_or_assign_port(&self, site_id: &str) -> Result<u16, Error> {
+        let ports_dir = self.install_dir.join(".ports");
+        let port_file = ports_dir.join(site_id);
+        let taken: Vec<u16> = self.servers.values().map(|s| s.spec.port).collect();
+
         if let Ok(content) = tokio::fs::read_to_string(&port_file).await {
             if let Ok(saved_port) = content.trim().parse::<u16>() {
-                if portpicker::is_free(saved_port) {
-                    info!("Reusing saved port {}", saved_port);
+                if !taken.contains(&saved_port) && portpicker::is_free(saved_port) {
+                    info!("Reusing saved port {} for site {}", saved_port, site_id);
                     return Ok(saved_port);
                 }
-                info!("Saved port {} is in use, picking new one", saved_port);
+                info!(
+                    "Saved port {} for site {} is in use, picking new one",
+                    saved_port, site_id
+                );
             }
         }
-        
+
         let new_port = portpicker::pick_unused_port().ok_or(Error::NoAvailablePort)?;
+        if let Err(e) = tokio::fs::create_dir_all(&ports_dir).await {
+            tracing::warn!("Failed to create ports directory: {}", e);
+        }
         if let Err(e) = tokio::fs::write(&port_file, new_port.to_string()).await {
             tracing::warn!("Failed to persist port to file: {}", e);
         }
-        info!("Assigned new port {}", new_port);
-        
+        info!("Assigned new port {} to site {}", new_port, site_id);
+
         Ok(new_port)
     }
 
     fn binary_path(&self) -> PathBuf {
-        #[cfg(target_os = "windows")]
-        let name = "opencode.exe";
-        #[cfg(not(target_os = "windows"))]
-        let name = "opencode";
-
-        self.install_dir.join(name)
+        match &self.installs.active {
+            Some(version) => {
+                installs::version_dir(&self.install_dir, version).join(installs::binary_name())
+            }
+            None => self.install_dir.join(installs::binary_name()),
+        }
     }
 
     async fn fetch_latest_release(&self) -> Result<GitHubRelease, Error> {
@@ -307,59 +894,262 @@ impl OpenCodeManager {
         Ok(response)
     }
 
-    async fn download_file(
-        &self,
-        url: &str,
-        path: &PathBuf,
-        app: &AppHandle,
-    ) -> Result<(), Error> {
+    async fn fetch_release(&self, tag: &str) -> Result<GitHubRelease, Error> {
+        let url = format!(
+            "{}/repos/{}/releases/tags/{}",
+            GITHUB_API_URL, GITHUB_REPO, tag
+        );
         let response = self
+            .client
+            .get(&url)
+            .header("User-Agent", "wordforge-desktop")
+            .send()
+            .await?
+            .error_for_status()?
+            .json::<GitHubRelease>()
+            .await?;
+        Ok(response)
+    }
+
+    /// Finds the published SHA-256 of `archive_name`, preferring the digest
+    /// GitHub attaches to the asset and falling back to a checksum file
+    /// shipped with the release.
+    async fn resolve_expected_digest(
+        &self,
+        release: &GitHubRelease,
+        archive_name: &str,
+    ) -> Result<String, Error> {
+        let asset_digest = release
+            .assets
+            .iter()
+            .find(|a| a.name == archive_name)
+            .and_then(|a| a.digest.as_deref())
+            .and_then(|d| d.strip_prefix("sha256:"))
+            .map(|d| d.to_ascii_lowercase());
+
+        if let Some(digest) = asset_digest {
+            return Ok(digest);
+        }
+
+        let sidecar_name = format!("{}.sha256", archive_name);
+        let checksum_assets = release
+            .assets
+            .iter()
+            .filter(|a| a.name == sidecar_name || CHECKSUM_FILE_NAMES.contains(&a.name.as_str()));
+
+        for asset in checksum_assets {
+            let content = match self.fetch_text(&asset.browser_download_url).await {
+                Ok(content) => content,
+                Err(e) => {
+                    tracing::warn!("Failed to fetch checksum file {}: {}", asset.name, e);
+                    continue;
+                }
+            };
+
+            let sidecar = asset.name == sidecar_name;
+            if let Some(digest) = parse_checksum_file(&content, archive_name, sidecar) {
+                return Ok(digest);
+            }
+        }
+
+        Err(Error::ChecksumUnavailable(archive_name.to_string()))
+    }
+
+    async fn fetch_text(&self, url: &str) -> Result<String, Error> {
+        let text = self
             .client
             .get(url)
             .header("User-Agent", "wordforge-desktop")
             .send()
             .await?
-            .error_for_status()?;
+            .error_for_status()?
+            .text()
+            .await?;
+        Ok(text)
+    }
+
+    /// Downloads `url` to `path` through a `.part` file that survives
+    /// interruptions. Transient failures resume from the bytes already on
+    /// disk using HTTP Range requests, with exponential backoff between
+    /// attempts.
+    async fn download_file(&self, url: &str, path: &Path, app: &AppHandle) -> Result<(), Error> {
+        let mut part_name = path.file_name().unwrap_or_default().to_os_string();
+        part_name.push(".part");
+        let part_path = path.with_file_name(part_name);
+        let mut cancel_rx = self.download_cancel.reset();
+        let mut attempt: u32 = 0;
+
+        loop {
+            let before = file_len(&part_path).await;
+            let result = self
+                .download_attempt(url, &part_path, app, &mut cancel_rx)
+                .await;
+
+            let error = match result {
+                Ok(()) => break,
+                Err(e) if !is_transient(&e) => return Err(e),
+                Err(e) => e,
+            };
+
+            if file_len(&part_path).await > before {
+                attempt = 0;
+            }
+            attempt += 1;
+
+            if attempt >= DOWNLOAD_MAX_ATTEMPTS {
+                return Err(Error::DownloadFailed(format!(
+                    "giving up after {} attempts: {}",
+                    attempt, error
+                )));
+            }
 
-        let total_size = response.content_length().unwrap_or(0);
-        let mut downloaded: u64 = 0;
-        let mut file = tokio::fs::File::create(path).await?;
+            let delay = download_backoff(attempt);
+            tracing::warn!(
+                "Download interrupted ({}), retrying in {}s",
+                error,
+                delay.as_secs()
+            );
+            self.emit_progress(
+                app,
+                &format!("Connection lost, retrying in {}s...", delay.as_secs()),
+                20,
+            );
+
+            tokio::select! {
+                _ = tokio::time::sleep(delay) => {}
+                _ = cancelled(&mut cancel_rx) => return Err(Error::DownloadCancelled),
+            }
+        }
+
+        tokio::fs::rename(&part_path, path).await?;
+        Ok(())
+    }
+
+    async fn download_attempt(
+        &self,
+        url: &str,
+        part_path: &Path,
+        app: &AppHandle,
+        cancel_rx: &mut watch::Receiver<bool>,
+    ) -> Result<(), Error> {
+        let existing = file_len(part_path).await;
+
+        let mut request = self
+            .client
+            .get(url)
+            .header("User-Agent", "wordforge-desktop");
+        if existing > 0 {
+            info!("Resuming download at byte {}", existing);
+            request = request.header(reqwest::header::RANGE, format!("bytes={}-", existing));
+        }
+
+        let response = request.send().await?;
+
+        if existing > 0 && response.status() == reqwest::StatusCode::RANGE_NOT_SATISFIABLE {
+            info!("Partial download already complete");
+            return Ok(());
+        }
+
+        let response = response.error_for_status()?;
+        let resumed = response.status() == reqwest::StatusCode::PARTIAL_CONTENT;
+
+        let mut downloaded = if resumed { existing } else { 0 };
+        let total_size = response.content_length().map(|len| len + downloaded);
+        let mut file = if resumed {
+            tokio::fs::OpenOptions::new()
+                .append(true)
+                .open(part_path)
+                .await?
+        } else {
+            tokio::fs::File::create(part_path).await?
+        };
         let mut stream = response.bytes_stream();
 
-        while let Some(chunk) = stream.next().await {
-            let chunk = chunk?;
+        let session_start = downloaded;
+        let started_at = std::time::Instant::now();
+        let mut last_emit: Option<std::time::Instant> = None;
+
+        loop {
+            let next = tokio::select! {
+                next = tokio::time::timeout(DOWNLOAD_STALL_TIMEOUT, stream.next()) => next,
+                _ = cancelled(cancel_rx) => {
+                    tokio::io::AsyncWriteExt::flush(&mut file).await.ok();
+                    info!("Download cancelled at byte {}", downloaded);
+                    return Err(Error::DownloadCancelled);
+                }
+            };
+
+            let chunk = match next {
+                Ok(Some(chunk)) => chunk?,
+                Ok(None) => break,
+                Err(_) => {
+                    tokio::io::AsyncWriteExt::flush(&mut file).await.ok();
+                    return Err(Error::Io(std::io::Error::new(
+                        std::io::ErrorKind::TimedOut,
+                        "download stalled",
+                    )));
+                }
+            };
+
             tokio::io::AsyncWriteExt::write_all(&mut file, &chunk).await?;
             downloaded += chunk.len() as u64;
 
-            if total_size > 0 {
-                let progress = 20 + ((downloaded as f64 / total_size as f64) * 60.0) as u32;
-                self.emit_progress(app, "Downloading...", progress);
+            if last_emit.is_none_or(|t| t.elapsed() >= DOWNLOAD_PROGRESS_INTERVAL) {
+                last_emit = Some(std::time::Instant::now());
+                let elapsed = started_at.elapsed().as_secs_f64();
+                let bytes_per_second = if elapsed > 0.0 {
+                    ((downloaded - session_start) as f64 / elapsed) as u64
+                } else {
+                    0
+                };
+                self.emit_transfer_progress(app, downloaded, total_size, bytes_per_second);
+            }
+        }
+
+        tokio::io::AsyncWriteExt::flush(&mut file).await?;
+
+        if let Some(total) = total_size {
+            if downloaded < total {
+                return Err(Error::Io(std::io::Error::new(
+                    std::io::ErrorKind::UnexpectedEof,
+                    format!("connection closed after {} of {} bytes", downloaded, total),
+                )));
             }
         }
 
         Ok(())
     }
 
-    async fn extract_archive(&self, archive_path: &PathBuf) -> Result<(), Error> {
+    async fn extract_archive(&self, archive_path: &Path, dest: &Path) -> Result<(), Error> {
         let archive_name = archive_path.to_string_lossy().to_string();
-        let install_dir = self.install_dir.clone();
+        let dest_dir = dest.to_path_buf();
 
-        tokio::task::spawn_blocking(move || {
-            if archive_name.ends_with(".tar.gz") {
-                extract_tar_gz(&PathBuf::from(&archive_name), &install_dir)
+        let report = tokio::task::spawn_blocking(move || {
+            let limits = ExtractLimits::RELEASE;
+            let report = if archive_name.ends_with(".tar.gz") {
+                archive::extract_tar_gz(Path::new(&archive_name), &dest_dir, &limits)
             } else if archive_name.ends_with(".zip") {
-                extract_zip(&PathBuf::from(&archive_name), &install_dir)
+                std::fs::File::open(&archive_name)
+                    .map_err(ArchiveError::from)
+                    .and_then(|file| archive::extract_zip(file, &dest_dir, &limits))
             } else {
-                Err(Error::ExtractionFailed("Unknown archive format".into()))
-            }
+                return Err(Error::ExtractionFailed("Unknown archive format".into()));
+            };
+            report.map_err(|e| Error::ExtractionFailed(e.to_string()))
         })
         .await
         .map_err(|e| Error::ExtractionFailed(e.to_string()))??;
+        info!(
+            "Extracted {} files ({} bytes) from {}",
+            report.extracted,
+            report.total_bytes,
+            archive_path.display()
+        );
 
         #[cfg(unix)]
         {
             use std::os::unix::fs::PermissionsExt;
-            let binary = self.binary_path();
+            let binary = dest.join(installs::binary_name());
             let mut perms = std::fs::metadata(&binary)?.permissions();
             perms.set_mode(0o755);
             std::fs::set_permissions(&binary, perms)?;
@@ -368,133 +1158,335 @@ impl OpenCodeManager {
         Ok(())
     }
 
-    fn spawn_log_handler(&self, child: &mut Child) {
-        if let Some(stdout) = child.stdout.take() {
-            let app = self.app.clone();
-            tokio::spawn(async move {
-                let reader = BufReader::new(stdout);
-                let mut lines = reader.lines();
-                while let Ok(Some(line)) = lines.next_line().await {
-                    info!("[opencode stdout] {}", line);
-                    app.emit("opencode:log", &line).ok();
-                }
-            });
-        }
+    fn emit_progress(&self, app: &AppHandle, message: &str, percent: u32) {
+        app.emit(
+            "opencode:download-progress",
+            serde_json::json!({
+                "message": message,
+                "percent": percent
+            }),
+        )
+        .ok();
+    }
 
-        if let Some(stderr) = child.stderr.take() {
-            let app = self.app.clone();
-            tokio::spawn(async move {
-                let reader = BufReader::new(stderr);
-                let mut lines = reader.lines();
-                while let Ok(Some(line)) = lines.next_line().await {
-                    error!("[opencode stderr] {}", line);
-                    app.emit("opencode:error", &line).ok();
-                }
-            });
-        }
+    fn emit_transfer_progress(
+        &self,
+        app: &AppHandle,
+        downloaded: u64,
+        total: Option<u64>,
+        bytes_per_second: u64,
+    ) {
+        let percent = match total {
+            Some(total) if total > 0 => 20 + ((downloaded as f64 / total as f64) * 60.0) as u32,
+            _ => 20,
+        };
+        let eta_secs = match total {
+            Some(total) if bytes_per_second > 0 => {
+                Some(total.saturating_sub(downloaded) / bytes_per_second)
+            }
+            _ => None,
+        };
+
+        app.emit(
+            "opencode:download-progress",
+            serde_json::json!({
+                "message": "Downloading...",
+                "percent": percent,
+                "downloaded_bytes": downloaded,
+                "total_bytes": total,
+                "bytes_per_second": bytes_per_second,
+                "eta_secs": eta_secs
+            }),
+        )
+        .ok();
     }
 
-    fn spawn_idle_monitor(&mut self, port: u16) {
-        let (stop_tx, mut stop_rx) = watch::channel(false);
-        self.idle_monitor_stop = Some(stop_tx);
+    pub fn log_store(&self) -> Arc<LogStore> {
+        self.logs.store()
+    }
 
-        let app = self.app.clone();
-        let client = self.client.clone();
+    pub fn download_cancel_handle(&self) -> DownloadCancel {
+        self.download_cancel.clone()
+    }
 
-        tokio::spawn(async move {
-            info!("Idle monitor started for port {}", port);
-            
-            loop {
-                tokio::select! {
-                    _ = tokio::time::sleep(tokio::time::Duration::from_secs(IDLE_CHECK_INTERVAL_SECS)) => {
-                        if let Some(last_activity) = fetch_last_activity(&client, port).await {
-                            let now = std::time::SystemTime::now()
-                                .duration_since(std::time::UNIX_EPOCH)
-                                .map(|d| d.as_secs())
-                                .unwrap_or(0);
-                            
-                            let idle_secs = now.saturating_sub(last_activity);
-                            
-                            if idle_secs > IDLE_TIMEOUT_SECS {
-                                info!("OpenCode idle for {}s (threshold: {}s), requesting shutdown", 
-                                    idle_secs, IDLE_TIMEOUT_SECS);
-                                app.emit("opencode:idle-shutdown", ()).ok();
-                                break;
-                            }
-                        }
-                    }
-                    _ = stop_rx.changed() => {
-                        info!("Idle monitor stopped");
-                        break;
-                    }
-                }
-            }
-        });
+    fn global_config_path(&self) -> PathBuf {
+        self.install_dir.join("config").join("opencode.json")
+    }
+
+    pub async fn get_global_config(&self) -> Result<GlobalConfig, ConfigError> {
+        config::load(&self.global_config_path()).await
+    }
+
+    pub async fn set_global_config(&self, config: &GlobalConfig) -> Result<(), ConfigError> {
+        config::save(&self.global_config_path(), config).await
+    }
+
+    pub async fn patch_global_config(&self, patch: &Value) -> Result<GlobalConfig, ConfigError> {
+        config::patch(&self.global_config_path(), patch).await
+    }
+
+    pub async fn list_config_snapshots(&self) -> Result<Vec<ConfigSnapshot>, ConfigError> {
+        config::list_snapshots(&self.global_config_path()).await
     }
 
-    async fn wait_for_ready(&self, port: u16) -> Result<(), Error> {
-        let url = format!("http://localhost:{}/", port);
-        let max_attempts = 30;
+    pub async fn diff_config_snapshots(
+        &self,
+        from: &str,
+        to: Option<&str>,
+    ) -> Result<Vec<ConfigChange>, ConfigError> {
+        config::diff_snapshots(&self.global_config_path(), from, to).await
+    }
+
+    pub async fn restore_config_snapshot(&self, id: &str) -> Result<GlobalConfig, ConfigError> {
+        config::restore_snapshot(&self.global_config_path(), id).await
+    }
+
+    pub async fn get_config_history_settings(&self) -> HistorySettings {
+        config::load_history_settings(&self.global_config_path()).await
+    }
+
+    pub async fn set_config_history_settings(
+        &self,
+        settings: &HistorySettings,
+    ) -> Result<(), ConfigError> {
+        config::save_history_settings(&self.global_config_path(), settings).await
+    }
+}
+
+/// Reads `<hex>  <file>` lines (sha256sum format). A bare digest without a
+/// file name is only accepted from the archive's own `.sha256` sidecar; in
+/// a release-wide checksum file it could belong to any asset.
+fn parse_checksum_file(content: &str, archive_name: &str, sidecar: bool) -> Option<String> {
+    let is_digest = |s: &str| s.len() == 64 && s.chars().all(|c| c.is_ascii_hexdigit());
 
-        for _ in 0..max_attempts {
-            if self.client.get(&url).send().await.is_ok() {
-                info!("OpenCode is ready on port {}", port);
-                return Ok(());
+    for line in content.lines() {
+        let mut parts = line.split_whitespace();
+        let Some(digest) = parts.next().filter(|d| is_digest(d)) else {
+            continue;
+        };
+
+        match parts.next() {
+            Some(name) if name.trim_start_matches('*') == archive_name => {
+                return Some(digest.to_ascii_lowercase());
             }
-            tokio::time::sleep(tokio::time::Duration::from_millis(500)).await;
+            None if sidecar => return Some(digest.to_ascii_lowercase()),
+            None => {}
+            Some(_) => {}
         }
+    }
+
+    None
+}
+
+async fn verify_checksum(path: &Path, archive_name: &str, expected: &str) -> Result<(), Error> {
+    let path = path.to_path_buf();
+    let actual = tokio::task::spawn_blocking(move || -> Result<String, Error> {
+        let mut file = std::fs::File::open(&path)?;
+        let mut hasher = Sha256::new();
+        std::io::copy(&mut file, &mut hasher)?;
+        Ok(hex::encode(hasher.finalize()))
+    })
+    .await
+    .map_err(|e| Error::ExtractionFailed(e.to_string()))??;
+
+    if actual != expected {
+        return Err(Error::ChecksumMismatch {
+            archive: archive_name.to_string(),
+            expected: expected.to_string(),
+            actual,
+        });
+    }
+
+    info!("Verified SHA-256 of {}: {}", archive_name, actual);
+    Ok(())
+}
 
-        Err(Error::DownloadFailed(
-            "OpenCode failed to start within timeout".into(),
-        ))
+fn detect_binary_version(binary: &Path) -> Option<String> {
+    if !binary.exists() {
+        return None;
     }
 
-    fn emit_progress(&self, app: &AppHandle, message: &str, percent: u32) {
-        app.emit("opencode:download-progress", serde_json::json!({
-            "message": message,
-            "percent": percent
-        }))
+    let output = std::process::Command::new(binary)
+        .arg("version")
+        .output()
+        .ok()?;
+
+    if !output.status.success() {
+        return None;
+    }
+
+    parse_version_output(&String::from_utf8_lossy(&output.stdout))
+}
+
+/// The semver printed by `opencode version`, including any prerelease and
+/// build suffix.
+fn parse_version_output(output: &str) -> Option<String> {
+    let re = regex::Regex::new(r"(\d+\.\d+\.\d+(?:-[0-9A-Za-z.-]+)?(?:\+[0-9A-Za-z.-]+)?)").ok()?;
+    re.captures(output)
+        .and_then(|c| c.get(1))
+        .map(|m| m.as_str().to_string())
+}
+
+/// Whether a binary reporting `detected` belongs to the release `expected`.
+/// Build metadata is ignored, as semver does; a binary that prints only the
+/// numeric core is accepted for a prerelease of that core.
+fn same_version(detected: &str, expected: &str) -> bool {
+    let without_build = |v: &str| v.split('+').next().unwrap_or(v).to_string();
+    let (detected, expected) = (without_build(detected), without_build(expected));
+
+    if detected.contains('-') {
+        detected == expected
+    } else {
+        detected == expected.split('-').next().unwrap_or(&expected)
+    }
+}
+
+async fn shutdown_server(
+    app: &AppHandle,
+    site_id: &str,
+    server: ServerInstance,
+) -> Option<ShutdownOutcome> {
+    info!(
+        "Stopping OpenCode for site {} (port {})",
+        site_id, server.spec.port
+    );
+    server.idle_monitor_stop.send(true).ok();
+    server.supervisor_stop.send(true).ok();
+    if server.supervisor.is_finished() {
+        emit_status(app, site_id, &Status::Stopped);
+    }
+
+    let outcome = server.supervisor.await.ok().flatten();
+    PidFile::remove(&server.spec.run_dir, site_id);
+    match &outcome {
+        Some(o) if o.clean => info!(
+            "OpenCode for site {} shut down cleanly (code: {:?})",
+            site_id, o.exit_code
+        ),
+        Some(_) => tracing::warn!("OpenCode for site {} had to be killed", site_id),
+        None => {}
+    }
+    outcome
+}
+
+pub fn emit_status(app: &AppHandle, site_id: &str, status: &Status) {
+    app.emit("opencode:status", StatusPayload { site_id, status })
         .ok();
+}
+
+/// Spawns the server described by `spec` and waits until it answers HTTP
+/// requests. The child is killed if it never becomes ready.
+pub async fn spawn_server(
+    client: &Client,
+    spec: &ServerSpec,
+    stderr_tail: &StderrTail,
+    logs: &LogSink,
+) -> Result<Child, Error> {
+    // Checked up front so a spawn error can be blamed on the binary.
+    if !spec.project_dir.is_dir() {
+        return Err(Error::ProjectDirMissing(spec.project_dir.clone()));
+    }
+    let mut child = spec.command().spawn().map_err(Error::SpawnFailed)?;
+    if let Some(pid) = child.id() {
+        PidFile::write(spec, pid);
     }
 
-    fn global_config_path(&self) -> PathBuf {
-        self.install_dir
-            .join("config")
-            .join("opencode.json")
+    spawn_log_handler(&mut child, &spec.site_id, stderr_tail, logs);
+
+    if let Err(e) = wait_for_ready(client, &mut child, spec.port).await {
+        child.kill().await.ok();
+        return Err(e);
     }
 
-    pub async fn get_global_config(&self) -> GlobalConfig {
-        let config_path = self.global_config_path();
-        
-        let content = match tokio::fs::read_to_string(&config_path).await {
-            Ok(content) => content,
-            Err(_) => return serde_json::json!({}),
-        };
+    Ok(child)
+}
 
-        match serde_json::from_str(&content) {
-            Ok(json) => json,
-            Err(_) => serde_json::json!({}),
-        }
+fn spawn_log_handler(child: &mut Child, site_id: &str, stderr_tail: &StderrTail, logs: &LogSink) {
+    if let Some(stdout) = child.stdout.take() {
+        let logs = logs.clone();
+        let site_id = site_id.to_string();
+        tokio::spawn(async move {
+            let reader = BufReader::new(stdout);
+            let mut lines = reader.lines();
+            while let Ok(Some(line)) = lines.next_line().await {
+                debug!("[opencode stdout] {}", line);
+                logs.push(&site_id, &line, LogLevel::Info);
+            }
+        });
     }
 
-    pub async fn set_global_config(&self, config: Value) -> Result<(), Error> {
-        let config_path = self.global_config_path();
-        
-        if let Some(parent) = config_path.parent() {
-            tokio::fs::create_dir_all(parent).await?;
-        }
+    if let Some(stderr) = child.stderr.take() {
+        let stderr_tail = stderr_tail.clone();
+        let logs = logs.clone();
+        let site_id = site_id.to_string();
+        tokio::spawn(async move {
+            let reader = BufReader::new(stderr);
+            let mut lines = reader.lines();
+            while let Ok(Some(line)) = lines.next_line().await {
+                debug!("[opencode stderr] {}", line);
+                logs.push(&site_id, &line, LogLevel::Error);
+                if let Ok(mut tail) = stderr_tail.lock() {
+                    if tail.len() >= STDERR_TAIL_LINES {
+                        tail.pop_front();
+                    }
+                    tail.push_back(line);
+                }
+            }
+        });
+    }
+}
+
+async fn wait_for_ready(client: &Client, child: &mut Child, port: u16) -> Result<(), Error> {
+    let url = format!("http://localhost:{}/", port);
+    let max_attempts = 30;
 
-        let mut json = config;
-        
-        if !json.get("$schema").is_some() {
-            json["$schema"] = Value::String("https://opencode.ai/config.json".to_string());
+    for _ in 0..max_attempts {
+        if let Some(status) = child.try_wait()? {
+            return Err(Error::ExitedEarly(status.code()));
+        }
+        if client.get(&url).send().await.is_ok() {
+            info!("OpenCode is ready on port {}", port);
+            return Ok(());
         }
+        tokio::time::sleep(tokio::time::Duration::from_millis(500)).await;
+    }
 
-        let content = serde_json::to_string_pretty(&json)?;
-        tokio::fs::write(&config_path, content).await?;
+    Err(Error::StartTimeout)
+}
 
-        info!("Updated global OpenCode config at {:?}", config_path);
-        Ok(())
+async fn file_len(path: &Path) -> u64 {
+    tokio::fs::metadata(path)
+        .await
+        .map(|m| m.len())
+        .unwrap_or(0)
+}
+
+fn is_transient(error: &Error) -> bool {
+    match error {
+        Error::Http(e) => match e.status() {
+            Some(status) => {
+                status.is_server_error() || status == reqwest::StatusCode::TOO_MANY_REQUESTS
+            }
+            None => true,
+        },
+        Error::Io(e) => matches!(
+            e.kind(),
+            std::io::ErrorKind::TimedOut | std::io::ErrorKind::UnexpectedEof
+        ),
+        _ => false,
+    }
+}
+
+fn download_backoff(attempt: u32) -> std::time::Duration {
+    let delay = DOWNLOAD_INITIAL_BACKOFF_MS.saturating_mul(1 << attempt.saturating_sub(1).min(16));
+    std::time::Duration::from_millis(delay.min(DOWNLOAD_MAX_BACKOFF_MS))
+}
+
+async fn cancelled(rx: &mut watch::Receiver<bool>) {
+    while !*rx.borrow_and_update() {
+        if rx.changed().await.is_err() {
+            std::future::pending::<()>().await;
+        }
     }
 }
 
@@ -522,52 +1514,151 @@ fn get_archive_name() -> Result<String, Error> {
     Ok(format!("{}.{}", target, extension))
 }
 
-async fn fetch_last_activity(client: &Client, port: u16) -> Option<u64> {
-    let url = format!("http://localhost:{}/session", port);
-    
-    let response = client
-        .get(&url)
-        .timeout(std::time::Duration::from_secs(5))
-        .send()
-        .await
-        .ok()?;
-    
-    let sessions: Vec<SessionInfo> = response.json().await.ok()?;
-    
-    sessions.iter()
-        .filter_map(|s| s.time.updated)
-        .max()
-}
-
-fn extract_tar_gz(archive: &PathBuf, dest: &PathBuf) -> Result<(), Error> {
-    use flate2::read::GzDecoder;
-    use tar::Archive;
-
-    let file = std::fs::File::open(archive)?;
-    let decoder = GzDecoder::new(file);
-    let mut archive = Archive::new(decoder);
-    archive.unpack(dest)?;
-    Ok(())
-}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DIGEST_A: &str = "aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa";
+    const DIGEST_B: &str = "BBBBBBBBBBBBBBBBBBBBBBBBBBBBBBBBBBBBBBBBBBBBBBBBBBBBBBBBBBBBBBBB";
+
+    fn status_error(status: u16) -> Error {
+        let response = tauri::http::Response::builder()
+            .status(status)
+            .body("")
+            .unwrap();
+        Error::Http(
+            reqwest::Response::from(response)
+                .error_for_status()
+                .unwrap_err(),
+        )
+    }
 
-fn extract_zip(archive: &PathBuf, dest: &PathBuf) -> Result<(), Error> {
-    let file = std::fs::File::open(archive)?;
-    let mut archive = zip::ZipArchive::new(file).map_err(|e| Error::ExtractionFailed(e.to_string()))?;
+    #[test]
+    fn download_backoff_doubles_up_to_the_cap() {
+        let delays: Vec<u64> = (1..=7)
+            .map(|attempt| download_backoff(attempt).as_millis() as u64)
+            .collect();
+        assert_eq!(delays, [1_000, 2_000, 4_000, 8_000, 16_000, 30_000, 30_000]);
+        assert_eq!(
+            download_backoff(u32::MAX).as_millis() as u64,
+            DOWNLOAD_MAX_BACKOFF_MS
+        );
+    }
 
-    for i in 0..archive.len() {
-        let mut file = archive.by_index(i).map_err(|e| Error::ExtractionFailed(e.to_string()))?;
-        let outpath = dest.join(file.name());
+    #[test]
+    fn server_errors_and_rate_limits_are_transient() {
+        assert!(is_transient(&status_error(500)));
+        assert!(is_transient(&status_error(503)));
+        assert!(is_transient(&status_error(429)));
+        assert!(!is_transient(&status_error(404)));
+        assert!(!is_transient(&status_error(403)));
+    }
 
-        if file.name().ends_with('/') {
-            std::fs::create_dir_all(&outpath)?;
-        } else {
-            if let Some(parent) = outpath.parent() {
-                std::fs::create_dir_all(parent)?;
-            }
-            let mut outfile = std::fs::File::create(&outpath)?;
-            std::io::copy(&mut file, &mut outfile)?;
-        }
+    #[test]
+    fn interrupted_reads_are_transient() {
+        let io = |kind| Error::Io(std::io::Error::from(kind));
+        assert!(is_transient(&io(std::io::ErrorKind::TimedOut)));
+        assert!(is_transient(&io(std::io::ErrorKind::UnexpectedEof)));
+        assert!(!is_transient(&io(std::io::ErrorKind::PermissionDenied)));
+        assert!(!is_transient(&Error::DownloadCancelled));
+        assert!(!is_transient(&Error::ChecksumUnavailable("a.zip".into())));
     }
 
-    Ok(())
+    #[tokio::test]
+    async fn connection_failures_are_transient() {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        drop(listener);
+
+        let error = Client::new().get(&url).send().await.unwrap_err();
+        assert!(is_transient(&Error::Http(error)));
+    }
+
+    #[test]
+    fn version_output_keeps_prerelease_and_build() {
+        assert_eq!(parse_version_output("1.2.3\n").as_deref(), Some("1.2.3"));
+        assert_eq!(
+            parse_version_output("opencode v1.0.0-beta.1\n").as_deref(),
+            Some("1.0.0-beta.1")
+        );
+        assert_eq!(
+            parse_version_output("1.0.0-rc.2+build.5").as_deref(),
+            Some("1.0.0-rc.2+build.5")
+        );
+        assert_eq!(parse_version_output("unknown"), None);
+    }
+
+    #[test]
+    fn staged_version_matches_the_release_tag() {
+        let expected = |tag: &str| installs::normalize_version(tag);
+
+        assert!(same_version("1.2.3", &expected("v1.2.3")));
+        assert!(same_version("1.0.0-beta.1", &expected("v1.0.0-beta.1")));
+        assert!(same_version("1.0.0", &expected("v1.0.0-beta.1")));
+        assert!(same_version(
+            "1.0.0-beta.1",
+            &expected("v1.0.0-beta.1+build.7")
+        ));
+
+        assert!(!same_version("1.2.4", &expected("v1.2.3")));
+        assert!(!same_version("1.0.0-beta.2", &expected("v1.0.0-beta.1")));
+        assert!(!same_version("1.0.0-beta.1", &expected("v1.0.0")));
+    }
+
+    #[test]
+    fn checksum_file_matches_the_archive_by_name() {
+        let content = format!(
+            "{}  opencode-darwin-arm64.zip\n{}  opencode-linux-x64.tar.gz\n",
+            DIGEST_A, DIGEST_B
+        );
+        assert_eq!(
+            parse_checksum_file(&content, "opencode-linux-x64.tar.gz", false),
+            Some(DIGEST_B.to_ascii_lowercase())
+        );
+        assert_eq!(
+            parse_checksum_file(&content, "opencode-windows-x64.zip", false),
+            None
+        );
+    }
+
+    #[test]
+    fn checksum_file_accepts_the_binary_marker() {
+        let content = format!("{} *opencode-linux-x64.tar.gz\n", DIGEST_A);
+        assert_eq!(
+            parse_checksum_file(&content, "opencode-linux-x64.tar.gz", false),
+            Some(DIGEST_A.to_string())
+        );
+    }
+
+    #[test]
+    fn checksum_file_ignores_bare_digests() {
+        let content = format!("{}\n{}  opencode-linux-x64.tar.gz\n", DIGEST_A, DIGEST_B);
+        assert_eq!(
+            parse_checksum_file(&content, "opencode-linux-x64.tar.gz", false),
+            Some(DIGEST_B.to_ascii_lowercase())
+        );
+        assert_eq!(
+            parse_checksum_file(DIGEST_A, "opencode-linux-x64.tar.gz", false),
+            None
+        );
+    }
+
+    #[test]
+    fn sidecar_accepts_a_bare_digest() {
+        let content = format!("{}\n", DIGEST_A);
+        assert_eq!(
+            parse_checksum_file(&content, "opencode-linux-x64.tar.gz", true),
+            Some(DIGEST_A.to_string())
+        );
+
+        let named = format!("{}  opencode-linux-x64.tar.gz\n", DIGEST_B);
+        assert_eq!(
+            parse_checksum_file(&named, "opencode-linux-x64.tar.gz", true),
+            Some(DIGEST_B.to_ascii_lowercase())
+        );
+        assert_eq!(
+            parse_checksum_file("not a digest", "opencode-linux-x64.tar.gz", true),
+            None
+        );
+    }
 }
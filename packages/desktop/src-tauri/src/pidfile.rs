@@ -0,0 +1,264 @@
+use crate::opencode::ServerSpec;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use tracing::warn;
+
+/// How far the recorded `started_at` may be from the start time the OS
+/// reports. The pidfile is written right after spawning, the slack covers
+/// clock-tick rounding and the coarse `ps` output.
+const START_TIME_TOLERANCE_SECS: u64 = 10;
+
+/// Written for every server we spawn, so that a later launch can find
+/// servers that outlived a crashed or force-killed app.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PidFile {
+    pub pid: u32,
+    /// Seconds since the Unix epoch.
+    pub started_at: u64,
+    pub spec: ServerSpec,
+}
+
+impl PidFile {
+    fn path(run_dir: &Path, site_id: &str) -> PathBuf {
+        run_dir.join(format!("{}.pid", site_id))
+    }
+
+    pub fn write(spec: &ServerSpec, pid: u32) {
+        let pidfile = Self {
+            pid,
+            started_at: now_secs(),
+            spec: spec.clone(),
+        };
+
+        let written = std::fs::create_dir_all(&spec.run_dir)
+            .and_then(|_| Ok(serde_json::to_string_pretty(&pidfile)?))
+            .and_then(|content| std::fs::write(Self::path(&spec.run_dir, &spec.site_id), content));
+
+        if let Err(e) = written {
+            warn!("Failed to write pidfile for site {}: {}", spec.site_id, e);
+        }
+    }
+
+    pub fn remove(run_dir: &Path, site_id: &str) {
+        std::fs::remove_file(Self::path(run_dir, site_id)).ok();
+    }
+
+    pub fn load_all(run_dir: &Path) -> Vec<Self> {
+        let Ok(entries) = std::fs::read_dir(run_dir) else {
+            return Vec::new();
+        };
+
+        entries
+            .flatten()
+            .map(|entry| entry.path())
+            .filter(|path| path.extension().is_some_and(|ext| ext == "pid"))
+            .filter_map(|path| {
+                let parsed = std::fs::read_to_string(&path)
+                    .ok()
+                    .and_then(|content| serde_json::from_str::<Self>(&content).ok());
+                if parsed.is_none() {
+                    warn!("Removing unreadable pidfile {:?}", path);
+                    std::fs::remove_file(&path).ok();
+                }
+                parsed
+            })
+            .map(|mut pidfile| {
+                pidfile.spec.run_dir = run_dir.to_path_buf();
+                pidfile
+            })
+            .collect()
+    }
+
+    /// Whether the pid still runs the process we recorded, rather than an
+    /// unrelated process that was handed the same pid: the executable must
+    /// match and the process must have started when the pidfile was written.
+    pub fn is_ours(&self) -> bool {
+        let Some(exe) = executable_path(self.pid) else {
+            return false;
+        };
+        let same_binary = match (exe.canonicalize(), self.spec.binary.canonicalize()) {
+            (Ok(exe), Ok(binary)) => exe == binary,
+            _ => exe == self.spec.binary,
+        };
+        same_binary
+            && start_time(self.pid).is_some_and(|started| {
+                started.abs_diff(self.started_at) <= START_TIME_TOLERANCE_SECS
+            })
+    }
+}
+
+#[cfg(unix)]
+pub fn is_alive(pid: u32) -> bool {
+    // SAFETY: signal 0 only checks that the process exists.
+    let result = unsafe { libc::kill(pid as libc::pid_t, 0) };
+    result == 0 || std::io::Error::last_os_error().raw_os_error() == Some(libc::EPERM)
+}
+
+#[cfg(windows)]
+pub fn is_alive(pid: u32) -> bool {
+    hidden_command("tasklist")
+        .args(["/FI", &format!("PID eq {}", pid), "/NH", "/FO", "CSV"])
+        .output()
+        .map(|out| String::from_utf8_lossy(&out.stdout).contains(&format!("\"{}\"", pid)))
+        .unwrap_or(false)
+}
+
+/// Asks the process to exit on its own. Returns false where that is not
+/// possible, in which case the caller should kill it right away.
+#[cfg(unix)]
+pub fn request_exit(pid: u32) -> bool {
+    // SAFETY: kill(2) has no memory-safety requirements.
+    unsafe { libc::kill(pid as libc::pid_t, libc::SIGTERM) == 0 }
+}
+
+/// Windows has no SIGTERM equivalent for console processes.
+#[cfg(windows)]
+pub fn request_exit(_pid: u32) -> bool {
+    false
+}
+
+#[cfg(unix)]
+pub fn force_kill(pid: u32) {
+    // SAFETY: kill(2) has no memory-safety requirements.
+    unsafe { libc::kill(pid as libc::pid_t, libc::SIGKILL) };
+}
+
+#[cfg(windows)]
+pub fn force_kill(pid: u32) {
+    hidden_command("taskkill")
+        .args(["/F", "/PID", &pid.to_string()])
+        .output()
+        .ok();
+}
+
+#[cfg(target_os = "linux")]
+fn executable_path(pid: u32) -> Option<PathBuf> {
+    let exe = std::fs::read_link(format!("/proc/{}/exe", pid)).ok()?;
+    // A binary replaced by an update is reported with this suffix.
+    let exe = exe.to_string_lossy();
+    Some(PathBuf::from(exe.trim_end_matches(" (deleted)")))
+}
+
+#[cfg(all(unix, not(target_os = "linux")))]
+fn executable_path(pid: u32) -> Option<PathBuf> {
+    let out = std::process::Command::new("ps")
+        .args(["-o", "comm=", "-p", &pid.to_string()])
+        .output()
+        .ok()?;
+    let path = String::from_utf8_lossy(&out.stdout).trim().to_string();
+    (!path.is_empty()).then(|| PathBuf::from(path))
+}
+
+#[cfg(windows)]
+fn executable_path(pid: u32) -> Option<PathBuf> {
+    let out = hidden_command("powershell")
+        .args([
+            "-NoProfile",
+            "-Command",
+            &format!("(Get-Process -Id {}).Path", pid),
+        ])
+        .output()
+        .ok()?;
+    let path = String::from_utf8_lossy(&out.stdout).trim().to_string();
+    (!path.is_empty()).then(|| PathBuf::from(path))
+}
+
+fn now_secs() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+/// Start time of the process in seconds since the Unix epoch.
+#[cfg(target_os = "linux")]
+fn start_time(pid: u32) -> Option<u64> {
+    let stat = std::fs::read_to_string(format!("/proc/{}/stat", pid)).ok()?;
+    // The command name may contain spaces; fields resume after its ')'.
+    let fields: Vec<&str> = stat.rsplit_once(')')?.1.split_whitespace().collect();
+    // `starttime` is field 22 overall, the 20th after the name.
+    let ticks: u64 = fields.get(19)?.parse().ok()?;
+    let boot_time: u64 = std::fs::read_to_string("/proc/stat")
+        .ok()?
+        .lines()
+        .find_map(|line| line.strip_prefix("btime "))?
+        .trim()
+        .parse()
+        .ok()?;
+    // SAFETY: sysconf has no memory-safety requirements.
+    let ticks_per_sec = u64::try_from(unsafe { libc::sysconf(libc::_SC_CLK_TCK) })
+        .ok()
+        .filter(|t| *t > 0)?;
+    Some(boot_time + ticks / ticks_per_sec)
+}
+
+#[cfg(all(unix, not(target_os = "linux")))]
+fn start_time(pid: u32) -> Option<u64> {
+    let out = std::process::Command::new("ps")
+        .args(["-o", "etime=", "-p", &pid.to_string()])
+        .output()
+        .ok()?;
+    let elapsed = parse_elapsed(String::from_utf8_lossy(&out.stdout).trim())?;
+    now_secs().checked_sub(elapsed)
+}
+
+/// Parses the `[[dd-]hh:]mm:ss` elapsed time printed by `ps -o etime=`.
+#[cfg(all(unix, not(target_os = "linux")))]
+fn parse_elapsed(s: &str) -> Option<u64> {
+    let (days, clock) = match s.split_once('-') {
+        Some((days, clock)) => (days.parse::<u64>().ok()?, clock),
+        None => (0, s),
+    };
+    let mut secs = 0;
+    for part in clock.split(':') {
+        secs = secs * 60 + part.parse::<u64>().ok()?;
+    }
+    Some(days * 86_400 + secs)
+}
+
+#[cfg(windows)]
+fn start_time(pid: u32) -> Option<u64> {
+    let out = hidden_command("powershell")
+        .args([
+            "-NoProfile",
+            "-Command",
+            &format!(
+                "([DateTimeOffset](Get-Process -Id {}).StartTime).ToUnixTimeSeconds()",
+                pid
+            ),
+        ])
+        .output()
+        .ok()?;
+    String::from_utf8_lossy(&out.stdout).trim().parse().ok()
+}
+
+#[cfg(windows)]
+fn hidden_command(program: &str) -> std::process::Command {
+    use std::os::windows::process::CommandExt;
+    const CREATE_NO_WINDOW: u32 = 0x0800_0000;
+
+    let mut cmd = std::process::Command::new(program);
+    cmd.creation_flags(CREATE_NO_WINDOW);
+    cmd
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reports_own_start_time() {
+        let started = start_time(std::process::id()).expect("start time of the test process");
+        assert!(started <= now_secs());
+        assert!(now_secs() - started < 24 * 60 * 60);
+    }
+
+    #[cfg(all(unix, not(target_os = "linux")))]
+    #[test]
+    fn parses_ps_elapsed_time() {
+        assert_eq!(parse_elapsed("00:05"), Some(5));
+        assert_eq!(parse_elapsed("01:02:03"), Some(3_723));
+        assert_eq!(parse_elapsed("2-00:00:01"), Some(172_801));
+        assert_eq!(parse_elapsed("garbage"), None);
+    }
+}
@@ -0,0 +1,117 @@
+use crate::reauth;
+use crate::sites::{SiteClient, SiteError, SiteManager};
+use crate::state::AppState;
+use futures_util::future::join_all;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+use tauri::{AppHandle, Manager};
+use tokio::sync::Mutex;
+use tracing::debug;
+
+/// How often every site is told whether this device's server is up. The
+/// plugin counts a device as offline after missing a few of these.
+const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(5 * 60);
+/// Budget for telling sites about stopped servers while the app exits.
+pub const EXIT_REPORT_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// Tells each of `site_ids` that this device's server has stopped, so the
+/// WordPress admin stops connecting to its port.
+pub async fn report_stopped(app: &AppHandle, site_ids: &[String]) {
+    let (device_id, clients) = presence_clients(app, site_ids).await;
+
+    let device_id = &device_id;
+    let reports = clients.iter().map(|(site_id, client)| async move {
+        (site_id, client.report_presence(device_id, None).await)
+    });
+    for (site_id, result) in join_all(reports).await {
+        if let Err(e) = result {
+            tracing::warn!(
+                "Failed to tell site {} its OpenCode server stopped: {}",
+                site_id,
+                e
+            );
+            note_error(app, site_id, &e).await;
+        }
+    }
+}
+
+/// The device id and a client for each of `site_ids` that can be reported
+/// to. Sites waiting to be paired again or whose credentials are locked are
+/// left out. The requests themselves are made without holding the
+/// [`SiteManager`] lock.
+async fn presence_clients(
+    app: &AppHandle,
+    site_ids: &[String],
+) -> (String, Vec<(String, SiteClient)>) {
+    let site_manager = app.state::<Arc<Mutex<SiteManager>>>();
+    let mut manager = site_manager.lock().await;
+    let device_id = manager.get_device_id().await;
+
+    let clients = site_ids
+        .iter()
+        .filter(|site_id| {
+            manager
+                .get_site(site_id)
+                .is_some_and(|site| !site.needs_reauth)
+        })
+        .filter_map(|site_id| {
+            let client = manager.site_client(site_id).ok()?;
+            Some((site_id.clone(), client))
+        })
+        .collect();
+    (device_id, clients)
+}
+
+async fn note_error(app: &AppHandle, site_id: &str, error: &SiteError) {
+    let site_manager = app.state::<Arc<Mutex<SiteManager>>>();
+    let mut manager = site_manager.lock().await;
+    reauth::note_error(app, &mut manager, site_id, error).await;
+}
+
+/// Reports the actual state of every site's server on a fixed interval.
+/// This also catches servers that went away without passing through a stop
+/// command, such as one the supervisor gave up restarting.
+pub fn spawn_heartbeat(app: AppHandle) {
+    tauri::async_runtime::spawn(async move {
+        loop {
+            tokio::time::sleep(HEARTBEAT_INTERVAL).await;
+            send_heartbeats(&app).await;
+        }
+    });
+}
+
+async fn send_heartbeats(app: &AppHandle) {
+    let ports: HashMap<String, u16> = {
+        let state = app.state::<Arc<Mutex<AppState>>>();
+        let state = state.lock().await;
+        state
+            .list_servers()
+            .into_iter()
+            .filter_map(|server| {
+                state
+                    .get_port(&server.site_id)
+                    .map(|port| (server.site_id, port))
+            })
+            .collect()
+    };
+
+    let site_ids: Vec<String> = {
+        let site_manager = app.state::<Arc<Mutex<SiteManager>>>();
+        let manager = site_manager.lock().await;
+        manager.list_sites().iter().map(|s| s.id.clone()).collect()
+    };
+    let (device_id, clients) = presence_clients(app, &site_ids).await;
+
+    let (device_id, ports) = (&device_id, &ports);
+    let reports = clients.iter().map(|(site_id, client)| async move {
+        let port = ports.get(site_id).copied();
+        (site_id, client.report_presence(device_id, port).await)
+    });
+    for (site_id, result) in join_all(reports).await {
+        if let Err(e) = result {
+            debug!("Heartbeat to site {} failed: {}", site_id, e);
+            note_error(app, site_id, &e).await;
+        }
+    }
+}
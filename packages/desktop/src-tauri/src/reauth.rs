@@ -0,0 +1,63 @@
+use crate::sites::{SiteError, SiteManager, WordPressSite};
+use serde::Serialize;
+use tauri::{AppHandle, Emitter};
+
+#[derive(Debug, Clone, Serialize)]
+struct NeedsReauthPayload<'a> {
+    site_id: &'a str,
+    name: &'a str,
+    url: &'a str,
+}
+
+/// Flags the site for re-pairing when `error` means its credentials were
+/// rejected, emitting `site:needs-reauth` the first time.
+pub async fn note_error(
+    app: &AppHandle,
+    manager: &mut SiteManager,
+    site_id: &str,
+    error: &SiteError,
+) {
+    if !matches!(error, SiteError::Unauthorized(_)) {
+        return;
+    }
+    match manager.flag_needs_reauth(site_id).await {
+        Ok(Some(site)) => {
+            tracing::warn!(
+                "Site {} rejected its credentials; it needs to be paired again",
+                site_id
+            );
+            let payload = NeedsReauthPayload {
+                site_id,
+                name: &site.name,
+                url: &site.url,
+            };
+            app.emit("site:needs-reauth", &payload).ok();
+        }
+        Ok(None) => {}
+        Err(e) => tracing::warn!("Failed to flag site {} for re-pairing: {}", site_id, e),
+    }
+}
+
+/// Converts a command's result for the frontend, noting rejected
+/// credentials on the way.
+pub async fn check<T>(
+    app: &AppHandle,
+    manager: &mut SiteManager,
+    site_id: &str,
+    result: Result<T, SiteError>,
+) -> Result<T, String> {
+    if let Err(e) = &result {
+        note_error(app, manager, site_id, e).await;
+    }
+    result.map_err(|e| e.to_string())
+}
+
+/// The WordForge settings page, told to issue a fresh connection token and
+/// send it back through the usual `wordforge://connect` deep link. Pairing
+/// with it updates the existing site in place.
+pub fn pairing_url(site: &WordPressSite) -> String {
+    format!(
+        "{}/wp-admin/admin.php?page=wordforge&wordforge_reauth=1",
+        site.url.trim_end_matches('/')
+    )
+}
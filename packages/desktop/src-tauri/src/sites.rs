@@ -1,13 +1,18 @@
+use crate::archive::{self, ArchiveError, ExtractLimits, ExtractReport};
+use crate::drift::{ConfigDrift, DriftSettings, MIN_INTERVAL_MINUTES};
+use crate::idle::IdlePolicy;
+use crate::manifest::{self, Manifest, ManifestError, RefreshPlan, Resolution};
+use crate::persist;
+use crate::snapshot::{self, SiteSnapshot, SnapshotError};
+use crate::vault::{SiteCredentials, Vault, VaultError, VaultStatus};
 use deunicode::deunicode;
-use reqwest::Client;
+use reqwest::{Client, StatusCode};
 use serde::{Deserialize, Serialize};
-use sha2::{Sha256, Digest};
+use sha2::{Digest, Sha256};
 use std::collections::HashMap;
-use std::io::{Read, Write};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use thiserror::Error;
 use uuid::Uuid;
-use zip::ZipArchive;
 
 #[derive(Debug, Error)]
 pub enum SiteError {
@@ -29,6 +34,35 @@ pub enum SiteError {
     InvalidUrl(String),
     #[error("API error: {0}")]
     ApiError(String),
+    #[error(transparent)]
+    Vault(#[from] VaultError),
+    #[error("Config archive rejected: {0}")]
+    Archive(#[from] ArchiveError),
+    #[error(transparent)]
+    Manifest(#[from] ManifestError),
+    #[error(transparent)]
+    Snapshot(#[from] SnapshotError),
+    #[error("Not found on the site: {0}")]
+    RemoteNotFound(String),
+    #[error("{0} is already connected")]
+    AlreadyConnected(String),
+    #[error("The connection token for {host} claims to be for {claimed}; refusing to re-pair")]
+    SiteMismatch { host: String, claimed: String },
+    #[error("{0} is not waiting to be paired again")]
+    NotAwaitingReauth(String),
+    #[error("The site rejected the stored credentials (HTTP {0}); re-pair it to continue")]
+    Unauthorized(u16),
+}
+
+/// Turns an unsuccessful response into an error. Rejected credentials get
+/// their own variant so callers can ask for a re-pairing.
+async fn response_error(response: reqwest::Response, wrap: fn(String) -> SiteError) -> SiteError {
+    let status = response.status();
+    if matches!(status, StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN) {
+        return SiteError::Unauthorized(status.as_u16());
+    }
+    let body = response.text().await.unwrap_or_default();
+    wrap(format!("HTTP {}: {}", status, body))
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -40,7 +74,10 @@ pub struct WordPressSite {
     pub mcp_endpoint: String,
     pub abilities_url: String,
     pub username: String,
+    /// Held in the credential vault and filled in while it is unlocked.
+    #[serde(default)]
     pub app_password: String,
+    #[serde(default)]
     pub auth: String,
     pub project_dir: PathBuf,
     pub created_at: u64,
@@ -49,6 +86,16 @@ pub struct WordPressSite {
     pub config_hash: Option<String>,
     #[serde(default)]
     pub config_updated_at: Option<u64>,
+    #[serde(default)]
+    pub idle_policy: IdlePolicy,
+    /// Set by the background poller while the site's config differs from
+    /// the one last applied.
+    #[serde(default)]
+    pub config_drift: Option<ConfigDrift>,
+    /// Set once the site rejects the stored credentials; cleared by pairing
+    /// again.
+    #[serde(default)]
+    pub needs_reauth: bool,
 }
 
 #[derive(Debug, Deserialize)]
@@ -102,62 +149,627 @@ pub struct ConfigSyncStatus {
     pub last_checked: Option<u64>,
 }
 
+const STORE_SCHEMA_VERSION: u32 = 1;
+const STORE_BACKUPS: usize = 5;
+const REVOCATION_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(10);
+/// Bound on requests made through a [`SiteClient`], so a site that accepts
+/// the connection and never answers cannot stall the caller.
+const SITE_REQUEST_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(20);
+/// A queued revocation is dropped after this many failed attempts.
+const MAX_REVOCATION_ATTEMPTS: u32 = 50;
+pub const REVOCATION_RETRY_INTERVAL: std::time::Duration = std::time::Duration::from_secs(15 * 60);
+
+/// Credentials of a removed site that could not be revoked on the site yet.
+/// Like a site's, `auth` lives in the vault once there is one, under the
+/// removed site's id.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PendingRevocation {
+    pub site_id: String,
+    pub url: String,
+    pub device_id: Option<String>,
+    #[serde(default)]
+    pub auth: String,
+    pub queued_at: u64,
+    pub attempts: u32,
+    pub last_error: Option<String>,
+}
+
+enum RevokeOutcome {
+    Revoked,
+    /// The site no longer accepts the credential, so there is nothing left
+    /// to revoke.
+    AlreadyInvalid,
+    Retry(String),
+}
+
+/// A queued revocation, detached from the [`SiteManager`] so the request
+/// can be made without holding its lock.
+pub struct RevocationJob {
+    client: Client,
+    pending: PendingRevocation,
+}
+
+/// The result of a [`RevocationJob`], for `SiteManager::record_revocations`.
+pub struct RevocationAttempt {
+    pending: PendingRevocation,
+    outcome: RevokeOutcome,
+}
+
+impl RevocationJob {
+    /// Asks the site to revoke this app's application password and forget
+    /// the device, authenticating with the password being revoked.
+    pub async fn run(self) -> RevocationAttempt {
+        let pending = self.pending;
+        let disconnect_url = format!(
+            "{}/wp-json/wordforge/v1/desktop/disconnect",
+            pending.url.trim_end_matches('/')
+        );
+        let response = self
+            .client
+            .post(&disconnect_url)
+            .header("Authorization", format!("Basic {}", pending.auth))
+            .timeout(REVOCATION_TIMEOUT)
+            .json(&serde_json::json!({ "device_id": pending.device_id }))
+            .send()
+            .await;
+
+        let outcome = match response {
+            Ok(response) if response.status().is_success() => RevokeOutcome::Revoked,
+            Ok(response) if matches!(response.status().as_u16(), 401 | 403) => {
+                RevokeOutcome::AlreadyInvalid
+            }
+            Ok(response) => {
+                let status = response.status();
+                let body = response.text().await.unwrap_or_default();
+                RevokeOutcome::Retry(format!("HTTP {}: {}", status, body))
+            }
+            Err(e) => RevokeOutcome::Retry(e.to_string()),
+        };
+        RevocationAttempt { pending, outcome }
+    }
+}
+
+/// A site together with the manager's HTTP client, so background requests
+/// can be made without holding the [`SiteManager`] lock.
+pub struct SiteClient {
+    client: Client,
+    site: WordPressSite,
+    device_name: String,
+}
+
+impl SiteClient {
+    /// Posts this device's local settings: enabled on `port` while a server
+    /// runs, disabled with `None`. Each post also refreshes the device's
+    /// last-seen time on the site.
+    pub async fn report_presence(
+        &self,
+        device_id: &str,
+        port: Option<u16>,
+    ) -> Result<(), SiteError> {
+        let settings_url = format!(
+            "{}/wp-json/wordforge/v1/opencode/local-settings",
+            self.site.url.trim_end_matches('/')
+        );
+
+        let mut settings = serde_json::json!({
+            "device_id": device_id,
+            "name": self.device_name,
+            "platform": std::env::consts::OS,
+            "enabled": port.is_some(),
+            "project_id": SiteManager::generate_opencode_project_id(&self.site.project_dir),
+            "project_dir": self.site.project_dir.to_string_lossy(),
+        });
+        if let Some(port) = port {
+            settings["port"] = port.into();
+        }
+
+        let response = self
+            .client
+            .post(&settings_url)
+            .header("Authorization", format!("Basic {}", self.site.auth))
+            .header("Content-Type", "application/json")
+            .timeout(SITE_REQUEST_TIMEOUT)
+            .json(&settings)
+            .send()
+            .await?;
+
+        if !response.status().is_success() {
+            return Err(response_error(response, |e| {
+                SiteError::ApiError(format!("Failed to sync settings: {}", e))
+            })
+            .await);
+        }
+        Ok(())
+    }
+
+    pub async fn config_hash(&self) -> Result<ConfigHashResponse, SiteError> {
+        let hash_url = format!(
+            "{}/wp-json/wordforge/v1/desktop/config-hash",
+            self.site.url.trim_end_matches('/')
+        );
+
+        tracing::info!("Checking config hash from: {}", hash_url);
+
+        let response = self
+            .client
+            .get(&hash_url)
+            .header("Authorization", format!("Basic {}", self.site.auth))
+            .timeout(SITE_REQUEST_TIMEOUT)
+            .send()
+            .await?;
+
+        if !response.status().is_success() {
+            return Err(response_error(response, SiteError::ApiError).await);
+        }
+
+        let hash_response: ConfigHashResponse = response.json().await?;
+        tracing::info!("Remote config hash: {}", hash_response.hash);
+
+        Ok(hash_response)
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
 pub struct SitesStore {
+    /// Missing in stores written before versioning, which count as 0.
+    #[serde(default)]
+    pub schema_version: u32,
     pub sites: HashMap<String, WordPressSite>,
     pub active_site_id: Option<String>,
     pub device_id: Option<String>,
+    #[serde(default)]
+    pub drift: DriftSettings,
+    #[serde(default)]
+    pub pending_revocations: Vec<PendingRevocation>,
+    /// How this install is labelled in a site's device list.
+    #[serde(default)]
+    pub device_name: Option<String>,
+}
+
+/// An install registered with a site, as the site reports it.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SiteDevice {
+    pub device_id: String,
+    pub name: Option<String>,
+    pub platform: Option<String>,
+    pub port: Option<u16>,
+    pub enabled: bool,
+    pub online: bool,
+    pub last_seen: Option<u64>,
+    /// Whether this is the install making the request.
+    #[serde(default)]
+    pub current: bool,
+}
+
+#[derive(Debug, Deserialize)]
+struct DevicesResponse {
+    devices: Vec<SiteDevice>,
+}
+
+/// The machine's host name, falling back to a generic label.
+fn default_device_name() -> String {
+    host_name()
+        .map(|name| name.trim().to_string())
+        .filter(|name| !name.is_empty())
+        .unwrap_or_else(|| "WordForge Desktop".to_string())
+}
+
+#[cfg(unix)]
+fn host_name() -> Option<String> {
+    let mut buf = [0u8; 256];
+    // SAFETY: the buffer is valid for `buf.len()` bytes; the result is
+    // read only up to the first NUL.
+    if unsafe { libc::gethostname(buf.as_mut_ptr().cast(), buf.len()) } != 0 {
+        return None;
+    }
+    let len = buf.iter().position(|&b| b == 0).unwrap_or(buf.len());
+    Some(String::from_utf8_lossy(&buf[..len]).to_string())
+}
+
+#[cfg(windows)]
+fn host_name() -> Option<String> {
+    std::env::var("COMPUTERNAME").ok()
+}
+
+/// What happened when `.sites.json` could not be read at startup.
+#[derive(Debug, Clone, Serialize)]
+pub struct StoreRecovery {
+    pub error: String,
+    /// Where the unreadable file was moved.
+    pub quarantined: Option<PathBuf>,
+    /// The backup the sites were restored from, if any was valid.
+    pub restored_from: Option<PathBuf>,
 }
 
 pub struct SiteManager {
     client: Client,
     store: SitesStore,
+    /// The app's data folder, holding the store, site folders and the
+    /// OpenCode state.
+    data_dir: PathBuf,
     store_path: PathBuf,
+    vault: Vault,
+    recovery: Option<StoreRecovery>,
 }
 
 impl SiteManager {
-    pub fn new() -> Self {
-        let store_path = dirs::data_local_dir()
-            .unwrap_or_else(|| PathBuf::from("."))
-            .join("wordforge")
-            .join(".sites.json");
-
-        let store = Self::load_store(&store_path).unwrap_or_default();
+    pub fn new(data_dir: PathBuf) -> Self {
+        let store_path = data_dir.join(".sites.json");
+
+        let (store, mut recovery) = Self::load_store(&store_path);
+        let mut vault = Vault::open(&data_dir);
+        if let Some(quarantined) = vault.take_quarantined() {
+            recovery.get_or_insert(StoreRecovery {
+                error: "Credential vault is unreadable; affected sites need to be paired again"
+                    .to_string(),
+                quarantined: Some(quarantined),
+                restored_from: None,
+            });
+        }
 
-        Self {
+        let mut manager = Self {
             client: Client::new(),
             store,
+            data_dir,
             store_path,
+            vault,
+            recovery,
+        };
+        manager.migrate_credentials();
+        manager.hydrate_credentials();
+        manager
+    }
+
+    /// Moves credentials still stored in plaintext into the vault, creating
+    /// one keyed by the OS keychain if there is none yet.
+    fn migrate_credentials(&mut self) {
+        let has_plaintext = self
+            .store
+            .sites
+            .values()
+            .any(|s| !s.auth.is_empty() || !s.app_password.is_empty());
+        if !has_plaintext {
+            return;
+        }
+
+        if self.vault.status() == VaultStatus::Uninitialized {
+            if let Err(e) = self.vault.initialize_with_keychain() {
+                tracing::warn!(
+                    "Site credentials stay unencrypted until a vault passphrase is set: {}",
+                    e
+                );
+                return;
+            }
+        }
+        if self.vault.status() != VaultStatus::Unlocked {
+            return;
+        }
+
+        if let Err(e) = self.move_credentials_to_vault() {
+            tracing::warn!("Failed to migrate site credentials to the vault: {}", e);
+            return;
+        }
+        if let Err(e) = Self::write_store(&self.store_path, &self.persisted_store()) {
+            tracing::warn!("Failed to rewrite site store after migration: {}", e);
+            return;
         }
+        Self::scrub_plaintext_copies(&self.store_path);
+        tracing::info!("Migrated site credentials to the encrypted vault");
     }
 
-    fn load_store(path: &PathBuf) -> Option<SitesStore> {
-        let content = std::fs::read_to_string(path).ok()?;
-        serde_json::from_str(&content).ok()
+    /// Removes credentials from the backups and quarantined copies of the
+    /// store, which may predate the vault. Copies that cannot be parsed are
+    /// deleted, since they may still hold plaintext credentials.
+    fn scrub_plaintext_copies(store_path: &Path) {
+        let copies = persist::backups(store_path, STORE_BACKUPS)
+            .into_iter()
+            .chain(persist::quarantined(store_path));
+        for copy in copies {
+            let result = match Self::read_store(&copy) {
+                Ok(Some(mut store)) => {
+                    let has_plaintext = store
+                        .sites
+                        .values()
+                        .any(|s| !s.auth.is_empty() || !s.app_password.is_empty())
+                        || store.pending_revocations.iter().any(|p| !p.auth.is_empty());
+                    if !has_plaintext {
+                        continue;
+                    }
+                    for site in store.sites.values_mut() {
+                        site.app_password.clear();
+                        site.auth.clear();
+                    }
+                    for pending in &mut store.pending_revocations {
+                        pending.auth.clear();
+                    }
+                    serde_json::to_string_pretty(&store)
+                        .map_err(SiteError::from)
+                        .and_then(|content| Ok(persist::write_atomic(&copy, content.as_bytes())?))
+                }
+                Ok(None) => continue,
+                Err(_) => std::fs::remove_file(&copy).map_err(SiteError::from),
+            };
+            if let Err(e) = result {
+                tracing::warn!("Failed to scrub credentials from {:?}: {}", copy, e);
+            }
+        }
+    }
+
+    fn move_credentials_to_vault(&mut self) -> Result<(), SiteError> {
+        for site in self.store.sites.values() {
+            if site.auth.is_empty() && site.app_password.is_empty() {
+                continue;
+            }
+            self.vault.set(
+                &site.id,
+                SiteCredentials {
+                    app_password: site.app_password.clone(),
+                    auth: site.auth.clone(),
+                },
+            )?;
+        }
+        for pending in self
+            .store
+            .pending_revocations
+            .iter()
+            .filter(|p| !p.auth.is_empty())
+        {
+            self.vault.set(
+                &pending.site_id,
+                SiteCredentials {
+                    app_password: String::new(),
+                    auth: pending.auth.clone(),
+                },
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Fills in the credentials of every site from the unlocked vault.
+    fn hydrate_credentials(&mut self) {
+        for site in self.store.sites.values_mut() {
+            if let Some(credentials) = self.vault.get(&site.id) {
+                site.app_password = credentials.app_password.clone();
+                site.auth = credentials.auth.clone();
+            }
+        }
+        for pending in &mut self.store.pending_revocations {
+            if let Some(credentials) = self.vault.get(&pending.site_id) {
+                pending.auth = credentials.auth.clone();
+            }
+        }
+    }
+
+    /// The store as written to disk: credentials are left out only where the
+    /// unlocked vault holds the very same ones. Anything else, such as
+    /// plaintext credentials loaded while the vault is locked, is kept so it
+    /// cannot be lost.
+    fn persisted_store(&self) -> SitesStore {
+        let mut store = self.store.clone();
+        store.schema_version = STORE_SCHEMA_VERSION;
+        if self.vault.status() != VaultStatus::Unlocked {
+            return store;
+        }
+        for site in store.sites.values_mut() {
+            let in_vault = self
+                .vault
+                .get(&site.id)
+                .is_some_and(|c| c.auth == site.auth && c.app_password == site.app_password);
+            if in_vault {
+                site.app_password.clear();
+                site.auth.clear();
+            }
+        }
+        for pending in &mut store.pending_revocations {
+            if self
+                .vault
+                .get(&pending.site_id)
+                .is_some_and(|c| c.auth == pending.auth)
+            {
+                pending.auth.clear();
+            }
+        }
+        store
+    }
+
+    fn require_credentials(&self, site: &WordPressSite) -> Result<(), SiteError> {
+        if !site.auth.is_empty() {
+            return Ok(());
+        }
+        match self.vault.status() {
+            VaultStatus::Locked => Err(VaultError::Locked.into()),
+            _ => Err(SiteError::ApiError(format!(
+                "No credentials stored for site {}",
+                site.id
+            ))),
+        }
+    }
+
+    pub fn vault_status(&self) -> VaultStatus {
+        self.vault.status()
+    }
+
+    pub async fn unlock_vault(&mut self, passphrase: &str) -> Result<(), SiteError> {
+        self.vault.unlock(passphrase)?;
+        let site_ids: Vec<&str> = self
+            .store
+            .sites
+            .keys()
+            .chain(self.store.pending_revocations.iter().map(|p| &p.site_id))
+            .map(String::as_str)
+            .collect();
+        self.vault.retain(&site_ids)?;
+        self.move_credentials_to_vault()?;
+        self.hydrate_credentials();
+        self.save_store().await?;
+        Self::scrub_plaintext_copies(&self.store_path);
+        Ok(())
+    }
+
+    /// Forgets the decrypted credentials. Only a passphrase vault can be
+    /// locked; a keychain-held key is unlocked again on demand by the OS.
+    pub fn lock_vault(&mut self) {
+        self.vault.lock();
+        if self.vault.status() == VaultStatus::Locked {
+            for site in self.store.sites.values_mut() {
+                site.app_password.clear();
+                site.auth.clear();
+            }
+            for pending in &mut self.store.pending_revocations {
+                pending.auth.clear();
+            }
+        }
+    }
+
+    /// Protects the vault with `passphrase`, or with the OS keychain when
+    /// `None`. Plaintext credentials left in the store are moved in too.
+    pub async fn set_vault_passphrase(
+        &mut self,
+        passphrase: Option<&str>,
+    ) -> Result<(), SiteError> {
+        self.vault.set_passphrase(passphrase)?;
+        self.move_credentials_to_vault()?;
+        self.save_store().await?;
+        Self::scrub_plaintext_copies(&self.store_path);
+        Ok(())
+    }
+
+    /// Loads the store, recovering from the newest valid backup when the
+    /// file is unreadable instead of starting over with no sites.
+    fn load_store(path: &Path) -> (SitesStore, Option<StoreRecovery>) {
+        let error = match Self::read_store(path) {
+            Ok(Some(store)) => return (store, None),
+            Ok(None) if persist::backups(path, STORE_BACKUPS).is_empty() => {
+                return (SitesStore::default(), None)
+            }
+            Ok(None) => "Site store is missing".to_string(),
+            Err(e) => e.to_string(),
+        };
+
+        tracing::warn!("Failed to read site store {:?}: {}", path, error);
+        let quarantined = persist::quarantine(path);
+
+        for backup in persist::backups(path, STORE_BACKUPS) {
+            match Self::read_store(&backup) {
+                Ok(Some(store)) => {
+                    tracing::warn!(
+                        "Restored {} site(s) from backup {:?}",
+                        store.sites.len(),
+                        backup
+                    );
+                    if let Err(e) = Self::write_store(path, &store) {
+                        tracing::warn!("Failed to write restored site store: {}", e);
+                    }
+                    let recovery = StoreRecovery {
+                        error,
+                        quarantined,
+                        restored_from: Some(backup),
+                    };
+                    return (store, Some(recovery));
+                }
+                Ok(None) => {}
+                Err(e) => tracing::warn!("Backup {:?} is unreadable too: {}", backup, e),
+            }
+        }
+
+        let recovery = StoreRecovery {
+            error,
+            quarantined,
+            restored_from: None,
+        };
+        (SitesStore::default(), Some(recovery))
+    }
+
+    fn read_store(path: &Path) -> Result<Option<SitesStore>, SiteError> {
+        let content = match std::fs::read_to_string(path) {
+            Ok(content) => content,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
+        let value = Self::migrate_store(serde_json::from_str(&content)?);
+        Ok(Some(serde_json::from_value(value)?))
+    }
+
+    /// Brings an older store document up to `STORE_SCHEMA_VERSION`, one
+    /// version at a time.
+    fn migrate_store(mut value: serde_json::Value) -> serde_json::Value {
+        let mut version = value
+            .get("schema_version")
+            .and_then(|v| v.as_u64())
+            .unwrap_or(0) as u32;
+        if version > STORE_SCHEMA_VERSION {
+            tracing::warn!(
+                "Site store has schema version {}, newer than this app supports",
+                version
+            );
+            return value;
+        }
+
+        while version < STORE_SCHEMA_VERSION {
+            match version {
+                // Version 1 introduced `schema_version` itself.
+                0 => {}
+                _ => unreachable!("missing site store migration from version {}", version),
+            }
+            version += 1;
+        }
+
+        value["schema_version"] = serde_json::Value::from(version);
+        value
+    }
+
+    /// Rotates the backups and atomically replaces the store file.
+    fn write_store(path: &Path, store: &SitesStore) -> Result<(), SiteError> {
+        let content = serde_json::to_string_pretty(store)?;
+        persist::rotate_backups(path, STORE_BACKUPS)?;
+        persist::write_atomic(path, content.as_bytes())?;
+        Ok(())
     }
 
     async fn save_store(&self) -> Result<(), SiteError> {
         let path = self.store_path.clone();
-        let content = serde_json::to_string_pretty(&self.store)?;
-        
-        tokio::task::spawn_blocking(move || {
-            if let Some(parent) = path.parent() {
-                std::fs::create_dir_all(parent)?;
-            }
-            std::fs::write(&path, content)?;
-            Ok::<(), SiteError>(())
-        })
-        .await
-        .map_err(|e| SiteError::Io(std::io::Error::other(format!("Task join error: {e}"))))?
+        let store = self.persisted_store();
+
+        tokio::task::spawn_blocking(move || Self::write_store(&path, &store))
+            .await
+            .map_err(|e| SiteError::Io(std::io::Error::other(format!("Task join error: {e}"))))?
+    }
+
+    pub fn take_store_recovery(&mut self) -> Option<StoreRecovery> {
+        self.recovery.take()
     }
 
-    pub async fn exchange_token(&mut self, site_url: &str, token: &str) -> Result<WordPressSite, SiteError> {
+    /// Pairs with a site using a one-time connect token. A site that is
+    /// already connected is only re-authenticated when `reauthenticate` is
+    /// set; otherwise the token is left unused so the caller can ask first.
+    ///
+    /// Re-authenticating in place is only allowed when the token came from
+    /// the host the stored site lives on, whatever URL the exchange response
+    /// claims.
+    pub async fn exchange_token(
+        &mut self,
+        site_url: &str,
+        token: &str,
+        reauthenticate: bool,
+    ) -> Result<WordPressSite, SiteError> {
+        if self.vault.status() == VaultStatus::Locked {
+            return Err(VaultError::Locked.into());
+        }
+
+        let existing = self.find_site_by_url(site_url).cloned();
+        if let Some(existing) = &existing {
+            if !reauthenticate {
+                return Err(SiteError::AlreadyConnected(existing.name.clone()));
+            }
+        }
+
         let base_url = site_url.trim_end_matches('/');
         let exchange_url = format!("{}/wp-json/wordforge/v1/desktop/exchange", base_url);
-        
+
         tracing::info!("Exchanging token with: {}", exchange_url);
-        
-        let response = self.client
+
+        let response = self
+            .client
             .post(&exchange_url)
             .header("Content-Type", "application/json")
             .json(&serde_json::json!({ "token": token }))
@@ -166,105 +778,176 @@ impl SiteManager {
 
         let status = response.status();
         let body = response.text().await.unwrap_or_default();
-        
+
         tracing::info!("Exchange response status: {}", status);
 
         if !status.is_success() {
-            return Err(SiteError::TokenExchange(format!("HTTP {}: {}", status, body)));
+            return Err(SiteError::TokenExchange(format!(
+                "HTTP {}: {}",
+                status, body
+            )));
         }
 
-        let exchange_response: ExchangeResponse = serde_json::from_str(&body)
-            .map_err(|e| SiteError::TokenExchange(format!("Failed to parse response: {}. Body: {}", e, &body[..body.len().min(200)])))?;
+        let exchange_response: ExchangeResponse = serde_json::from_str(&body).map_err(|e| {
+            SiteError::TokenExchange(format!(
+                "Failed to parse response: {}. Body: {}",
+                e,
+                &body[..body.len().min(200)]
+            ))
+        })?;
 
         if !exchange_response.success {
             return Err(SiteError::TokenExchange("Exchange failed".into()));
         }
 
-        let site_id = Uuid::new_v4().to_string();
+        // The site may report a different canonical URL than the link had,
+        // but it must not claim to be another connected site.
+        let claimed = self.find_site_by_url(&exchange_response.site.url).cloned();
+        let existing = match (existing, claimed) {
+            (Some(existing), Some(claimed)) if claimed.id != existing.id => {
+                return Err(SiteError::SiteMismatch {
+                    host: base_url.to_string(),
+                    claimed: claimed.url,
+                });
+            }
+            (Some(existing), _) => Some(existing),
+            // Only known once the token is spent, so asking means pairing again.
+            (None, Some(claimed)) if !reauthenticate => {
+                return Err(SiteError::AlreadyConnected(claimed.name))
+            }
+            (None, claimed) => claimed,
+        };
+        if let Some(existing) = &existing {
+            if !Self::same_host(base_url, &existing.url) {
+                return Err(SiteError::SiteMismatch {
+                    host: base_url.to_string(),
+                    claimed: existing.url.clone(),
+                });
+            }
+        }
         let now = std::time::SystemTime::now()
             .duration_since(std::time::UNIX_EPOCH)
             .unwrap()
             .as_secs();
 
-        let project_dir = self.create_project_dir(&exchange_response.site.name)?;
-        Self::ensure_opencode_project(&project_dir)?;
-        
-        self.download_and_extract_config(
-            base_url,
-            &exchange_response.credentials.auth,
-            &project_dir,
-        ).await?;
-
-        let temp_site = WordPressSite {
-            id: site_id.clone(),
-            name: exchange_response.site.name.clone(),
-            url: exchange_response.site.url.clone(),
-            rest_url: exchange_response.site.rest_url.clone(),
-            mcp_endpoint: exchange_response.site.mcp_endpoint.clone(),
-            abilities_url: exchange_response.site.abilities_url.clone(),
-            username: exchange_response.credentials.username.clone(),
-            app_password: exchange_response.credentials.app_password.clone(),
-            auth: exchange_response.credentials.auth.clone(),
-            project_dir: project_dir.clone(),
-            created_at: now,
-            last_used_at: now,
-            config_hash: None,
-            config_updated_at: None,
+        // Reconnecting keeps the id, folder and settings so sessions and
+        // local config edits carry over; only credentials and URLs change.
+        let (mut site, on_conflict) = match existing {
+            Some(existing) => {
+                tracing::info!("Re-authenticating site {} in place", existing.id);
+                (existing, Resolution::KeepLocal)
+            }
+            None => {
+                let project_dir = self.create_project_dir(&exchange_response.site.name)?;
+                let site = WordPressSite {
+                    id: Uuid::new_v4().to_string(),
+                    name: String::new(),
+                    url: String::new(),
+                    rest_url: String::new(),
+                    mcp_endpoint: String::new(),
+                    abilities_url: String::new(),
+                    username: String::new(),
+                    app_password: String::new(),
+                    auth: String::new(),
+                    project_dir,
+                    created_at: now,
+                    last_used_at: now,
+                    config_hash: None,
+                    config_updated_at: None,
+                    idle_policy: IdlePolicy::default(),
+                    config_drift: None,
+                    needs_reauth: false,
+                };
+                (site, Resolution::UseRemote)
+            }
         };
 
-        let config_hash = self.check_config_hash(&temp_site).await.ok().map(|r| r.hash);
-
-        let site = WordPressSite {
-            id: site_id.clone(),
-            name: exchange_response.site.name,
-            url: exchange_response.site.url,
-            rest_url: exchange_response.site.rest_url,
-            mcp_endpoint: exchange_response.site.mcp_endpoint,
-            abilities_url: exchange_response.site.abilities_url,
-            username: exchange_response.credentials.username,
-            app_password: exchange_response.credentials.app_password,
-            auth: exchange_response.credentials.auth,
-            project_dir,
-            created_at: now,
-            last_used_at: now,
-            config_hash,
-            config_updated_at: Some(now),
-        };
+        site.name = exchange_response.site.name;
+        site.url = exchange_response.site.url;
+        site.rest_url = exchange_response.site.rest_url;
+        site.mcp_endpoint = exchange_response.site.mcp_endpoint;
+        site.abilities_url = exchange_response.site.abilities_url;
+        site.username = exchange_response.credentials.username;
+        site.app_password = exchange_response.credentials.app_password;
+        site.auth = exchange_response.credentials.auth;
+        site.last_used_at = now;
+        site.needs_reauth = false;
+
+        Self::ensure_opencode_project(&site.project_dir)?;
+
+        let (plan, remote) = self
+            .stage_config(base_url, &site.auth, &site.project_dir)
+            .await?;
+        let resolutions = plan
+            .conflicts
+            .iter()
+            .map(|c| (c.path.clone(), on_conflict))
+            .collect();
+        if !plan.is_empty() && on_conflict == Resolution::KeepLocal {
+            snapshot::create(&site.project_dir, site.config_hash.as_deref())?;
+        }
+        Self::apply_staged_config(&site.project_dir, &plan, &remote, &resolutions)?;
+
+        let hash_response = self.check_config_hash(&site).await.ok();
+        if let Some(response) = &hash_response {
+            snapshot::record_hash_response(&site.project_dir, response)?;
+        }
+        site.config_hash = hash_response.map(|r| r.hash);
+        site.config_updated_at = Some(now);
+        site.config_drift = None;
 
-        self.store.sites.insert(site_id.clone(), site.clone());
-        self.store.active_site_id = Some(site_id);
+        self.store_credentials(&site)?;
+        self.store.sites.insert(site.id.clone(), site.clone());
+        self.store.active_site_id = Some(site.id.clone());
         self.save_store().await?;
 
         Ok(site)
     }
 
-    pub async fn sync_port_to_wordpress(&self, site: &WordPressSite, port: u16, device_id: &str) -> Result<(), SiteError> {
-        let settings_url = format!("{}/wp-json/wordforge/v1/opencode/local-settings", site.url.trim_end_matches('/'));
-        let project_id = Self::generate_opencode_project_id(&site.project_dir);
-        let project_dir = site.project_dir.to_string_lossy().to_string();
-        
-        tracing::info!("Syncing port {} (device: {}, project: {}) to WordPress", port, device_id, project_id);
+    /// Re-pairs a site flagged by [`SiteManager::flag_needs_reauth`] with a
+    /// token from its `wordforge_reauth` pairing link. The link must point
+    /// at that very site; any other site is left alone.
+    pub async fn repair_site(
+        &mut self,
+        site_id: &str,
+        site_url: &str,
+        token: &str,
+    ) -> Result<WordPressSite, SiteError> {
+        let site = self
+            .store
+            .sites
+            .get(site_id)
+            .ok_or_else(|| SiteError::NotFound(site_id.to_string()))?;
+        if !site.needs_reauth {
+            return Err(SiteError::NotAwaitingReauth(site.name.clone()));
+        }
+        if self
+            .find_site_by_url(site_url)
+            .is_none_or(|linked| linked.id != site_id)
+        {
+            return Err(SiteError::SiteMismatch {
+                host: site_url.to_string(),
+                claimed: site.url.clone(),
+            });
+        }
 
-        let response = self.client
-            .post(&settings_url)
-            .header("Authorization", format!("Basic {}", site.auth))
-            .header("Content-Type", "application/json")
-            .json(&serde_json::json!({
-                "port": port,
-                "device_id": device_id,
-                "enabled": true,
-                "project_id": project_id,
-                "project_dir": project_dir
-            }))
-            .send()
-            .await?;
+        self.exchange_token(site_url, token, true).await
+    }
 
-        if !response.status().is_success() {
-            let body = response.text().await.unwrap_or_default();
-            return Err(SiteError::ApiError(format!("Failed to sync settings: {}", body)));
+    fn store_credentials(&mut self, site: &WordPressSite) -> Result<(), SiteError> {
+        if self.vault.status() == VaultStatus::Uninitialized {
+            if let Err(e) = self.vault.initialize_with_keychain() {
+                tracing::warn!("Storing credentials of site {} unencrypted: {}", site.id, e);
+                return Ok(());
+            }
         }
-
-        tracing::info!("Settings synced successfully");
+        self.vault.set(
+            &site.id,
+            SiteCredentials {
+                app_password: site.app_password.clone(),
+                auth: site.auth.clone(),
+            },
+        )?;
         Ok(())
     }
 
@@ -272,81 +955,152 @@ impl SiteManager {
         &self,
         base_url: &str,
         auth: &str,
-        project_dir: &PathBuf,
-    ) -> Result<(), SiteError> {
+        project_dir: &Path,
+    ) -> Result<ExtractReport, SiteError> {
         // Use runtime=bun to get the MCP server as plain JavaScript
         // OpenCode's bundled Bun runtime will execute it
         let config_url = format!(
             "{}/wp-json/wordforge/v1/opencode/local-config?runtime=bun",
             base_url
         );
-        
+
         tracing::info!("Downloading config from: {}", config_url);
 
-        let response = self.client
+        let response = self
+            .client
             .get(&config_url)
             .header("Authorization", format!("Basic {}", auth))
             .send()
             .await?;
 
-        let status = response.status();
-        if !status.is_success() {
-            let body = response.text().await.unwrap_or_default();
-            return Err(SiteError::ConfigDownload(format!("HTTP {}: {}", status, body)));
+        if !response.status().is_success() {
+            return Err(response_error(response, SiteError::ConfigDownload).await);
         }
 
         let bytes = response.bytes().await?;
         tracing::info!("Downloaded {} bytes", bytes.len());
 
         let cursor = std::io::Cursor::new(bytes.as_ref());
-        let mut archive = ZipArchive::new(cursor)?;
+        let report = archive::extract_zip(cursor, project_dir, &ExtractLimits::SITE_CONFIG)?;
 
-        for i in 0..archive.len() {
-            let mut file = archive.by_index(i)?;
-            let outpath = project_dir.join(file.name());
+        tracing::info!(
+            "Extracted {} config files to {:?} ({} rejected)",
+            report.extracted,
+            project_dir,
+            report.rejected.len()
+        );
 
-            if file.name().ends_with('/') {
-                std::fs::create_dir_all(&outpath)?;
-            } else {
-                if let Some(parent) = outpath.parent() {
-                    std::fs::create_dir_all(parent)?;
-                }
-                let mut outfile = std::fs::File::create(&outpath)?;
-                let mut contents = Vec::new();
-                file.read_to_end(&mut contents)?;
-                outfile.write_all(&contents)?;
-            }
+        Ok(report)
+    }
 
-            #[cfg(unix)]
-            {
-                use std::os::unix::fs::PermissionsExt;
-                if let Some(mode) = file.unix_mode() {
-                    std::fs::set_permissions(&outpath, std::fs::Permissions::from_mode(mode))?;
-                }
-            }
+    /// Downloads the site's config into the staging folder and works out
+    /// what applying it would change in `project_dir`.
+    async fn stage_config(
+        &self,
+        base_url: &str,
+        auth: &str,
+        project_dir: &Path,
+    ) -> Result<(RefreshPlan, Manifest), SiteError> {
+        let staging = manifest::staging_dir(project_dir);
+        if staging.exists() {
+            std::fs::remove_dir_all(&staging)?;
+        }
+        std::fs::create_dir_all(&staging)?;
+
+        if let Err(e) = self
+            .download_and_extract_config(base_url, auth, &staging)
+            .await
+        {
+            std::fs::remove_dir_all(&staging).ok();
+            return Err(e);
         }
+        Ok(manifest::plan(project_dir, &staging)?)
+    }
 
-        tracing::info!("Extracted config to: {:?}", project_dir);
-        
+    fn apply_staged_config(
+        project_dir: &Path,
+        plan: &RefreshPlan,
+        remote: &Manifest,
+        resolutions: &HashMap<String, Resolution>,
+    ) -> Result<(), SiteError> {
+        let staging = manifest::staging_dir(project_dir);
+        let result = manifest::apply(project_dir, &staging, plan, remote, resolutions);
+        std::fs::remove_dir_all(&staging).ok();
+        result?;
+
+        tracing::info!(
+            "Applied config to {:?}: {} added, {} updated, {} deleted, {} conflicts",
+            project_dir,
+            plan.added.len(),
+            plan.updated.len(),
+            plan.deleted.len(),
+            plan.conflicts.len()
+        );
         Ok(())
     }
 
+    /// Picks a folder no other site uses, adding `-2`, `-3`, ... to the
+    /// sanitized name when sites share a name.
     fn create_project_dir(&self, site_name: &str) -> Result<PathBuf, SiteError> {
         let sanitized = Self::sanitize_site_name(site_name);
 
-        let base_dir = dirs::data_local_dir()
-            .unwrap_or_else(|| PathBuf::from("."))
-            .join("wordforge")
-            .join("sites")
-            .join(&sanitized);
+        let sites_dir = self.data_dir.join("sites");
+
+        let mut candidate = sites_dir.join(&sanitized);
+        let mut n = 1;
+        while candidate.exists()
+            || self
+                .store
+                .sites
+                .values()
+                .any(|s| s.project_dir == candidate)
+        {
+            n += 1;
+            candidate = sites_dir.join(format!("{}-{}", sanitized, n));
+        }
+
+        std::fs::create_dir_all(&candidate)?;
+        Ok(candidate)
+    }
+
+    /// Identifies a site by host, port and path; scheme, case and a
+    /// trailing slash are ignored.
+    fn site_key(url: &str) -> String {
+        match url::Url::parse(url.trim()) {
+            Ok(parsed) => format!(
+                "{}{}{}",
+                parsed.host_str().unwrap_or_default(),
+                parsed.port().map(|p| format!(":{}", p)).unwrap_or_default(),
+                parsed.path().trim_end_matches('/')
+            ),
+            Err(_) => url.trim().trim_end_matches('/').to_lowercase(),
+        }
+    }
 
-        std::fs::create_dir_all(&base_dir)?;
-        Ok(base_dir)
+    /// Whether both URLs point at the same host and explicit port.
+    fn same_host(a: &str, b: &str) -> bool {
+        match (url::Url::parse(a.trim()), url::Url::parse(b.trim())) {
+            (Ok(a), Ok(b)) => {
+                a.host_str().is_some_and(|host| {
+                    b.host_str()
+                        .is_some_and(|other| host.eq_ignore_ascii_case(other))
+                }) && a.port() == b.port()
+            }
+            _ => false,
+        }
+    }
+
+    pub fn find_site_by_url(&self, url: &str) -> Option<&WordPressSite> {
+        let key = Self::site_key(url);
+        self.store
+            .sites
+            .values()
+            .find(|site| Self::site_key(&site.url) == key)
     }
 
     fn sanitize_site_name(site_name: &str) -> String {
         let ascii = deunicode(site_name);
-        
+
         let sanitized: String = ascii
             .chars()
             .map(|c| {
@@ -384,14 +1138,17 @@ impl SiteManager {
     }
 
     pub fn get_site_folder(&self, id: &str) -> Option<PathBuf> {
-        self.store.sites.get(id).map(|site| site.project_dir.clone())
+        self.store
+            .sites
+            .get(id)
+            .map(|site| site.project_dir.clone())
     }
 
     pub async fn get_device_id(&mut self) -> String {
         if let Some(id) = &self.store.device_id {
             return id.clone();
         }
-        
+
         let device_id = Uuid::new_v4().to_string();
         self.store.device_id = Some(device_id.clone());
         if let Err(e) = self.save_store().await {
@@ -400,6 +1157,114 @@ impl SiteManager {
         device_id
     }
 
+    /// The name set by the user, or the machine's host name.
+    pub fn device_name(&self) -> String {
+        self.store
+            .device_name
+            .clone()
+            .unwrap_or_else(default_device_name)
+    }
+
+    async fn devices_request(
+        &mut self,
+        site_id: &str,
+        method: reqwest::Method,
+        device_id: Option<&str>,
+        body: Option<serde_json::Value>,
+    ) -> Result<Vec<SiteDevice>, SiteError> {
+        let site = self
+            .store
+            .sites
+            .get(site_id)
+            .ok_or_else(|| SiteError::NotFound(site_id.to_string()))?
+            .clone();
+        self.require_credentials(&site)?;
+
+        let mut url = format!(
+            "{}/wp-json/wordforge/v1/desktop/devices",
+            site.url.trim_end_matches('/')
+        );
+        if let Some(device_id) = device_id {
+            url = format!("{}/{}", url, urlencoding::encode(device_id));
+        }
+
+        let mut request = self
+            .client
+            .request(method, &url)
+            .header("Authorization", format!("Basic {}", site.auth));
+        if let Some(body) = body {
+            request = request.json(&body);
+        }
+        let response = request.send().await?;
+
+        if response.status() == StatusCode::NOT_FOUND {
+            return Err(SiteError::RemoteNotFound(url));
+        }
+        if !response.status().is_success() {
+            return Err(response_error(response, SiteError::ApiError).await);
+        }
+
+        let mut devices = response.json::<DevicesResponse>().await?.devices;
+        let own_id = self.get_device_id().await;
+        for device in &mut devices {
+            device.current = device.device_id == own_id;
+        }
+        devices.sort_by_key(|d| std::cmp::Reverse(d.last_seen));
+        Ok(devices)
+    }
+
+    pub async fn list_devices(&mut self, site_id: &str) -> Result<Vec<SiteDevice>, SiteError> {
+        self.devices_request(site_id, reqwest::Method::GET, None, None)
+            .await
+    }
+
+    /// Renames this install. The site is updated right away; other sites pick
+    /// the name up with the next presence report.
+    pub async fn rename_device(
+        &mut self,
+        site_id: &str,
+        name: &str,
+    ) -> Result<Vec<SiteDevice>, SiteError> {
+        let name = name.trim();
+        if name.is_empty() {
+            return Err(SiteError::ApiError("Device name cannot be empty".into()));
+        }
+        self.store.device_name = Some(name.to_string());
+        self.save_store().await?;
+
+        let own_id = self.get_device_id().await;
+        let body = serde_json::json!({ "name": name });
+        match self
+            .devices_request(site_id, reqwest::Method::POST, Some(&own_id), Some(body))
+            .await
+        {
+            // Not registered with this site until its server first starts.
+            Err(SiteError::RemoteNotFound(_)) => self.list_devices(site_id).await,
+            result => result,
+        }
+    }
+
+    /// Unregisters another install from the site and revokes its
+    /// application password. This install leaves a site by removing it.
+    pub async fn revoke_device(
+        &mut self,
+        site_id: &str,
+        device_id: &str,
+    ) -> Result<Vec<SiteDevice>, SiteError> {
+        if self.store.device_id.as_deref() == Some(device_id) {
+            return Err(SiteError::ApiError(
+                "This device cannot revoke itself; remove the site instead".into(),
+            ));
+        }
+        self.devices_request(site_id, reqwest::Method::DELETE, Some(device_id), None)
+            .await
+    }
+
+    /// For long-running requests made without holding the manager's lock.
+    pub fn http_client(&self) -> Client {
+        self.client.clone()
+    }
+
     pub fn list_sites(&self) -> Vec<&WordPressSite> {
         self.store.sites.values().collect()
     }
@@ -408,8 +1273,27 @@ impl SiteManager {
         self.store.sites.get(id)
     }
 
+    /// Marks the site as needing to be paired again. Returns the site only
+    /// when it was not already marked, so callers can announce it once.
+    pub async fn flag_needs_reauth(
+        &mut self,
+        id: &str,
+    ) -> Result<Option<WordPressSite>, SiteError> {
+        let Some(site) = self.store.sites.get_mut(id) else {
+            return Ok(None);
+        };
+        if site.needs_reauth {
+            return Ok(None);
+        }
+        site.needs_reauth = true;
+        let site = site.clone();
+        self.save_store().await?;
+        Ok(Some(site))
+    }
+
     pub fn get_active_site(&self) -> Option<&WordPressSite> {
-        self.store.active_site_id
+        self.store
+            .active_site_id
             .as_ref()
             .and_then(|id| self.store.sites.get(id))
     }
@@ -419,38 +1303,155 @@ impl SiteManager {
             return Err(SiteError::NotFound(id.to_string()));
         }
         self.store.active_site_id = Some(id.to_string());
-        
+
         if let Some(site) = self.store.sites.get_mut(id) {
             site.last_used_at = std::time::SystemTime::now()
                 .duration_since(std::time::UNIX_EPOCH)
                 .unwrap()
                 .as_secs();
         }
-        
+
+        self.save_store().await?;
+        Ok(())
+    }
+
+    pub async fn set_idle_policy(&mut self, id: &str, policy: IdlePolicy) -> Result<(), SiteError> {
+        let site = self
+            .store
+            .sites
+            .get_mut(id)
+            .ok_or_else(|| SiteError::NotFound(id.to_string()))?;
+        site.idle_policy = policy;
         self.save_store().await?;
         Ok(())
     }
 
-    pub async fn remove_site(&mut self, id: &str) -> Result<(), SiteError> {
-        if let Some(site) = self.store.sites.get(id) {
+    fn forget_credentials(&mut self, id: &str) {
+        if self.vault.status() == VaultStatus::Unlocked {
+            if let Err(e) = self.vault.remove(id) {
+                tracing::warn!(
+                    "Failed to remove credentials of site {} from the vault: {}",
+                    id,
+                    e
+                );
+            }
+        }
+    }
+
+    /// Removes the site locally and queues the revocation of its
+    /// credentials. The returned job makes the network call; run it without
+    /// holding the manager's lock and hand the result to
+    /// `record_revocations`. Until then the revocation stays queued and is
+    /// retried later, even if the app exits in between.
+    pub async fn remove_site(&mut self, id: &str) -> Result<Option<RevocationJob>, SiteError> {
+        let mut job = None;
+        if let Some(site) = self.store.sites.get(id).cloned() {
             if let Err(e) = self.cleanup_opencode_project(&site.project_dir) {
                 tracing::warn!("Failed to cleanup OpenCode project: {}", e);
             }
+
+            let pending = PendingRevocation {
+                site_id: site.id.clone(),
+                url: site.url.clone(),
+                device_id: self.store.device_id.clone(),
+                auth: site.auth.clone(),
+                queued_at: std::time::SystemTime::now()
+                    .duration_since(std::time::UNIX_EPOCH)
+                    .unwrap()
+                    .as_secs(),
+                attempts: 0,
+                last_error: None,
+            };
+            job = self.revocation_job(&pending);
+            self.store.pending_revocations.push(pending);
         }
-        
+
         self.store.sites.remove(id);
-        
+
         if self.store.active_site_id.as_deref() == Some(id) {
             self.store.active_site_id = self.store.sites.keys().next().cloned();
         }
-        
+
         self.save_store().await?;
-        Ok(())
+        Ok(job)
+    }
+
+    fn revocation_job(&self, pending: &PendingRevocation) -> Option<RevocationJob> {
+        // Waiting for the vault to be unlocked is not a failed attempt.
+        (!pending.auth.is_empty()).then(|| RevocationJob {
+            client: self.client.clone(),
+            pending: pending.clone(),
+        })
+    }
+
+    /// Jobs for every queued revocation whose credentials are available.
+    pub fn revocation_jobs(&self) -> Vec<RevocationJob> {
+        self.store
+            .pending_revocations
+            .iter()
+            .filter_map(|pending| self.revocation_job(pending))
+            .collect()
+    }
+
+    /// Applies the results of revocation jobs, dropping revocations that
+    /// succeeded, found the credential already invalid, or have failed too
+    /// often.
+    pub async fn record_revocations(
+        &mut self,
+        attempts: Vec<RevocationAttempt>,
+    ) -> Result<(), SiteError> {
+        if attempts.is_empty() {
+            return Ok(());
+        }
+
+        for attempt in attempts {
+            let job = &attempt.pending;
+            let Some(index) = self
+                .store
+                .pending_revocations
+                .iter()
+                .position(|p| p.site_id == job.site_id && p.queued_at == job.queued_at)
+            else {
+                continue;
+            };
+
+            let done = match attempt.outcome {
+                RevokeOutcome::Revoked | RevokeOutcome::AlreadyInvalid => {
+                    tracing::info!("Revoked credentials of removed site {}", job.site_id);
+                    true
+                }
+                RevokeOutcome::Retry(error) => {
+                    let pending = &mut self.store.pending_revocations[index];
+                    pending.attempts += 1;
+                    pending.last_error = Some(error);
+                    if pending.attempts >= MAX_REVOCATION_ATTEMPTS {
+                        tracing::warn!(
+                            "Giving up revoking credentials of removed site {} after {} attempts: {}",
+                            pending.site_id,
+                            pending.attempts,
+                            pending.last_error.as_deref().unwrap_or_default()
+                        );
+                        true
+                    } else {
+                        tracing::warn!(
+                            "Could not revoke credentials of site {}, will retry: {}",
+                            pending.site_id,
+                            pending.last_error.as_deref().unwrap_or_default()
+                        );
+                        false
+                    }
+                }
+            };
+            if done {
+                let pending = self.store.pending_revocations.remove(index);
+                self.forget_credentials(&pending.site_id);
+            }
+        }
+        self.save_store().await
     }
 
     pub fn parse_connect_url(url: &str) -> Result<(String, String, String), SiteError> {
-        let parsed = url::Url::parse(url)
-            .map_err(|e| SiteError::InvalidUrl(e.to_string()))?;
+        let parsed = url::Url::parse(url).map_err(|e| SiteError::InvalidUrl(e.to_string()))?;
 
         if parsed.scheme() != "wordforge" {
             return Err(SiteError::InvalidUrl("Invalid scheme".into()));
@@ -461,45 +1462,57 @@ impl SiteManager {
             .map(|(k, v)| (k.to_string(), v.to_string()))
             .collect();
 
-        let token = params.get("token")
+        let token = params
+            .get("token")
             .ok_or_else(|| SiteError::InvalidUrl("Missing token".into()))?
             .clone();
 
-        let site = params.get("site")
+        let site = params
+            .get("site")
             .ok_or_else(|| SiteError::InvalidUrl("Missing site".into()))?
             .clone();
 
-        let name = params.get("name")
+        let name = params
+            .get("name")
             .map(|n| urlencoding::decode(n).unwrap_or_default().to_string())
             .unwrap_or_else(|| "WordPress Site".to_string());
 
         Ok((site, token, name))
     }
 
-    pub async fn check_config_hash(&self, site: &WordPressSite) -> Result<ConfigHashResponse, SiteError> {
-        let hash_url = format!("{}/wp-json/wordforge/v1/desktop/config-hash", site.url.trim_end_matches('/'));
-        
-        tracing::info!("Checking config hash from: {}", hash_url);
+    pub async fn check_config_hash(
+        &self,
+        site: &WordPressSite,
+    ) -> Result<ConfigHashResponse, SiteError> {
+        self.require_credentials(site)?;
+        self.client_for(site.clone()).config_hash().await
+    }
 
-        let response = self.client
-            .get(&hash_url)
-            .header("Authorization", format!("Basic {}", site.auth))
-            .send()
-            .await?;
+    /// A [`SiteClient`] for `site_id`, for requests made after the lock on
+    /// this manager is released.
+    pub fn site_client(&self, site_id: &str) -> Result<SiteClient, SiteError> {
+        let site = self
+            .store
+            .sites
+            .get(site_id)
+            .ok_or_else(|| SiteError::NotFound(site_id.to_string()))?;
+        self.require_credentials(site)?;
+        Ok(self.client_for(site.clone()))
+    }
 
-        let status = response.status();
-        if !status.is_success() {
-            let body = response.text().await.unwrap_or_default();
-            return Err(SiteError::ApiError(format!("HTTP {}: {}", status, body)));
+    fn client_for(&self, site: WordPressSite) -> SiteClient {
+        SiteClient {
+            client: self.client.clone(),
+            site,
+            device_name: self.device_name(),
         }
-
-        let hash_response: ConfigHashResponse = response.json().await?;
-        tracing::info!("Remote config hash: {}", hash_response.hash);
-        
-        Ok(hash_response)
     }
 
-    pub fn get_config_sync_status(&self, site: &WordPressSite, remote_hash: Option<&str>) -> ConfigSyncStatus {
+    pub fn get_config_sync_status(
+        &self,
+        site: &WordPressSite,
+        remote_hash: Option<&str>,
+    ) -> ConfigSyncStatus {
         let current_hash = site.config_hash.clone();
         let update_available = match (&current_hash, remote_hash) {
             (Some(current), Some(remote)) => current != remote,
@@ -511,25 +1524,68 @@ impl SiteManager {
             update_available,
             current_hash,
             remote_hash: remote_hash.map(String::from),
-            last_checked: Some(std::time::SystemTime::now()
-                .duration_since(std::time::UNIX_EPOCH)
-                .unwrap()
-                .as_secs()),
+            last_checked: Some(
+                std::time::SystemTime::now()
+                    .duration_since(std::time::UNIX_EPOCH)
+                    .unwrap()
+                    .as_secs(),
+            ),
         }
     }
 
-    pub async fn refresh_site_config(&mut self, site_id: &str) -> Result<String, SiteError> {
-        let site = self.store.sites.get(site_id)
+    /// What `refresh_site_config` would change, including a diff for every
+    /// file that was edited locally and conflicts with the site's copy.
+    pub async fn preview_config_refresh(&self, site_id: &str) -> Result<RefreshPlan, SiteError> {
+        let site = self
+            .store
+            .sites
+            .get(site_id)
+            .ok_or_else(|| SiteError::NotFound(site_id.to_string()))?;
+        self.require_credentials(site)?;
+
+        let staged = self
+            .stage_config(
+                site.url.trim_end_matches('/'),
+                &site.auth,
+                &site.project_dir,
+            )
+            .await;
+        std::fs::remove_dir_all(manifest::staging_dir(&site.project_dir)).ok();
+        Ok(staged?.0)
+    }
+
+    /// Applies the site's current config. Fails without writing anything if
+    /// a conflicting file has no entry in `resolutions`.
+    pub async fn refresh_site_config(
+        &mut self,
+        site_id: &str,
+        resolutions: &HashMap<String, Resolution>,
+    ) -> Result<String, SiteError> {
+        let site = self
+            .store
+            .sites
+            .get(site_id)
             .ok_or_else(|| SiteError::NotFound(site_id.to_string()))?
             .clone();
 
         let hash_response = self.check_config_hash(&site).await?;
-        
-        self.download_and_extract_config(
-            site.url.trim_end_matches('/'),
-            &site.auth,
-            &site.project_dir,
-        ).await?;
+
+        let (plan, remote) = self
+            .stage_config(
+                site.url.trim_end_matches('/'),
+                &site.auth,
+                &site.project_dir,
+            )
+            .await?;
+        if let Err(e) = plan.check_resolved(resolutions) {
+            std::fs::remove_dir_all(manifest::staging_dir(&site.project_dir)).ok();
+            return Err(e.into());
+        }
+        if !plan.is_empty() {
+            snapshot::create(&site.project_dir, site.config_hash.as_deref())?;
+        }
+        Self::apply_staged_config(&site.project_dir, &plan, &remote, resolutions)?;
+        snapshot::record_hash_response(&site.project_dir, &hash_response)?;
 
         let now = std::time::SystemTime::now()
             .duration_since(std::time::UNIX_EPOCH)
@@ -539,15 +1595,120 @@ impl SiteManager {
         if let Some(stored_site) = self.store.sites.get_mut(site_id) {
             stored_site.config_hash = Some(hash_response.hash.clone());
             stored_site.config_updated_at = Some(now);
+            stored_site.config_drift = None;
         }
-        
+
         self.save_store().await?;
-        
-        tracing::info!("Refreshed config for site {}, new hash: {}", site_id, hash_response.hash);
+
+        tracing::info!(
+            "Refreshed config for site {}, new hash: {}",
+            site_id,
+            hash_response.hash
+        );
         Ok(hash_response.hash)
     }
 
-    pub fn generate_opencode_project_id(project_dir: &PathBuf) -> String {
+    /// Compares a config hash fetched from the site with the one last
+    /// applied and records the result. Returns the drift only when it is new
+    /// since the previous check, so callers can announce it once.
+    pub async fn record_config_hash(
+        &mut self,
+        site_id: &str,
+        response: &ConfigHashResponse,
+    ) -> Result<Option<ConfigDrift>, SiteError> {
+        let site = self
+            .store
+            .sites
+            .get(site_id)
+            .ok_or_else(|| SiteError::NotFound(site_id.to_string()))?
+            .clone();
+
+        let drift = (site.config_hash.as_deref() != Some(response.hash.as_str())).then(|| {
+            let baseline = snapshot::current_hash_response(&site.project_dir);
+            ConfigDrift::between(baseline.as_ref().map(|r| &r.components), response)
+        });
+        let is_new = match (&site.config_drift, &drift) {
+            (Some(previous), Some(current)) => previous.remote_hash != current.remote_hash,
+            (None, None) => false,
+            (None, Some(_)) => true,
+            (Some(_), None) => {
+                self.set_config_drift(site_id, None).await?;
+                return Ok(None);
+            }
+        };
+        if !is_new {
+            return Ok(None);
+        }
+
+        self.set_config_drift(site_id, drift.clone()).await?;
+        Ok(drift)
+    }
+
+    async fn set_config_drift(
+        &mut self,
+        site_id: &str,
+        drift: Option<ConfigDrift>,
+    ) -> Result<(), SiteError> {
+        if let Some(site) = self.store.sites.get_mut(site_id) {
+            site.config_drift = drift;
+            self.save_store().await?;
+        }
+        Ok(())
+    }
+
+    pub fn drift_settings(&self) -> DriftSettings {
+        self.store.drift.clone()
+    }
+
+    pub async fn set_drift_settings(
+        &mut self,
+        mut settings: DriftSettings,
+    ) -> Result<DriftSettings, SiteError> {
+        settings.interval_minutes = settings.interval_minutes.max(MIN_INTERVAL_MINUTES);
+        self.store.drift = settings.clone();
+        self.save_store().await?;
+        Ok(settings)
+    }
+
+    pub fn list_config_snapshots(&self, site_id: &str) -> Result<Vec<SiteSnapshot>, SiteError> {
+        let site = self
+            .store
+            .sites
+            .get(site_id)
+            .ok_or_else(|| SiteError::NotFound(site_id.to_string()))?;
+        Ok(snapshot::list(&site.project_dir)?)
+    }
+
+    /// Rolls the site's project folder back to an earlier config without
+    /// contacting the site. The next drift check will offer the site's
+    /// current config again.
+    pub async fn restore_config_snapshot(
+        &mut self,
+        site_id: &str,
+        snapshot_id: &str,
+    ) -> Result<SiteSnapshot, SiteError> {
+        let site = self
+            .store
+            .sites
+            .get_mut(site_id)
+            .ok_or_else(|| SiteError::NotFound(site_id.to_string()))?;
+        let restored =
+            snapshot::restore(&site.project_dir, snapshot_id, site.config_hash.as_deref())?;
+
+        site.config_hash = restored.config_hash.clone();
+        site.config_drift = None;
+        site.config_updated_at = Some(
+            std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .unwrap()
+                .as_secs(),
+        );
+        self.save_store().await?;
+
+        Ok(restored)
+    }
+
+    pub fn generate_opencode_project_id(project_dir: &Path) -> String {
         let path_str = project_dir.to_string_lossy();
         let mut hasher = Sha256::new();
         hasher.update(path_str.as_bytes());
@@ -563,27 +1724,32 @@ impl SiteManager {
         std::fs::create_dir_all(&git_dir)?;
         std::fs::write(&opencode_file, &project_id)?;
 
-        tracing::info!("Created OpenCode project {} at {:?}", project_id, project_dir);
+        tracing::info!(
+            "Created OpenCode project {} at {:?}",
+            project_id,
+            project_dir
+        );
         Ok(project_id)
     }
 
     pub fn cleanup_opencode_project(&self, project_dir: &PathBuf) -> Result<(), SiteError> {
         let project_id = Self::generate_opencode_project_id(project_dir);
-        
+
         let git_dir = project_dir.join(".git");
         if git_dir.exists() {
             std::fs::remove_dir_all(&git_dir)?;
             tracing::info!("Removed .git directory from {:?}", project_dir);
         }
 
-        let opencode_storage = dirs::data_local_dir()
-            .unwrap_or_else(|| PathBuf::from("."))
-            .join("wordforge")
+        let opencode_storage = self
+            .data_dir
             .join("opencode-state")
             .join("data")
             .join("storage");
 
-        let project_file = opencode_storage.join("project").join(format!("{}.json", project_id));
+        let project_file = opencode_storage
+            .join("project")
+            .join(format!("{}.json", project_id));
         if project_file.exists() {
             std::fs::remove_file(&project_file)?;
             tracing::info!("Removed OpenCode project file: {:?}", project_file);
@@ -597,5 +1763,265 @@ impl SiteManager {
 
         Ok(())
     }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::{json, Value};
+
+    fn temp_dir() -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("wordforge-sites-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn site_json(id: &str, url: &str, auth: &str) -> Value {
+        json!({
+            "id": id,
+            "name": id,
+            "url": url,
+            "rest_url": format!("{}/wp-json", url),
+            "mcp_endpoint": format!("{}/wp-json/mcp", url),
+            "abilities_url": format!("{}/wp-json/abilities", url),
+            "username": "admin",
+            "app_password": if auth.is_empty() { String::new() } else { format!("{}-password", auth) },
+            "auth": auth,
+            "project_dir": "project",
+            "created_at": 0,
+            "last_used_at": 0,
+        })
+    }
+
+    fn store_json(sites: &[Value]) -> Value {
+        let sites: serde_json::Map<String, Value> = sites
+            .iter()
+            .map(|site| (site["id"].as_str().unwrap().to_string(), site.clone()))
+            .collect();
+        json!({
+            "schema_version": STORE_SCHEMA_VERSION,
+            "sites": sites,
+            "active_site_id": null,
+            "device_id": null,
+        })
+    }
+
+    #[test]
+    fn scrubs_credentials_from_backups_and_quarantine() {
+        let dir = temp_dir();
+        let store_path = dir.join(".sites.json");
+        let plaintext = store_json(&[site_json("a", "https://a.example", "plaintext-secret")]);
+        std::fs::write(dir.join(".sites.json.1"), plaintext.to_string()).unwrap();
+        std::fs::write(
+            dir.join(".sites.json.corrupt-1"),
+            "{\"auth\": \"plaintext-secret\"",
+        )
+        .unwrap();
+
+        SiteManager::scrub_plaintext_copies(&store_path);
+
+        let backup = std::fs::read_to_string(dir.join(".sites.json.1")).unwrap();
+        assert!(!backup.contains("plaintext-secret"));
+        let scrubbed = SiteManager::read_store(&dir.join(".sites.json.1"))
+            .unwrap()
+            .unwrap();
+        assert_eq!(scrubbed.sites["a"].url, "https://a.example");
+        assert!(!dir.join(".sites.json.corrupt-1").exists());
+
+        std::fs::remove_dir_all(dir).ok();
+    }
+
+    /// A manager keeping everything, site folders included, under `dir`.
+    fn manager_with(dir: &Path, sites: &[Value]) -> SiteManager {
+        let sites: Vec<Value> = sites
+            .iter()
+            .map(|site| {
+                let mut site = site.clone();
+                let project_dir = dir.join("sites").join(site["id"].as_str().unwrap());
+                site["project_dir"] = project_dir.to_string_lossy().into();
+                site
+            })
+            .collect();
+        let store_path = dir.join(".sites.json");
+        std::fs::write(&store_path, store_json(&sites).to_string()).unwrap();
+        let (store, _) = SiteManager::load_store(&store_path);
+        SiteManager {
+            client: Client::new(),
+            store,
+            data_dir: dir.to_path_buf(),
+            store_path,
+            vault: Vault::open(dir),
+            recovery: None,
+        }
+    }
 
+    /// Serves a single token exchange whose response claims `claimed_url`,
+    /// and returns the base URL to connect to.
+    async fn exchange_stub(claimed_url: &str) -> String {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let base_url = format!("http://{}", listener.local_addr().unwrap());
+        let body = json!({
+            "success": true,
+            "credentials": { "username": "attacker", "appPassword": "pw", "auth": "attacker-auth" },
+            "site": {
+                "name": "Claimed",
+                "url": claimed_url,
+                "restUrl": format!("{}/wp-json", claimed_url),
+                "mcpEndpoint": format!("{}/wp-json/mcp", claimed_url),
+                "abilitiesUrl": format!("{}/wp-json/abilities", claimed_url),
+            },
+        })
+        .to_string();
+
+        tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let mut request = Vec::new();
+            let mut buf = [0u8; 4096];
+            while !String::from_utf8_lossy(&request).contains("\r\n\r\n") {
+                let n = socket.read(&mut buf).await.unwrap();
+                if n == 0 {
+                    break;
+                }
+                request.extend_from_slice(&buf[..n]);
+            }
+            let response = format!(
+                "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                body.len(),
+                body
+            );
+            socket.write_all(response.as_bytes()).await.unwrap();
+        });
+
+        base_url
+    }
+
+    #[tokio::test]
+    async fn exchange_claiming_another_site_is_rejected() {
+        let dir = temp_dir();
+        let victim = site_json("victim", "https://victim.example", "victim-auth");
+
+        for reauthenticate in [false, true] {
+            let mut manager = manager_with(&dir, std::slice::from_ref(&victim));
+            let base_url = exchange_stub("https://victim.example").await;
+
+            let result = manager
+                .exchange_token(&base_url, "token", reauthenticate)
+                .await;
+            match reauthenticate {
+                false => assert!(matches!(result, Err(SiteError::AlreadyConnected(_)))),
+                true => assert!(matches!(result, Err(SiteError::SiteMismatch { .. }))),
+            }
+
+            let site = manager.get_site("victim").unwrap();
+            assert_eq!(site.auth, "victim-auth");
+            assert_eq!(site.username, "admin");
+        }
+
+        std::fs::remove_dir_all(dir).ok();
+    }
+
+    #[test]
+    fn same_host_compares_host_and_port() {
+        assert!(SiteManager::same_host(
+            "http://Example.com/wp",
+            "https://example.com"
+        ));
+        assert!(!SiteManager::same_host(
+            "http://127.0.0.1:8080",
+            "http://127.0.0.1:9090"
+        ));
+        assert!(!SiteManager::same_host(
+            "https://evil.example",
+            "https://victim.example"
+        ));
+        assert!(!SiteManager::same_host(
+            "not a url",
+            "https://victim.example"
+        ));
+    }
+
+    #[tokio::test]
+    async fn repair_requires_a_flagged_site() {
+        let dir = temp_dir();
+        let mut victim = site_json("victim", "https://victim.example", "victim-auth");
+        let other = site_json("other", "https://other.example", "other-auth");
+
+        let mut manager = manager_with(&dir, &[victim.clone(), other.clone()]);
+        let result = manager
+            .repair_site("victim", "https://victim.example", "token")
+            .await;
+        assert!(matches!(result, Err(SiteError::NotAwaitingReauth(_))));
+
+        victim["needs_reauth"] = json!(true);
+        let mut manager = manager_with(&dir, &[victim, other]);
+        let result = manager
+            .repair_site("victim", "https://other.example", "token")
+            .await;
+        assert!(matches!(result, Err(SiteError::SiteMismatch { .. })));
+        assert_eq!(manager.get_site("other").unwrap().auth, "other-auth");
+
+        std::fs::remove_dir_all(dir).ok();
+    }
+
+    #[tokio::test]
+    async fn removal_queues_revocation_until_recorded() {
+        let dir = temp_dir();
+        let mut manager = manager_with(
+            &dir,
+            &[site_json("gone", "https://gone.example", "gone-auth")],
+        );
+        let project_dir = manager.get_site("gone").unwrap().project_dir.clone();
+        assert!(project_dir.starts_with(&dir));
+        let project_file = dir
+            .join("opencode-state/data/storage/project")
+            .join(format!(
+                "{}.json",
+                SiteManager::generate_opencode_project_id(&project_dir)
+            ));
+        std::fs::create_dir_all(project_file.parent().unwrap()).unwrap();
+        std::fs::write(&project_file, "{}").unwrap();
+
+        let job = manager
+            .remove_site("gone")
+            .await
+            .unwrap()
+            .expect("revocation job");
+        assert!(manager.get_site("gone").is_none());
+        assert!(!project_file.exists());
+        assert_eq!(manager.store.pending_revocations.len(), 1);
+        assert_eq!(manager.revocation_jobs().len(), 1);
+
+        let failed = RevocationAttempt {
+            pending: job.pending.clone(),
+            outcome: RevokeOutcome::Retry("offline".into()),
+        };
+        manager.record_revocations(vec![failed]).await.unwrap();
+        let pending = &manager.store.pending_revocations[0];
+        assert_eq!(pending.attempts, 1);
+        assert_eq!(pending.last_error.as_deref(), Some("offline"));
+
+        let revoked = RevocationAttempt {
+            pending: job.pending,
+            outcome: RevokeOutcome::Revoked,
+        };
+        manager.record_revocations(vec![revoked]).await.unwrap();
+        assert!(manager.store.pending_revocations.is_empty());
+
+        std::fs::remove_dir_all(dir).ok();
+    }
+
+    #[test]
+    fn device_name_falls_back_to_host_name() {
+        let dir = temp_dir();
+        let mut manager = manager_with(&dir, &[]);
+        assert_eq!(manager.device_name(), default_device_name());
+        assert!(!manager.device_name().is_empty());
+
+        manager.store.device_name = Some("Studio Mac".into());
+        assert_eq!(manager.device_name(), "Studio Mac");
+
+        std::fs::remove_dir_all(dir).ok();
+    }
 }
@@ -0,0 +1,341 @@
+use crate::archive::safe_relative_path;
+use crate::manifest::{self, Manifest, ManifestError, STATE_DIR};
+use crate::persist;
+use crate::sites::ConfigHashResponse;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use thiserror::Error;
+
+const SNAPSHOTS_DIR: &str = "snapshots";
+const SNAPSHOT_FILE: &str = "snapshot.json";
+const FILES_DIR: &str = "files";
+const CURRENT_HASH_FILE: &str = "config-hash.json";
+/// Snapshots kept per site; the oldest are dropped first.
+const RETENTION: usize = 10;
+
+#[derive(Debug, Error)]
+pub enum SnapshotError {
+    #[error("IO error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Invalid snapshot: {0}")]
+    Json(#[from] serde_json::Error),
+    #[error(transparent)]
+    Manifest(#[from] ManifestError),
+    #[error("Config snapshot not found: {0}")]
+    NotFound(String),
+}
+
+/// A copy of the config files a site had before a refresh replaced them.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SiteSnapshot {
+    pub id: String,
+    /// Milliseconds since the epoch.
+    pub created_at: u64,
+    pub config_hash: Option<String>,
+    /// What the site reported for this config, when it was recorded.
+    pub hash_response: Option<ConfigHashResponse>,
+    pub files: usize,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct SnapshotFile {
+    #[serde(flatten)]
+    snapshot: SiteSnapshot,
+    manifest: Manifest,
+}
+
+fn snapshots_dir(project_dir: &Path) -> PathBuf {
+    project_dir.join(STATE_DIR).join(SNAPSHOTS_DIR)
+}
+
+fn current_hash_path(project_dir: &Path) -> PathBuf {
+    project_dir.join(STATE_DIR).join(CURRENT_HASH_FILE)
+}
+
+/// Remembers the hash response for the config now in `project_dir`, so the
+/// next snapshot can carry it.
+pub fn record_hash_response(
+    project_dir: &Path,
+    response: &ConfigHashResponse,
+) -> Result<(), SnapshotError> {
+    persist::write_atomic(
+        &current_hash_path(project_dir),
+        &serde_json::to_vec_pretty(response)?,
+    )?;
+    Ok(())
+}
+
+/// The hash response recorded for the config now in `project_dir`.
+pub fn current_hash_response(project_dir: &Path) -> Option<ConfigHashResponse> {
+    let content = std::fs::read_to_string(current_hash_path(project_dir)).ok()?;
+    serde_json::from_str(&content).ok()
+}
+
+/// Copies the managed files currently in `project_dir` into a new snapshot.
+/// Returns `None` when there is nothing to keep.
+pub fn create(
+    project_dir: &Path,
+    config_hash: Option<&str>,
+) -> Result<Option<SiteSnapshot>, SnapshotError> {
+    let snapshot = write_snapshot(project_dir, config_hash)?;
+    prune(project_dir)?;
+    Ok(snapshot)
+}
+
+fn write_snapshot(
+    project_dir: &Path,
+    config_hash: Option<&str>,
+) -> Result<Option<SiteSnapshot>, SnapshotError> {
+    // Folders from before refreshes were tracked count as entirely managed.
+    let manifest = match Manifest::load(project_dir)? {
+        Some(manifest) => manifest,
+        None => Manifest::scan(project_dir)?,
+    };
+    if manifest.files.is_empty() {
+        return Ok(None);
+    }
+
+    let root = snapshots_dir(project_dir);
+    let mut created_at = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0);
+    while root.join(created_at.to_string()).exists() {
+        created_at += 1;
+    }
+    let dir = root.join(created_at.to_string());
+    let files_dir = dir.join(FILES_DIR);
+
+    let mut copied = Manifest::default();
+    for (path, hash) in &manifest.files {
+        let Some(relative) = safe_relative_path(Path::new(path)) else {
+            continue;
+        };
+        let source = project_dir.join(&relative);
+        if !source.is_file() {
+            continue;
+        }
+        let target = files_dir.join(&relative);
+        if let Some(parent) = target.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::copy(&source, &target)?;
+        copied.files.insert(path.clone(), hash.clone());
+    }
+
+    let hash_response = current_hash_response(project_dir);
+    let snapshot = SiteSnapshot {
+        id: created_at.to_string(),
+        created_at,
+        config_hash: config_hash
+            .map(String::from)
+            .or_else(|| hash_response.as_ref().map(|r| r.hash.clone())),
+        hash_response,
+        files: copied.files.len(),
+    };
+    let file = SnapshotFile {
+        snapshot: snapshot.clone(),
+        manifest: copied,
+    };
+    persist::write_atomic(&dir.join(SNAPSHOT_FILE), &serde_json::to_vec_pretty(&file)?)?;
+
+    tracing::info!(
+        "Snapshotted {} config files of {:?} as {}",
+        snapshot.files,
+        project_dir,
+        snapshot.id
+    );
+    Ok(Some(snapshot))
+}
+
+fn load(project_dir: &Path, id: &str) -> Result<SnapshotFile, SnapshotError> {
+    if id.parse::<u64>().is_err() {
+        return Err(SnapshotError::NotFound(id.to_string()));
+    }
+    let path = snapshots_dir(project_dir).join(id).join(SNAPSHOT_FILE);
+    match std::fs::read_to_string(path) {
+        Ok(content) => Ok(serde_json::from_str(&content)?),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            Err(SnapshotError::NotFound(id.to_string()))
+        }
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Snapshots, newest first. Unreadable ones are skipped.
+pub fn list(project_dir: &Path) -> Result<Vec<SiteSnapshot>, SnapshotError> {
+    let entries = match std::fs::read_dir(snapshots_dir(project_dir)) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e.into()),
+    };
+
+    let mut snapshots = Vec::new();
+    for entry in entries {
+        let name = entry?.file_name();
+        let Some(id) = name.to_str() else {
+            continue;
+        };
+        match load(project_dir, id) {
+            Ok(file) => snapshots.push(file.snapshot),
+            Err(e) => tracing::warn!("Skipping config snapshot {}: {}", id, e),
+        }
+    }
+
+    snapshots.sort_by_key(|s| std::cmp::Reverse(s.created_at));
+    Ok(snapshots)
+}
+
+fn prune(project_dir: &Path) -> Result<(), SnapshotError> {
+    for stale in list(project_dir)?.iter().skip(RETENTION) {
+        std::fs::remove_dir_all(snapshots_dir(project_dir).join(&stale.id))?;
+    }
+    Ok(())
+}
+
+/// Puts the files of snapshot `id` back in place of the current managed
+/// files and makes its manifest current. The config being replaced is
+/// snapshotted first, so a restore can itself be undone. Works entirely
+/// offline.
+pub fn restore(
+    project_dir: &Path,
+    id: &str,
+    current_hash: Option<&str>,
+) -> Result<SiteSnapshot, SnapshotError> {
+    let file = load(project_dir, id)?;
+    // Pruning waits until the restore is done so it cannot take `id` with it.
+    write_snapshot(project_dir, current_hash)?;
+    let files_dir = snapshots_dir(project_dir).join(id).join(FILES_DIR);
+
+    if let Some(current) = Manifest::load(project_dir)? {
+        let dropped = current
+            .files
+            .keys()
+            .filter(|path| !file.manifest.files.contains_key(*path));
+        for path in dropped {
+            let Some(relative) = safe_relative_path(Path::new(path)) else {
+                continue;
+            };
+            let target = project_dir.join(relative);
+            if target.is_file() {
+                manifest::remove_with_empty_parents(project_dir, &target)?;
+            }
+        }
+    }
+
+    for path in file.manifest.files.keys() {
+        let Some(relative) = safe_relative_path(Path::new(path)) else {
+            continue;
+        };
+        let target = project_dir.join(&relative);
+        if let Some(parent) = target.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::copy(files_dir.join(&relative), target)?;
+    }
+
+    file.manifest.save(project_dir)?;
+    match &file.snapshot.hash_response {
+        Some(response) => record_hash_response(project_dir, response)?,
+        None => {
+            std::fs::remove_file(current_hash_path(project_dir)).ok();
+        }
+    }
+
+    prune(project_dir)?;
+    tracing::info!("Restored config snapshot {} into {:?}", id, project_dir);
+    Ok(file.snapshot)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_project() -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("wordforge-snapshot-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn write(project_dir: &Path, path: &str, content: &str) {
+        let path = project_dir.join(path);
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(path, content).unwrap();
+    }
+
+    fn read(project_dir: &Path, path: &str) -> Option<String> {
+        std::fs::read_to_string(project_dir.join(path)).ok()
+    }
+
+    #[test]
+    fn restore_puts_files_back_and_can_be_undone() {
+        let project = temp_project();
+        write(&project, "opencode.json", "v1");
+        write(&project, "agent/a.md", "a1");
+        Manifest::scan(&project).unwrap().save(&project).unwrap();
+        let first = create(&project, Some("hash-1")).unwrap().unwrap();
+        assert_eq!(first.files, 2);
+
+        write(&project, "opencode.json", "v2");
+        std::fs::remove_dir_all(project.join("agent")).unwrap();
+        write(&project, "extra.md", "new");
+        Manifest::scan(&project).unwrap().save(&project).unwrap();
+
+        let restored = restore(&project, &first.id, Some("hash-2")).unwrap();
+        assert_eq!(restored.config_hash.as_deref(), Some("hash-1"));
+        assert_eq!(read(&project, "opencode.json").as_deref(), Some("v1"));
+        assert_eq!(read(&project, "agent/a.md").as_deref(), Some("a1"));
+        assert!(read(&project, "extra.md").is_none());
+        assert_eq!(Manifest::load(&project).unwrap().unwrap().files.len(), 2);
+
+        let snapshots = list(&project).unwrap();
+        assert_eq!(snapshots.len(), 2);
+        let undo = &snapshots[0];
+        assert_eq!(undo.config_hash.as_deref(), Some("hash-2"));
+        restore(&project, &undo.id, None).unwrap();
+        assert_eq!(read(&project, "opencode.json").as_deref(), Some("v2"));
+        assert_eq!(read(&project, "extra.md").as_deref(), Some("new"));
+
+        std::fs::remove_dir_all(project).ok();
+    }
+
+    #[test]
+    fn restore_of_unknown_snapshot_fails() {
+        let project = temp_project();
+        assert!(matches!(
+            restore(&project, "123", None),
+            Err(SnapshotError::NotFound(_))
+        ));
+        assert!(matches!(
+            restore(&project, "../escape", None),
+            Err(SnapshotError::NotFound(_))
+        ));
+        std::fs::remove_dir_all(project).ok();
+    }
+
+    #[test]
+    fn prune_keeps_the_newest() {
+        let project = temp_project();
+        write(&project, "opencode.json", "config");
+
+        let created: Vec<String> = (0..RETENTION + 2)
+            .map(|_| create(&project, None).unwrap().unwrap().id)
+            .collect();
+        let kept: Vec<String> = list(&project).unwrap().into_iter().map(|s| s.id).collect();
+
+        assert_eq!(kept.len(), RETENTION);
+        let mut newest: Vec<String> = created[2..].to_vec();
+        newest.reverse();
+        assert_eq!(kept, newest);
+
+        std::fs::remove_dir_all(project).ok();
+    }
+
+    #[test]
+    fn empty_project_has_nothing_to_snapshot() {
+        let project = temp_project();
+        assert!(create(&project, None).unwrap().is_none());
+        assert!(list(&project).unwrap().is_empty());
+        std::fs::remove_dir_all(project).ok();
+    }
+}
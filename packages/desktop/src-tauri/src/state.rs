@@ -1,6 +1,13 @@
-use crate::opencode::{GlobalConfig, OpenCodeManager, Status};
+use crate::config::{ConfigChange, ConfigError, ConfigSnapshot, GlobalConfig, HistorySettings};
+use crate::idle::IdlePolicy;
+use crate::installs::InstallMetadata;
+use crate::logs::LogStore;
+use crate::opencode::{DownloadCancel, OpenCodeManager, ReleaseInfo, ServerInfo, Status};
+use crate::supervisor::{RestartPolicy, ShutdownOutcome};
 use serde_json::Value;
+use std::collections::HashMap;
 use std::path::PathBuf;
+use std::sync::Arc;
 use tauri::AppHandle;
 
 pub struct AppState {
@@ -14,8 +21,8 @@ impl AppState {
         }
     }
 
-    pub async fn get_status(&self) -> Status {
-        self.opencode.get_status().await
+    pub async fn get_status(&self, site_id: Option<&str>) -> Status {
+        self.opencode.get_status(site_id).await
     }
 
     pub async fn get_installed_version(&self) -> Option<String> {
@@ -26,35 +33,145 @@ impl AppState {
         self.opencode.get_latest_version().await
     }
 
-    pub async fn download_opencode(&mut self, app: &AppHandle) -> Result<(), crate::opencode::Error> {
+    pub async fn download_opencode(
+        &mut self,
+        app: &AppHandle,
+    ) -> Result<(), crate::opencode::Error> {
         self.opencode.download(app).await
     }
 
+    pub fn log_store(&self) -> Arc<LogStore> {
+        self.opencode.log_store()
+    }
+
+    pub fn download_cancel_handle(&self) -> DownloadCancel {
+        self.opencode.download_cancel_handle()
+    }
+
+    pub async fn install_opencode_version(
+        &mut self,
+        app: &AppHandle,
+        tag: &str,
+    ) -> Result<(), crate::opencode::Error> {
+        self.opencode.install_version(app, tag).await
+    }
+
+    pub async fn list_opencode_releases(&self) -> Result<Vec<ReleaseInfo>, crate::opencode::Error> {
+        self.opencode.list_releases().await
+    }
+
+    pub fn get_opencode_installs(&self) -> InstallMetadata {
+        self.opencode.get_installs()
+    }
+
+    pub async fn set_active_opencode_version(
+        &mut self,
+        version: &str,
+        pinned: bool,
+    ) -> Result<(), crate::opencode::Error> {
+        self.opencode.set_active_version(version, pinned).await
+    }
+
+    pub async fn rollback_opencode(&mut self) -> Result<String, crate::opencode::Error> {
+        self.opencode.rollback().await
+    }
+
     pub async fn start_opencode_with_config(
-        &mut self, 
-        cors_origin: Option<String>,
-        project_dir: Option<PathBuf>,
+        &mut self,
+        site_id: &str,
+        cors_origin: String,
+        project_dir: PathBuf,
+        idle_policy: IdlePolicy,
     ) -> Result<u16, crate::opencode::Error> {
-        self.opencode.start(cors_origin, project_dir).await
+        self.opencode
+            .start(site_id, cors_origin, project_dir, idle_policy)
+            .await
+    }
+
+    pub fn set_idle_policy(&self, site_id: &str, policy: IdlePolicy) {
+        self.opencode.set_idle_policy(site_id, policy);
+    }
+
+    pub async fn stop_opencode(
+        &mut self,
+        site_id: &str,
+    ) -> Result<Option<ShutdownOutcome>, crate::opencode::Error> {
+        self.opencode.stop(site_id).await
+    }
+
+    pub async fn reclaim_orphaned_servers(&mut self, idle_policies: &HashMap<String, IdlePolicy>) {
+        self.opencode.reclaim_orphans(idle_policies).await
     }
 
-    pub async fn stop_opencode(&mut self) -> Result<(), crate::opencode::Error> {
-        self.opencode.stop().await
+    pub async fn stop_all_opencode(
+        &mut self,
+    ) -> Result<Vec<ShutdownOutcome>, crate::opencode::Error> {
+        self.opencode.stop_all().await
     }
 
-    pub fn get_port(&self) -> Option<u16> {
-        self.opencode.get_port()
+    pub fn get_port(&self, site_id: &str) -> Option<u16> {
+        self.opencode.get_port(site_id)
+    }
+
+    pub fn has_server(&self, site_id: &str) -> bool {
+        self.opencode.has_server(site_id)
+    }
+
+    pub fn list_servers(&self) -> Vec<ServerInfo> {
+        self.opencode.list_servers()
+    }
+
+    pub fn get_restart_policy(&self) -> RestartPolicy {
+        self.opencode.get_restart_policy()
+    }
+
+    pub async fn set_restart_policy(
+        &mut self,
+        policy: RestartPolicy,
+    ) -> Result<(), crate::opencode::Error> {
+        self.opencode.set_restart_policy(policy).await
     }
 
     pub async fn check_update_available(&self) -> Result<bool, crate::opencode::Error> {
         self.opencode.check_update_available().await
     }
 
-    pub async fn get_global_config(&self) -> GlobalConfig {
+    pub async fn get_global_config(&self) -> Result<GlobalConfig, ConfigError> {
         self.opencode.get_global_config().await
     }
 
-    pub async fn set_global_config(&self, config: Value) -> Result<(), crate::opencode::Error> {
+    pub async fn set_global_config(&self, config: &GlobalConfig) -> Result<(), ConfigError> {
         self.opencode.set_global_config(config).await
     }
+
+    pub async fn patch_global_config(&self, patch: &Value) -> Result<GlobalConfig, ConfigError> {
+        self.opencode.patch_global_config(patch).await
+    }
+
+    pub async fn list_config_snapshots(&self) -> Result<Vec<ConfigSnapshot>, ConfigError> {
+        self.opencode.list_config_snapshots().await
+    }
+
+    pub async fn diff_config_snapshots(
+        &self,
+        from: &str,
+        to: Option<&str>,
+    ) -> Result<Vec<ConfigChange>, ConfigError> {
+        self.opencode.diff_config_snapshots(from, to).await
+    }
+
+    pub async fn restore_config_snapshot(&self, id: &str) -> Result<GlobalConfig, ConfigError> {
+        self.opencode.restore_config_snapshot(id).await
+    }
+
+    pub async fn get_config_history_settings(&self) -> HistorySettings {
+        self.opencode.get_config_history_settings().await
+    }
+
+    pub async fn set_config_history_settings(
+        &self,
+        settings: &HistorySettings,
+    ) -> Result<(), ConfigError> {
+        self.opencode.set_config_history_settings(settings).await
+    }
 }
@@ -0,0 +1,341 @@
+use crate::logs::LogSink;
+use crate::opencode::{self, ServerSpec, Status};
+use crate::persist;
+use crate::pidfile;
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tauri::AppHandle;
+use tokio::process::Child;
+use tokio::sync::watch;
+use tracing::{error, info, warn};
+
+const POLICY_FILE: &str = "restart-policy.json";
+pub const STDERR_TAIL_LINES: usize = 20;
+const SHUTDOWN_POLL_INTERVAL: Duration = Duration::from_millis(100);
+const ADOPTED_POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// How a crashed OpenCode server is brought back up.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RestartPolicy {
+    pub enabled: bool,
+    /// Consecutive restarts allowed before the server is left in `Error`.
+    pub max_restarts: u32,
+    pub initial_backoff_ms: u64,
+    pub max_backoff_ms: u64,
+    pub multiplier: f64,
+    /// A server that stayed up this long is considered healthy again and
+    /// its restart counter is reset.
+    pub reset_after_secs: u64,
+    /// How long a server may take to exit after SIGTERM before it is killed.
+    pub shutdown_grace_secs: u64,
+}
+
+impl Default for RestartPolicy {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            max_restarts: 5,
+            initial_backoff_ms: 1_000,
+            max_backoff_ms: 60_000,
+            multiplier: 2.0,
+            reset_after_secs: 300,
+            shutdown_grace_secs: 10,
+        }
+    }
+}
+
+impl RestartPolicy {
+    pub fn load(install_dir: &Path) -> Self {
+        std::fs::read_to_string(install_dir.join(POLICY_FILE))
+            .ok()
+            .and_then(|content| serde_json::from_str(&content).ok())
+            .unwrap_or_default()
+    }
+
+    pub async fn save(&self, install_dir: &Path) -> Result<(), std::io::Error> {
+        let content = serde_json::to_string_pretty(self)?;
+        persist::write_atomic(&install_dir.join(POLICY_FILE), content.as_bytes())
+    }
+
+    fn backoff(&self, restarts: u32) -> Duration {
+        let factor = self.multiplier.max(1.0).powi(restarts.min(32) as i32);
+        let delay = (self.initial_backoff_ms as f64 * factor).min(self.max_backoff_ms as f64);
+        Duration::from_millis(delay as u64)
+    }
+}
+
+pub type StderrTail = Arc<Mutex<VecDeque<String>>>;
+
+/// How a stopped server went down. `clean` is false when it had to be killed
+/// after the grace period.
+#[derive(Debug, Clone, Serialize)]
+pub struct ShutdownOutcome {
+    pub site_id: String,
+    pub clean: bool,
+    pub exit_code: Option<i32>,
+}
+
+/// A running server: either one we spawned, or one left behind by a previous
+/// run of the app. An adopted server is not our child, so it can only be
+/// observed through its pid and its output is not captured.
+pub enum ServerProcess {
+    Spawned(Child),
+    Adopted(u32),
+}
+
+impl ServerProcess {
+    fn id(&self) -> Option<u32> {
+        match self {
+            ServerProcess::Spawned(child) => child.id(),
+            ServerProcess::Adopted(pid) => Some(*pid),
+        }
+    }
+
+    /// Waits for the process to exit and returns its exit code, if known.
+    async fn wait(&mut self) -> Option<i32> {
+        match self {
+            ServerProcess::Spawned(child) => child.wait().await.ok().and_then(|s| s.code()),
+            ServerProcess::Adopted(pid) => {
+                while pidfile::is_alive(*pid) {
+                    tokio::time::sleep(ADOPTED_POLL_INTERVAL).await;
+                }
+                None
+            }
+        }
+    }
+
+    /// `Some(exit code)` once the process has exited.
+    fn try_wait(&mut self) -> Option<Option<i32>> {
+        match self {
+            ServerProcess::Spawned(child) => child.try_wait().ok().flatten().map(|s| s.code()),
+            ServerProcess::Adopted(pid) => (!pidfile::is_alive(*pid)).then_some(None),
+        }
+    }
+
+    async fn kill(&mut self) {
+        match self {
+            ServerProcess::Spawned(child) => {
+                child.kill().await.ok();
+            }
+            ServerProcess::Adopted(pid) => pidfile::force_kill(*pid),
+        }
+    }
+}
+
+/// Everything the supervisor task needs to watch and respawn one server.
+pub struct Supervisor {
+    pub app: AppHandle,
+    pub client: Client,
+    pub spec: ServerSpec,
+    pub policy: RestartPolicy,
+    pub status: Arc<Mutex<Status>>,
+    pub stderr_tail: StderrTail,
+    pub logs: LogSink,
+}
+
+impl Supervisor {
+    fn set_status(&self, status: Status) {
+        if let Ok(mut current) = self.status.lock() {
+            *current = status.clone();
+        }
+        opencode::emit_status(&self.app, &self.spec.site_id, &status);
+    }
+
+    fn exit_message(&self, code: Option<i32>) -> String {
+        let code = code.map_or_else(|| "signal".to_string(), |c| c.to_string());
+        let tail = self
+            .stderr_tail
+            .lock()
+            .map(|t| t.iter().cloned().collect::<Vec<_>>().join("\n"))
+            .unwrap_or_default();
+
+        if tail.is_empty() {
+            format!("OpenCode exited unexpectedly (exit code: {})", code)
+        } else {
+            format!(
+                "OpenCode exited unexpectedly (exit code: {})\n{}",
+                code, tail
+            )
+        }
+    }
+
+    /// Watches `child` until it exits or `stop_rx` fires. Unexpected exits
+    /// are reported as `Status::Error` and restarted according to the
+    /// policy; a stop request shuts the child down and reports `Stopped`.
+    /// Returns how the shutdown went, or `None` if no process was running
+    /// when the stop arrived.
+    pub async fn run(
+        self,
+        process: ServerProcess,
+        mut stop_rx: watch::Receiver<bool>,
+    ) -> Option<ShutdownOutcome> {
+        let site_id = self.spec.site_id.clone();
+        let mut child = Some(process);
+        let mut spawn_error: Option<String> = None;
+        let mut restarts: u32 = 0;
+        let mut started_at = Instant::now();
+
+        loop {
+            let failure = match child.as_mut() {
+                Some(process) => {
+                    tokio::select! {
+                        code = process.wait() => {
+                            warn!("OpenCode for site {} exited (code: {:?})", site_id, code);
+                            self.exit_message(code)
+                        }
+                        _ = stop_rx.changed() => {
+                            let grace = Duration::from_secs(self.policy.shutdown_grace_secs);
+                            let outcome = terminate(&site_id, process, grace).await;
+                            self.set_status(Status::Stopped);
+                            return Some(outcome);
+                        }
+                    }
+                }
+                None => spawn_error
+                    .take()
+                    .unwrap_or_else(|| self.exit_message(None)),
+            };
+            child = None;
+            self.set_status(Status::Error(failure));
+
+            if started_at.elapsed() >= Duration::from_secs(self.policy.reset_after_secs) {
+                restarts = 0;
+            }
+
+            if !self.policy.enabled || restarts >= self.policy.max_restarts {
+                error!(
+                    "OpenCode for site {} crashed {} times, giving up",
+                    site_id,
+                    restarts + 1
+                );
+                return None;
+            }
+
+            let delay = self.policy.backoff(restarts);
+            restarts += 1;
+            info!(
+                "Restarting OpenCode for site {} in {:?} (attempt {})",
+                site_id, delay, restarts
+            );
+
+            tokio::select! {
+                _ = tokio::time::sleep(delay) => {}
+                _ = stop_rx.changed() => {
+                    self.set_status(Status::Stopped);
+                    return None;
+                }
+            }
+
+            if let Ok(mut tail) = self.stderr_tail.lock() {
+                tail.clear();
+            }
+            self.set_status(Status::Starting);
+            started_at = Instant::now();
+
+            match opencode::spawn_server(&self.client, &self.spec, &self.stderr_tail, &self.logs)
+                .await
+            {
+                Ok(process) => {
+                    child = Some(ServerProcess::Spawned(process));
+                    self.set_status(Status::Running);
+                }
+                Err(e) => {
+                    error!("Failed to restart OpenCode for site {}: {}", site_id, e);
+                    spawn_error = Some(format!("Failed to restart OpenCode: {}", e));
+                }
+            }
+        }
+    }
+}
+
+/// Asks the server to exit with SIGTERM so it can flush its storage, polls
+/// for exit during `grace` and kills it afterwards. Windows has no SIGTERM
+/// for console processes, so the server is killed right away there.
+pub async fn terminate(
+    site_id: &str,
+    process: &mut ServerProcess,
+    grace: Duration,
+) -> ShutdownOutcome {
+    if process.id().is_some_and(pidfile::request_exit) {
+        let deadline = Instant::now() + grace;
+        while Instant::now() < deadline {
+            if let Some(exit_code) = process.try_wait() {
+                return ShutdownOutcome {
+                    site_id: site_id.to_string(),
+                    clean: true,
+                    exit_code,
+                };
+            }
+            tokio::time::sleep(SHUTDOWN_POLL_INTERVAL).await;
+        }
+        warn!(
+            "OpenCode for site {} did not exit within {:?}, killing it",
+            site_id, grace
+        );
+    }
+
+    process.kill().await;
+    ShutdownOutcome {
+        site_id: site_id.to_string(),
+        clean: false,
+        exit_code: process.try_wait().flatten(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn delays(policy: &RestartPolicy, restarts: std::ops::Range<u32>) -> Vec<u64> {
+        restarts
+            .map(|n| policy.backoff(n).as_millis() as u64)
+            .collect()
+    }
+
+    #[test]
+    fn backoff_grows_by_the_multiplier_up_to_the_cap() {
+        let policy = RestartPolicy::default();
+        assert_eq!(
+            delays(&policy, 0..8),
+            [1_000, 2_000, 4_000, 8_000, 16_000, 32_000, 60_000, 60_000]
+        );
+        assert_eq!(policy.backoff(u32::MAX), Duration::from_millis(60_000));
+    }
+
+    #[test]
+    fn backoff_never_shrinks() {
+        let policy = RestartPolicy {
+            multiplier: 0.5,
+            ..RestartPolicy::default()
+        };
+        assert_eq!(delays(&policy, 0..3), [1_000, 1_000, 1_000]);
+    }
+
+    #[tokio::test]
+    async fn policy_round_trips_and_falls_back_to_defaults() {
+        let dir = std::env::temp_dir().join(format!("wordforge-policy-{}", uuid::Uuid::new_v4()));
+        assert_eq!(RestartPolicy::load(&dir).max_restarts, 5);
+
+        let policy = RestartPolicy {
+            max_restarts: 2,
+            shutdown_grace_secs: 3,
+            ..RestartPolicy::default()
+        };
+        policy.save(&dir).await.unwrap();
+        let loaded = RestartPolicy::load(&dir);
+        assert_eq!(loaded.max_restarts, 2);
+        assert_eq!(loaded.shutdown_grace_secs, 3);
+
+        std::fs::write(dir.join(POLICY_FILE), "{\"enabled\": false}").unwrap();
+        let partial = RestartPolicy::load(&dir);
+        assert!(!partial.enabled);
+        assert_eq!(partial.max_backoff_ms, 60_000);
+
+        std::fs::remove_dir_all(dir).ok();
+    }
+}
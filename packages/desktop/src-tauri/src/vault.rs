@@ -0,0 +1,609 @@
+use crate::persist;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use ring::aead::{Aad, LessSafeKey, Nonce, UnboundKey, AES_256_GCM, NONCE_LEN};
+use ring::pbkdf2;
+use ring::rand::{SecureRandom, SystemRandom};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::num::NonZeroU32;
+use std::path::{Path, PathBuf};
+use thiserror::Error;
+use tracing::{info, warn};
+
+const VAULT_FILE: &str = ".vault.json";
+const VAULT_VERSION: u32 = 1;
+const KEY_LEN: usize = 32;
+const SALT_LEN: usize = 16;
+const PBKDF2_ITERATIONS: u32 = 600_000;
+const AAD: &[u8] = b"wordforge-vault-v1";
+#[cfg(windows)]
+const PROTECTED_KEY_FILE: &str = ".vault.key";
+#[cfg(any(target_os = "linux", target_os = "macos"))]
+const KEYCHAIN_SERVICE: &str = "wordforge-desktop";
+#[cfg(any(target_os = "linux", target_os = "macos"))]
+const KEYCHAIN_ACCOUNT: &str = "site-vault";
+
+#[derive(Debug, Error)]
+pub enum VaultError {
+    #[error("IO error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("JSON error: {0}")]
+    Json(#[from] serde_json::Error),
+    #[error("Credential vault is locked")]
+    Locked,
+    #[error("Wrong passphrase")]
+    WrongPassphrase,
+    #[error("Credential vault is corrupted: {0}")]
+    Corrupted(String),
+    #[error("OS keychain unavailable: {0}")]
+    Keychain(String),
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SiteCredentials {
+    pub app_password: String,
+    pub auth: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+enum KeySource {
+    /// A random key kept by the OS secret service.
+    Keychain,
+    /// A key derived from the user's passphrase with PBKDF2-HMAC-SHA256.
+    Passphrase { salt: String, iterations: u32 },
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct VaultFile {
+    version: u32,
+    key_source: KeySource,
+    nonce: String,
+    ciphertext: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum VaultStatus {
+    Unlocked,
+    /// Sites are listed, but their credentials need `unlock` first.
+    Locked,
+    /// No vault yet and no OS keychain to create one; credentials stay in
+    /// the site list until a passphrase is set.
+    Uninitialized,
+}
+
+/// Site credentials encrypted at rest with AES-256-GCM in `.vault.json`.
+pub struct Vault {
+    path: PathBuf,
+    source: Option<KeySource>,
+    key: Option<[u8; KEY_LEN]>,
+    entries: HashMap<String, SiteCredentials>,
+    quarantined: Option<PathBuf>,
+}
+
+impl Vault {
+    /// Opens the vault in `dir`, unlocking it right away when its key is
+    /// held by the OS keychain. A vault file that cannot be parsed is moved
+    /// aside rather than overwritten, see [`Vault::take_quarantined`].
+    pub fn open(dir: &Path) -> Self {
+        let path = dir.join(VAULT_FILE);
+        let mut quarantined = None;
+        let source = match std::fs::read_to_string(&path) {
+            Ok(content) => match serde_json::from_str::<VaultFile>(&content) {
+                Ok(file) => Some(file.key_source),
+                Err(e) => {
+                    warn!(
+                        "Credential vault {:?} is unreadable, moving it aside: {}",
+                        path, e
+                    );
+                    quarantined = persist::quarantine(&path);
+                    None
+                }
+            },
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => None,
+            Err(e) => {
+                warn!(
+                    "Failed to read credential vault {:?}, moving it aside: {}",
+                    path, e
+                );
+                quarantined = persist::quarantine(&path);
+                None
+            }
+        };
+
+        let mut vault = Self {
+            path,
+            source,
+            key: None,
+            entries: HashMap::new(),
+            quarantined,
+        };
+
+        if matches!(vault.source, Some(KeySource::Keychain)) {
+            match keychain_get(&vault.path).and_then(|key| decode_key(&key)) {
+                Ok(key) => {
+                    if let Err(e) = vault.unlock_with(key) {
+                        warn!("Failed to open credential vault: {}", e);
+                    }
+                }
+                Err(e) => warn!("Failed to read vault key from the OS keychain: {}", e),
+            }
+        }
+
+        vault
+    }
+
+    pub fn status(&self) -> VaultStatus {
+        match (&self.source, &self.key) {
+            (None, _) => VaultStatus::Uninitialized,
+            (Some(_), Some(_)) => VaultStatus::Unlocked,
+            (Some(_), None) => VaultStatus::Locked,
+        }
+    }
+
+    /// Where an unreadable vault file was moved when the vault was opened.
+    pub fn take_quarantined(&mut self) -> Option<PathBuf> {
+        self.quarantined.take()
+    }
+
+    pub fn get(&self, site_id: &str) -> Option<&SiteCredentials> {
+        self.entries.get(site_id)
+    }
+
+    pub fn set(&mut self, site_id: &str, credentials: SiteCredentials) -> Result<(), VaultError> {
+        if self.key.is_none() {
+            return Err(VaultError::Locked);
+        }
+        self.entries.insert(site_id.to_string(), credentials);
+        self.save()
+    }
+
+    pub fn remove(&mut self, site_id: &str) -> Result<(), VaultError> {
+        if self.key.is_none() {
+            return Err(VaultError::Locked);
+        }
+        if self.entries.remove(site_id).is_some() {
+            self.save()?;
+        }
+        Ok(())
+    }
+
+    /// Drops entries of sites that no longer exist, e.g. removed while the
+    /// vault was locked.
+    pub fn retain(&mut self, site_ids: &[&str]) -> Result<(), VaultError> {
+        let before = self.entries.len();
+        self.entries.retain(|id, _| site_ids.contains(&id.as_str()));
+        if self.entries.len() != before {
+            self.save()?;
+        }
+        Ok(())
+    }
+
+    /// Keys the vault with a fresh random key held by the OS keychain,
+    /// keeping its current entries.
+    pub fn initialize_with_keychain(&mut self) -> Result<(), VaultError> {
+        let key = random_bytes::<KEY_LEN>()?;
+        keychain_set(&self.path, &BASE64.encode(key))?;
+        self.source = Some(KeySource::Keychain);
+        self.key = Some(key);
+        self.save()?;
+        info!("Created credential vault keyed by the OS keychain");
+        Ok(())
+    }
+
+    pub fn unlock(&mut self, passphrase: &str) -> Result<(), VaultError> {
+        match &self.source {
+            Some(KeySource::Passphrase { salt, iterations }) => {
+                let salt = BASE64
+                    .decode(salt)
+                    .map_err(|e| VaultError::Corrupted(e.to_string()))?;
+                let key = derive_key(passphrase, &salt, *iterations);
+                self.unlock_with(key)
+            }
+            Some(KeySource::Keychain) => {
+                let key = decode_key(&keychain_get(&self.path)?)?;
+                self.unlock_with(key)
+            }
+            None => Ok(()),
+        }
+    }
+
+    pub fn lock(&mut self) {
+        if matches!(self.source, Some(KeySource::Passphrase { .. })) {
+            self.key = None;
+            self.entries.clear();
+        }
+    }
+
+    /// Re-encrypts the vault under a key derived from `passphrase`, or under
+    /// a keychain-held key when `None`. Creates the vault if needed.
+    pub fn set_passphrase(&mut self, passphrase: Option<&str>) -> Result<(), VaultError> {
+        if self.source.is_some() && self.key.is_none() {
+            return Err(VaultError::Locked);
+        }
+
+        match passphrase {
+            Some(passphrase) => {
+                let salt = random_bytes::<SALT_LEN>()?;
+                self.key = Some(derive_key(passphrase, &salt, PBKDF2_ITERATIONS));
+                self.source = Some(KeySource::Passphrase {
+                    salt: BASE64.encode(salt),
+                    iterations: PBKDF2_ITERATIONS,
+                });
+                self.save()
+            }
+            None => self.initialize_with_keychain(),
+        }
+    }
+
+    fn unlock_with(&mut self, key: [u8; KEY_LEN]) -> Result<(), VaultError> {
+        let content = std::fs::read_to_string(&self.path)?;
+        let file: VaultFile = serde_json::from_str(&content)?;
+        if file.version != VAULT_VERSION {
+            return Err(VaultError::Corrupted(format!(
+                "unsupported version {}",
+                file.version
+            )));
+        }
+
+        let nonce = BASE64
+            .decode(&file.nonce)
+            .map_err(|e| VaultError::Corrupted(e.to_string()))?;
+        let nonce = Nonce::try_assume_unique_for_key(&nonce)
+            .map_err(|_| VaultError::Corrupted("bad nonce".into()))?;
+        let mut data = BASE64
+            .decode(&file.ciphertext)
+            .map_err(|e| VaultError::Corrupted(e.to_string()))?;
+
+        let plaintext = cipher(&key)?
+            .open_in_place(nonce, Aad::from(AAD), &mut data)
+            .map_err(|_| VaultError::WrongPassphrase)?;
+
+        self.entries = serde_json::from_slice(plaintext)?;
+        self.key = Some(key);
+        Ok(())
+    }
+
+    fn save(&self) -> Result<(), VaultError> {
+        let (Some(key), Some(source)) = (&self.key, &self.source) else {
+            return Err(VaultError::Locked);
+        };
+
+        let nonce_bytes = random_bytes::<NONCE_LEN>()?;
+        let mut data = serde_json::to_vec(&self.entries)?;
+        cipher(key)?
+            .seal_in_place_append_tag(
+                Nonce::assume_unique_for_key(nonce_bytes),
+                Aad::from(AAD),
+                &mut data,
+            )
+            .map_err(|_| VaultError::Corrupted("encryption failed".into()))?;
+
+        let file = VaultFile {
+            version: VAULT_VERSION,
+            key_source: source.clone(),
+            nonce: BASE64.encode(nonce_bytes),
+            ciphertext: BASE64.encode(data),
+        };
+
+        persist::write_atomic(&self.path, serde_json::to_string_pretty(&file)?.as_bytes())?;
+        Ok(())
+    }
+}
+
+fn cipher(key: &[u8; KEY_LEN]) -> Result<LessSafeKey, VaultError> {
+    UnboundKey::new(&AES_256_GCM, key)
+        .map(LessSafeKey::new)
+        .map_err(|_| VaultError::Corrupted("invalid key".into()))
+}
+
+fn derive_key(passphrase: &str, salt: &[u8], iterations: u32) -> [u8; KEY_LEN] {
+    let mut key = [0u8; KEY_LEN];
+    let iterations = NonZeroU32::new(iterations).unwrap_or(NonZeroU32::MIN);
+    pbkdf2::derive(
+        pbkdf2::PBKDF2_HMAC_SHA256,
+        iterations,
+        salt,
+        passphrase.as_bytes(),
+        &mut key,
+    );
+    key
+}
+
+fn decode_key(encoded: &str) -> Result<[u8; KEY_LEN], VaultError> {
+    BASE64
+        .decode(encoded.trim())
+        .ok()
+        .and_then(|bytes| bytes.try_into().ok())
+        .ok_or_else(|| VaultError::Keychain("stored key is malformed".into()))
+}
+
+fn random_bytes<const N: usize>() -> Result<[u8; N], VaultError> {
+    let mut bytes = [0u8; N];
+    SystemRandom::new()
+        .fill(&mut bytes)
+        .map_err(|_| VaultError::Corrupted("no secure randomness available".into()))?;
+    Ok(bytes)
+}
+
+/// Reads the vault key through libsecret's `secret-tool`.
+#[cfg(target_os = "linux")]
+fn keychain_get(_vault_path: &Path) -> Result<String, VaultError> {
+    let out = std::process::Command::new("secret-tool")
+        .args([
+            "lookup",
+            "service",
+            KEYCHAIN_SERVICE,
+            "account",
+            KEYCHAIN_ACCOUNT,
+        ])
+        .output()
+        .map_err(|e| VaultError::Keychain(e.to_string()))?;
+    if !out.status.success() || out.stdout.is_empty() {
+        return Err(VaultError::Keychain("no key stored".into()));
+    }
+    Ok(String::from_utf8_lossy(&out.stdout).to_string())
+}
+
+/// Stores the vault key through libsecret's `secret-tool`, which reads the
+/// secret from stdin.
+#[cfg(target_os = "linux")]
+fn keychain_set(_vault_path: &Path, secret: &str) -> Result<(), VaultError> {
+    let mut cmd = std::process::Command::new("secret-tool");
+    cmd.args([
+        "store",
+        "--label=WordForge Desktop",
+        "service",
+        KEYCHAIN_SERVICE,
+        "account",
+        KEYCHAIN_ACCOUNT,
+    ]);
+    let out = run_with_stdin(cmd, secret)?;
+    if !out.status.success() {
+        return Err(VaultError::Keychain("secret-tool store failed".into()));
+    }
+    Ok(())
+}
+
+#[cfg(target_os = "macos")]
+fn keychain_get(_vault_path: &Path) -> Result<String, VaultError> {
+    let out = std::process::Command::new("security")
+        .args([
+            "find-generic-password",
+            "-s",
+            KEYCHAIN_SERVICE,
+            "-a",
+            KEYCHAIN_ACCOUNT,
+            "-w",
+        ])
+        .output()
+        .map_err(|e| VaultError::Keychain(e.to_string()))?;
+    if !out.status.success() {
+        return Err(VaultError::Keychain("no key stored".into()));
+    }
+    Ok(String::from_utf8_lossy(&out.stdout).to_string())
+}
+
+/// Stores the vault key with `security -i`, which reads its commands from
+/// stdin, so the secret never shows up in the process list. The key is
+/// base64 and needs no quoting.
+#[cfg(target_os = "macos")]
+fn keychain_set(_vault_path: &Path, secret: &str) -> Result<(), VaultError> {
+    let mut cmd = std::process::Command::new("security");
+    cmd.arg("-i");
+    let command = format!(
+        "add-generic-password -U -s {} -a {} -w {}\n",
+        KEYCHAIN_SERVICE, KEYCHAIN_ACCOUNT, secret
+    );
+    let out = run_with_stdin(cmd, &command)?;
+    // `security -i` exits 0 even when a command fails, but reports it.
+    if !out.status.success() || !out.stderr.is_empty() {
+        return Err(VaultError::Keychain(format!(
+            "security add-generic-password failed: {}",
+            String::from_utf8_lossy(&out.stderr).trim()
+        )));
+    }
+    Ok(())
+}
+
+/// Reads the vault key, protected with DPAPI for the current user, from
+/// `.vault.key` next to the vault.
+#[cfg(windows)]
+fn keychain_get(vault_path: &Path) -> Result<String, VaultError> {
+    let protected = match std::fs::read_to_string(vault_path.with_file_name(PROTECTED_KEY_FILE)) {
+        Ok(protected) => protected,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            return Err(VaultError::Keychain("no key stored".into()))
+        }
+        Err(e) => return Err(e.into()),
+    };
+    let out = run_with_stdin(
+        dpapi_command("[Convert]::ToBase64String([Security.Cryptography.ProtectedData]::Unprotect([Convert]::FromBase64String($data), $null, 'CurrentUser'))"),
+        protected.trim(),
+    )?;
+    if !out.status.success() || out.stdout.is_empty() {
+        return Err(VaultError::Keychain(
+            "DPAPI could not unprotect the vault key".into(),
+        ));
+    }
+    Ok(String::from_utf8_lossy(&out.stdout).trim().to_string())
+}
+
+/// Protects the vault key with DPAPI for the current user and stores the
+/// result in `.vault.key` next to the vault.
+#[cfg(windows)]
+fn keychain_set(vault_path: &Path, secret: &str) -> Result<(), VaultError> {
+    let out = run_with_stdin(
+        dpapi_command("[Convert]::ToBase64String([Security.Cryptography.ProtectedData]::Protect([Convert]::FromBase64String($data), $null, 'CurrentUser'))"),
+        secret,
+    )?;
+    if !out.status.success() || out.stdout.is_empty() {
+        return Err(VaultError::Keychain(
+            "DPAPI could not protect the vault key".into(),
+        ));
+    }
+    persist::write_atomic(
+        &vault_path.with_file_name(PROTECTED_KEY_FILE),
+        String::from_utf8_lossy(&out.stdout).trim().as_bytes(),
+    )?;
+    Ok(())
+}
+
+/// PowerShell running `script` with the first line of stdin in `$data`.
+#[cfg(windows)]
+fn dpapi_command(script: &str) -> std::process::Command {
+    use std::os::windows::process::CommandExt;
+    const CREATE_NO_WINDOW: u32 = 0x0800_0000;
+
+    let mut cmd = std::process::Command::new("powershell");
+    cmd.creation_flags(CREATE_NO_WINDOW).args([
+        "-NoProfile",
+        "-NonInteractive",
+        "-Command",
+        &format!(
+            "Add-Type -AssemblyName System.Security; $data = [Console]::In.ReadLine(); {}",
+            script
+        ),
+    ]);
+    cmd
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos", windows)))]
+fn keychain_get(_vault_path: &Path) -> Result<String, VaultError> {
+    Err(VaultError::Keychain(
+        "not supported on this platform".into(),
+    ))
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos", windows)))]
+fn keychain_set(_vault_path: &Path, _secret: &str) -> Result<(), VaultError> {
+    Err(VaultError::Keychain(
+        "not supported on this platform".into(),
+    ))
+}
+
+/// Runs `cmd` with `input` on stdin and collects its output.
+#[cfg(any(target_os = "linux", target_os = "macos", windows))]
+fn run_with_stdin(
+    mut cmd: std::process::Command,
+    input: &str,
+) -> Result<std::process::Output, VaultError> {
+    use std::io::Write;
+    use std::process::Stdio;
+
+    let mut child = cmd
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| VaultError::Keychain(e.to_string()))?;
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin.write_all(input.as_bytes())?;
+    }
+    Ok(child.wait_with_output()?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_dir() -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("wordforge-vault-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn credentials(auth: &str) -> SiteCredentials {
+        SiteCredentials {
+            app_password: format!("{}-password", auth),
+            auth: auth.to_string(),
+        }
+    }
+
+    #[test]
+    fn derives_pbkdf2_sha256_keys() {
+        // RFC 7914, section 11: PBKDF2-HMAC-SHA256 with P="passwd", S="salt", c=1.
+        let key = derive_key("passwd", b"salt", 1);
+        assert_eq!(
+            hex::encode(key),
+            "55ac046e56e3089fec1691c22544b605f94185216dde0465e68b9d57c20dacbc"
+        );
+        assert_ne!(derive_key("passwd", b"pepper", 1), key);
+        assert_ne!(derive_key("passwd", b"salt", 2), key);
+    }
+
+    #[test]
+    fn passphrase_vault_round_trips() {
+        let dir = temp_dir();
+        let mut vault = Vault::open(&dir);
+        assert_eq!(vault.status(), VaultStatus::Uninitialized);
+        vault.set_passphrase(Some("correct horse")).unwrap();
+        vault.set("site", credentials("secret")).unwrap();
+
+        let on_disk = std::fs::read_to_string(dir.join(VAULT_FILE)).unwrap();
+        assert!(!on_disk.contains("secret"));
+
+        let mut reopened = Vault::open(&dir);
+        assert_eq!(reopened.status(), VaultStatus::Locked);
+        assert!(matches!(
+            reopened.set("other", credentials("x")),
+            Err(VaultError::Locked)
+        ));
+        assert!(matches!(
+            reopened.unlock("wrong"),
+            Err(VaultError::WrongPassphrase)
+        ));
+        reopened.unlock("correct horse").unwrap();
+        assert_eq!(reopened.status(), VaultStatus::Unlocked);
+        assert_eq!(
+            reopened.get("site").map(|c| c.auth.as_str()),
+            Some("secret")
+        );
+
+        reopened.lock();
+        assert_eq!(reopened.status(), VaultStatus::Locked);
+        assert!(reopened.get("site").is_none());
+
+        std::fs::remove_dir_all(dir).ok();
+    }
+
+    #[test]
+    fn rejects_tampered_ciphertext() {
+        let dir = temp_dir();
+        let mut vault = Vault::open(&dir);
+        vault.set_passphrase(Some("passphrase")).unwrap();
+        vault.set("site", credentials("secret")).unwrap();
+
+        let path = dir.join(VAULT_FILE);
+        let mut file: VaultFile =
+            serde_json::from_str(&std::fs::read_to_string(&path).unwrap()).unwrap();
+        let mut data = BASE64.decode(&file.ciphertext).unwrap();
+        data[0] ^= 1;
+        file.ciphertext = BASE64.encode(data);
+        std::fs::write(&path, serde_json::to_string(&file).unwrap()).unwrap();
+
+        let mut reopened = Vault::open(&dir);
+        assert!(matches!(
+            reopened.unlock("passphrase"),
+            Err(VaultError::WrongPassphrase)
+        ));
+
+        std::fs::remove_dir_all(dir).ok();
+    }
+
+    #[test]
+    fn quarantines_unparsable_vault() {
+        let dir = temp_dir();
+        std::fs::write(dir.join(VAULT_FILE), "{ not json").unwrap();
+
+        let mut vault = Vault::open(&dir);
+        assert_eq!(vault.status(), VaultStatus::Uninitialized);
+        let quarantined = vault.take_quarantined().expect("vault file moved aside");
+        assert_eq!(std::fs::read_to_string(quarantined).unwrap(), "{ not json");
+        assert!(!dir.join(VAULT_FILE).exists());
+
+        std::fs::remove_dir_all(dir).ok();
+    }
+}
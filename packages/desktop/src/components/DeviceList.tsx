@@ -0,0 +1,180 @@
+import { Check, Laptop, Pencil, Trash2, X } from 'lucide-react';
+import { useState } from 'react';
+import { useSiteDevices } from '../hooks/useSiteDevices';
+import type { SiteDevice } from '../types';
+import styles from './DeviceList.module.css';
+import {
+  Badge,
+  IconButton,
+  Input,
+  Skeleton,
+  Table,
+  TableBody,
+  TableCell,
+  TableHead,
+  TableHeader,
+  TableRow,
+} from './ui';
+
+interface DeviceListProps {
+  siteId: string;
+}
+
+function formatLastSeen(timestamp: number | null): string {
+  if (!timestamp) return 'Never';
+  const diff = Date.now() / 1000 - timestamp;
+
+  if (diff < 60) return 'just now';
+  if (diff < 3600) return `${Math.floor(diff / 60)}m ago`;
+  if (diff < 86400) return `${Math.floor(diff / 3600)}h ago`;
+  if (diff < 604800) return `${Math.floor(diff / 86400)}d ago`;
+  return new Date(timestamp * 1000).toLocaleDateString();
+}
+
+function DeviceName({
+  device,
+  onRename,
+  isRenaming,
+}: {
+  device: SiteDevice;
+  onRename: (name: string) => void;
+  isRenaming: boolean;
+}) {
+  const [draft, setDraft] = useState<string | null>(null);
+  const label = device.name || device.device_id.slice(0, 8);
+
+  if (draft === null) {
+    return (
+      <div className={styles.name}>
+        <span>{label}</span>
+        {device.current && <Badge variant="primary">This device</Badge>}
+        {device.current && (
+          <IconButton
+            aria-label="Rename this device"
+            onClick={() => setDraft(device.name ?? '')}
+          >
+            <Pencil size={12} />
+          </IconButton>
+        )}
+      </div>
+    );
+  }
+
+  const save = () => {
+    if (draft.trim()) onRename(draft.trim());
+    setDraft(null);
+  };
+
+  return (
+    <div className={styles.name}>
+      <Input
+        value={draft}
+        autoFocus
+        onChange={(e) => setDraft(e.target.value)}
+        onKeyDown={(e) => {
+          if (e.key === 'Enter') save();
+          if (e.key === 'Escape') setDraft(null);
+        }}
+      />
+      <IconButton
+        aria-label="Save name"
+        disabled={isRenaming || !draft.trim()}
+        onClick={save}
+      >
+        <Check size={12} />
+      </IconButton>
+      <IconButton aria-label="Cancel" onClick={() => setDraft(null)}>
+        <X size={12} />
+      </IconButton>
+    </div>
+  );
+}
+
+export function DeviceList({ siteId }: DeviceListProps) {
+  const {
+    devices,
+    isLoading,
+    error,
+    rename,
+    isRenaming,
+    renameError,
+    revoke,
+    isRevoking,
+    revokeError,
+  } = useSiteDevices(siteId);
+
+  const handleRevoke = (device: SiteDevice) => {
+    const label = device.name || device.device_id;
+    if (
+      window.confirm(
+        `Revoke ${label}? It will lose access to this site until it connects again.`,
+      )
+    ) {
+      revoke(device.device_id);
+    }
+  };
+
+  if (isLoading) {
+    return <Skeleton height={120} />;
+  }
+
+  const actionError = error || renameError || revokeError;
+
+  return (
+    <>
+      {actionError && <p className={styles.error}>{actionError}</p>}
+      {devices.length === 0 && !error ? (
+        <p className={styles.empty}>
+          <Laptop size={14} /> No devices have registered with this site yet.
+        </p>
+      ) : (
+        <Table>
+          <TableHeader>
+            <TableRow>
+              <TableHead>Name</TableHead>
+              <TableHead>Platform</TableHead>
+              <TableHead>Last port</TableHead>
+              <TableHead>Last seen</TableHead>
+              <TableHead>Status</TableHead>
+              <TableHead />
+            </TableRow>
+          </TableHeader>
+          <TableBody>
+            {devices.map((device) => (
+              <TableRow key={device.device_id}>
+                <TableCell>
+                  <DeviceName
+                    device={device}
+                    onRename={rename}
+                    isRenaming={isRenaming}
+                  />
+                </TableCell>
+                <TableCell>{device.platform || '?'}</TableCell>
+                <TableCell>{device.port ?? '-'}</TableCell>
+                <TableCell>{formatLastSeen(device.last_seen)}</TableCell>
+                <TableCell>
+                  {device.online ? (
+                    <Badge variant="success">Online</Badge>
+                  ) : (
+                    <Badge variant="default">Offline</Badge>
+                  )}
+                </TableCell>
+                <TableCell>
+                  {!device.current && (
+                    <IconButton
+                      aria-label="Revoke device"
+                                disabled={isRevoking}
+                      onClick={() => handleRevoke(device)}
+                    >
+                      <Trash2 size={12} />
+                    </IconButton>
+                  )}
+                </TableCell>
+              </TableRow>
+            ))}
+          </TableBody>
+        </Table>
+      )}
+    </>
+  );
+}
@@ -0,0 +1,44 @@
+import { KeyRound } from 'lucide-react';
+import { useState } from 'react';
+import { useSiteMutations, useSitesList } from '../hooks/useSites';
+import styles from './ReauthBanner.module.css';
+import { Button } from './ui';
+
+interface ReauthBannerProps {
+  siteId: string;
+}
+
+export function ReauthBanner({ siteId }: ReauthBannerProps) {
+  const { sites } = useSitesList();
+  const { repairSite } = useSiteMutations();
+  const [error, setError] = useState<string | null>(null);
+
+  const site = sites.find((s) => s.id === siteId);
+  if (!site?.needs_reauth) {
+    return null;
+  }
+
+  const handleRepair = async () => {
+    setError(null);
+    try {
+      await repairSite(site.id);
+    } catch (e) {
+      setError(e instanceof Error ? e.message : String(e));
+    }
+  };
+
+  return (
+    <div className={styles.banner}>
+      <div className={styles.content}>
+        <KeyRound size={16} className={styles.icon} />
+        <span className={styles.message}>
+          {error ||
+            `${site.name} rejected the stored credentials. Pair it again to keep syncing.`}
+        </span>
+      </div>
+      <Button size="sm" variant="primary" onClick={handleRepair}>
+        Re-pair
+      </Button>
+    </div>
+  );
+}
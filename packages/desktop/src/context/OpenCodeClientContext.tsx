@@ -65,14 +65,14 @@ export function OpenCodeProvider({ site, children }: OpenCodeProviderProps) {
 
     const openInWebview = async (sessionId?: string): Promise<void> => {
       const url = buildUrl(sessionId);
-      if (!url) {
+      if (!site || !url) {
         throw new Error('Cannot open webview without a project directory');
       }
-      await invoke('open_opencode_view', { url });
+      await invoke('open_opencode_view', { siteId: site.id, url });
     };
 
     return { client, port, projectDir, buildUrl, openInWebview };
-  }, [status, port, site?.project_dir]);
+  }, [status, port, site]);
 
   return (
     <OpenCodeClientContext.Provider value={value}>
@@ -3,30 +3,42 @@ import { invoke } from '@tauri-apps/api/core';
 import { listen } from '@tauri-apps/api/event';
 import { useCallback, useEffect, useState } from 'react';
 import { useRestartRequired } from '../context/RestartContext';
-import type { ConfigSyncStatus } from '../types';
+import type {
+  ConfigRefreshPlan,
+  ConfigSyncStatus,
+  ConflictResolution,
+} from '../types';
 import { useOpenCodeStatus } from './useOpenCode';
 
 const CONFIG_CHECK_INTERVAL = 5 * 60 * 1000;
 
 const configSyncKeys = {
   all: ['configSync'] as const,
-  status: (siteId?: string) =>
+  status: (siteId: string) =>
     [...configSyncKeys.all, 'status', siteId] as const,
 };
 
-async function checkConfigUpdate(siteId?: string): Promise<ConfigSyncStatus> {
+async function checkConfigUpdate(siteId: string): Promise<ConfigSyncStatus> {
   return invoke<ConfigSyncStatus>('check_config_update', { siteId });
 }
 
-async function refreshConfig(siteId?: string): Promise<string> {
+async function previewRefresh(siteId: string): Promise<ConfigRefreshPlan> {
+  return invoke<ConfigRefreshPlan>('preview_site_config_refresh', { siteId });
+}
+
+async function refreshConfig(
+  siteId: string,
+  resolutions?: Record<string, ConflictResolution>,
+): Promise<string> {
   return invoke<string>('refresh_site_config', {
     siteId,
     restartOpencode: false,
+    resolutions,
   });
 }
 
 export interface UseConfigSyncOptions {
-  siteId?: string;
+  siteId: string;
   enabled?: boolean;
   pollingInterval?: number;
 }
@@ -38,12 +50,15 @@ export interface UseConfigSyncReturn {
   updateAvailable: boolean;
   error: string | null;
   checkNow: () => Promise<void>;
-  applyUpdate: () => Promise<string>;
+  previewUpdate: () => Promise<ConfigRefreshPlan>;
+  applyUpdate: (
+    resolutions?: Record<string, ConflictResolution>,
+  ) => Promise<string>;
   lastChecked: Date | null;
 }
 
 export function useConfigSync(
-  options: UseConfigSyncOptions = {},
+  options: UseConfigSyncOptions,
 ): UseConfigSyncReturn {
   const {
     siteId,
@@ -69,9 +84,9 @@ export function useConfigSync(
   });
 
   const refreshMutation = useMutation({
-    mutationFn: async () => {
+    mutationFn: async (resolutions?: Record<string, ConflictResolution>) => {
       setMutationError(null);
-      return refreshConfig(siteId);
+      return refreshConfig(siteId, resolutions);
     },
     onSuccess: () => {
       queryClient.invalidateQueries({ queryKey: configSyncKeys.all });
@@ -85,18 +100,30 @@ export function useConfigSync(
   });
 
   useEffect(() => {
-    const unlistenPromise = listen<string>('config:updated', () => {
-      queryClient.invalidateQueries({ queryKey: configSyncKeys.all });
-    });
+    const unlistenPromises = ['config:updated', 'config:drift'].map((event) =>
+      listen(event, () => {
+        queryClient.invalidateQueries({ queryKey: configSyncKeys.all });
+      }),
+    );
 
     return () => {
-      unlistenPromise.then((fn) => fn());
+      for (const unlistenPromise of unlistenPromises) {
+        unlistenPromise.then((fn) => fn());
+      }
     };
   }, [queryClient]);
 
-  const applyUpdate = useCallback(async () => {
-    return refreshMutation.mutateAsync();
-  }, [refreshMutation]);
+  const previewUpdate = useCallback(
+    async () => previewRefresh(siteId),
+    [siteId],
+  );
+
+  const applyUpdate = useCallback(
+    async (resolutions?: Record<string, ConflictResolution>) => {
+      return refreshMutation.mutateAsync(resolutions);
+    },
+    [refreshMutation],
+  );
 
   const status = statusQuery.data ?? null;
   const lastChecked = status?.last_checked
@@ -112,6 +139,7 @@ export function useConfigSync(
     checkNow: async () => {
       await statusQuery.refetch();
     },
+    previewUpdate,
     applyUpdate,
     lastChecked,
   };
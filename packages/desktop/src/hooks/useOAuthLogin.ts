@@ -11,6 +11,7 @@ import {
 } from '../lib/oauth-providers';
 import { useGlobalConfig, useSetGlobalConfig } from './useGlobalConfig';
 import { useOpenCode } from './useOpenCode';
+import { useActiveSite } from './useSites';
 
 export type OAuthLoginState =
   | { status: 'idle' }
@@ -49,6 +50,7 @@ export function useOAuthLogin(): UseOAuthLoginReturn {
   const { data: config } = useGlobalConfig();
   const { mutateAsync: setConfig } = useSetGlobalConfig();
   const { status: serverStatus, start, stop } = useOpenCode();
+  const { activeSite } = useActiveSite();
   const { clearRestartRequired } = useRestartRequired();
   const clientRef = useRef<OpenCodeClient | null>(null);
 
@@ -65,7 +67,9 @@ export function useOAuthLogin(): UseOAuthLoginReturn {
       return clientRef.current;
     }
 
-    const port = await invoke<number | null>('get_opencode_port');
+    const port = await invoke<number | null>('get_opencode_port', {
+      siteId: activeSite?.id,
+    });
     if (!port) {
       throw new Error('OpenCode server port not available');
     }
@@ -75,7 +79,7 @@ export function useOAuthLogin(): UseOAuthLoginReturn {
     });
 
     return clientRef.current;
-  }, []);
+  }, [activeSite?.id]);
 
   const waitForServer = useCallback(
     async (maxWaitMs = 30_000): Promise<boolean> => {
@@ -2,6 +2,7 @@ import { useMutation, useQuery, useQueryClient } from '@tanstack/react-query';
 import { invoke } from '@tauri-apps/api/core';
 import { listen } from '@tauri-apps/api/event';
 import { useCallback, useEffect, useState } from 'react';
+import { useActiveSite } from './useSites';
 
 export type OpenCodeStatus =
   | 'not_installed'
@@ -13,6 +14,10 @@ export type OpenCodeStatus =
 interface DownloadProgress {
   message: string;
   percent: number;
+  downloaded_bytes?: number;
+  total_bytes?: number | null;
+  bytes_per_second?: number;
+  eta_secs?: number | null;
 }
 
 interface OpenCodeState {
@@ -25,18 +30,19 @@ interface OpenCodeState {
 
 const openCodeKeys = {
   all: ['opencode'] as const,
-  status: () => [...openCodeKeys.all, 'status'] as const,
+  status: (siteId?: string) =>
+    [...openCodeKeys.all, 'status', siteId] as const,
 };
 
-async function fetchOpenCodeState(): Promise<OpenCodeState> {
+async function fetchOpenCodeState(siteId?: string): Promise<OpenCodeState> {
   const [status, installedVersion, latestVersion, port, updateAvailable] =
     await Promise.all([
-      invoke<OpenCodeStatus>('get_status').catch(
+      invoke<OpenCodeStatus>('get_status', { siteId }).catch(
         () => 'stopped' as OpenCodeStatus,
       ),
       invoke<string | null>('get_installed_version').catch(() => null),
       invoke<string>('get_latest_version').catch(() => null),
-      invoke<number | null>('get_opencode_port').catch(() => null),
+      invoke<number | null>('get_opencode_port', { siteId }).catch(() => null),
       invoke<boolean>('check_update_available').catch(() => false),
     ]);
 
@@ -44,9 +50,11 @@ async function fetchOpenCodeState(): Promise<OpenCodeState> {
 }
 
 export function useOpenCodeStatus() {
+  const { activeSite } = useActiveSite();
+  const siteId = activeSite?.id;
   const stateQuery = useQuery({
-    queryKey: openCodeKeys.status(),
-    queryFn: fetchOpenCodeState,
+    queryKey: openCodeKeys.status(siteId),
+    queryFn: () => fetchOpenCodeState(siteId),
     refetchInterval: 5000,
   });
 
@@ -62,8 +70,16 @@ export function useOpenCodeStatus() {
 
 export function useOpenCodeActions() {
   const queryClient = useQueryClient();
+  const { activeSite } = useActiveSite();
   const [mutationError, setMutationError] = useState<string | null>(null);
 
+  const requireSiteId = () => {
+    if (!activeSite) {
+      throw new Error('No site selected');
+    }
+    return activeSite.id;
+  };
+
   const invalidate = useCallback(() => {
     queryClient.invalidateQueries({ queryKey: openCodeKeys.all });
   }, [queryClient]);
@@ -71,7 +87,7 @@ export function useOpenCodeActions() {
   const startMutation = useMutation({
     mutationFn: async () => {
       setMutationError(null);
-      return invoke<number>('start_opencode');
+      return invoke<number>('start_opencode', { siteId: requireSiteId() });
     },
     onSuccess: invalidate,
     onError: (error) => {
@@ -83,7 +99,7 @@ export function useOpenCodeActions() {
   const stopMutation = useMutation({
     mutationFn: async () => {
       setMutationError(null);
-      await invoke('stop_opencode');
+      await invoke('stop_opencode', { siteId: requireSiteId() });
     },
     onSuccess: invalidate,
     onError: (error) => {
@@ -94,7 +110,7 @@ export function useOpenCodeActions() {
 
   const openViewMutation = useMutation({
     mutationFn: async () => {
-      await invoke('open_opencode_view');
+      await invoke('open_opencode_view', { siteId: requireSiteId() });
     },
     onError: (error) => {
       setMutationError(error instanceof Error ? error.message : String(error));
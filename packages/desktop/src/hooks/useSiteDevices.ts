@@ -0,0 +1,44 @@
+import { useMutation, useQuery, useQueryClient } from '@tanstack/react-query';
+import { invoke } from '@tauri-apps/api/core';
+import type { SiteDevice } from '../types';
+
+const deviceKeys = {
+  all: ['site-devices'] as const,
+  site: (siteId: string) => [...deviceKeys.all, siteId] as const,
+};
+
+export function useSiteDevices(siteId: string) {
+  const queryClient = useQueryClient();
+
+  const devicesQuery = useQuery({
+    queryKey: deviceKeys.site(siteId),
+    queryFn: () => invoke<SiteDevice[]>('list_site_devices', { siteId }),
+  });
+
+  const setDevices = (devices: SiteDevice[]) =>
+    queryClient.setQueryData(deviceKeys.site(siteId), devices);
+
+  const renameMutation = useMutation({
+    mutationFn: (name: string) =>
+      invoke<SiteDevice[]>('rename_device', { siteId, name }),
+    onSuccess: setDevices,
+  });
+
+  const revokeMutation = useMutation({
+    mutationFn: (deviceId: string) =>
+      invoke<SiteDevice[]>('revoke_site_device', { siteId, deviceId }),
+    onSuccess: setDevices,
+  });
+
+  return {
+    devices: devicesQuery.data ?? [],
+    isLoading: devicesQuery.isLoading,
+    error: devicesQuery.error?.message ?? null,
+    rename: renameMutation.mutate,
+    isRenaming: renameMutation.isPending,
+    renameError: renameMutation.error?.message ?? null,
+    revoke: revokeMutation.mutate,
+    isRevoking: revokeMutation.isPending,
+    revokeError: revokeMutation.error?.message ?? null,
+  };
+}
@@ -1,5 +1,8 @@
 import { useMutation, useQuery, useQueryClient } from '@tanstack/react-query';
 import { invoke } from '@tauri-apps/api/core';
+import { listen } from '@tauri-apps/api/event';
+import { openUrl } from '@tauri-apps/plugin-opener';
+import { useEffect } from 'react';
 import type { WordPressSite } from '../types';
 
 const siteKeys = {
@@ -41,8 +44,45 @@ export function useSiteMutations() {
     mutationFn: async ({
       siteUrl,
       token,
-    }: { siteUrl: string; token: string }) => {
-      return invoke<WordPressSite>('connect_site', { siteUrl, token });
+      reauthenticate = false,
+    }: { siteUrl: string; token: string; reauthenticate?: boolean }) => {
+      const existing = await invoke<WordPressSite | null>('find_site_by_url', {
+        siteUrl,
+      });
+
+      // A re-pairing link only ever updates the site that asked for it; the
+      // backend refuses it for any site not waiting to be paired again.
+      if (reauthenticate) {
+        if (!existing) {
+          throw new Error(`No connected site matches ${siteUrl}`);
+        }
+        if (
+          !window.confirm(
+            `Replace the stored credentials of ${existing.name} (${existing.url}) with the ones from this link?`,
+          )
+        ) {
+          return existing;
+        }
+        return invoke<WordPressSite>('repair_site', {
+          siteId: existing.id,
+          siteUrl,
+          token,
+        });
+      }
+
+      if (
+        existing &&
+        !window.confirm(
+          `${existing.name} is already connected. Re-authenticate it instead of adding it again?`,
+        )
+      ) {
+        return existing;
+      }
+      return invoke<WordPressSite>('connect_site', {
+        siteUrl,
+        token,
+        reauthenticate: existing !== null,
+      });
     },
     onSuccess: () => {
       queryClient.invalidateQueries({ queryKey: siteKeys.all });
@@ -71,6 +111,13 @@ export function useSiteMutations() {
     await invoke('open_site_folder', { id });
   };
 
+  // The site's settings page issues a fresh token and sends it back through
+  // the connect deep link, which updates this site in place.
+  const repairSite = async (id: string) => {
+    const url = await invoke<string>('get_site_pairing_url', { siteId: id });
+    await openUrl(url);
+  };
+
   return {
     connectSite: connectMutation.mutateAsync,
     isConnecting: connectMutation.isPending,
@@ -83,9 +130,24 @@ export function useSiteMutations() {
     isRemoving: removeMutation.isPending,
 
     openSiteFolder,
+    repairSite,
   };
 }
 
+export function useSiteReauthEvents() {
+  const queryClient = useQueryClient();
+
+  useEffect(() => {
+    const unlistenPromise = listen('site:needs-reauth', () => {
+      queryClient.invalidateQueries({ queryKey: siteKeys.all });
+    });
+
+    return () => {
+      unlistenPromise.then((unlisten) => unlisten());
+    };
+  }, [queryClient]);
+}
+
 export function useSiteInvalidation() {
   const queryClient = useQueryClient();
 
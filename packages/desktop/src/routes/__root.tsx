@@ -13,6 +13,7 @@ import { useOpenCodeStatus } from '../hooks/useOpenCode';
 import {
   useActiveSite,
   useSiteMutations,
+  useSiteReauthEvents,
   useSitesList,
 } from '../hooks/useSites';
 import '../styles/variables.css';
@@ -41,11 +42,14 @@ function RootLayoutInner() {
   const { status, port, installedVersion } = useOpenCodeStatus();
   const { navItems } = useSidebarNavItems();
 
+  useSiteReauthEvents();
+
   useDeepLink(async (payload) => {
     try {
       const site = await connectSite({
-        siteUrl: payload.site,
+        siteUrl: payload.site_url,
         token: payload.token,
+        reauthenticate: payload.reauthenticate,
       });
       navigate({ to: '/site/$siteId', params: { siteId: site.id } });
     } catch (e) {
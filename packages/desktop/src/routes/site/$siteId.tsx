@@ -1,6 +1,7 @@
 import { Outlet, createFileRoute, redirect } from '@tanstack/react-router';
 import { invoke } from '@tauri-apps/api/core';
 import { useEffect } from 'react';
+import { ReauthBanner } from '../../components/ReauthBanner';
 import { createSiteNavItems } from '../../components/ui';
 import { OpenCodeProvider } from '../../context/OpenCodeClientContext';
 import { useSidebarNavItems } from '../../context/SidebarContext';
@@ -41,6 +42,7 @@ function SiteLayout() {
   return (
     <OpenCodeProvider site={site}>
       <div className={styles.content}>
+        <ReauthBanner siteId={site.id} />
         <Outlet />
       </div>
     </OpenCodeProvider>
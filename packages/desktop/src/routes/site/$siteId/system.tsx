@@ -3,11 +3,13 @@ import {
   Activity,
   FileText,
   Image,
+  Laptop,
   Layout,
   MessageSquare,
   Paintbrush,
   Puzzle,
 } from 'lucide-react';
+import { DeviceList } from '../../../components/DeviceList';
 import {
   Badge,
   Card,
@@ -119,6 +121,14 @@ function SystemPage() {
         )}
       </section>
 
+      <section className={styles.section}>
+        <div className={styles.sectionHeader}>
+          <Laptop size={14} />
+          <span>Devices</span>
+        </div>
+        <DeviceList siteId={site.id} />
+      </section>
+
       <section className={styles.section}>
         <div className={styles.sectionHeader}>
           <Layout size={14} />
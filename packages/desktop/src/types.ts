@@ -15,8 +15,33 @@ export interface WordPressSite {
   last_used_at: number;
   config_hash?: string;
   config_updated_at?: number;
+  idle_policy?: IdlePolicy;
+  config_drift?: ConfigDrift | null;
+  needs_reauth?: boolean;
 }
 
+export interface ConfigDrift {
+  remote_hash: string;
+  changed: string[];
+  unchanged: string[];
+  detected_at: number;
+}
+
+export interface ConfigDriftEvent extends ConfigDrift {
+  site_id: string;
+  summary: string;
+}
+
+export interface DriftSettings {
+  enabled: boolean;
+  interval_minutes: number;
+}
+
+export type IdlePolicy =
+  | { mode: 'timeout'; minutes: number }
+  | { mode: 'never' }
+  | { mode: 'no_window_open'; minutes: number };
+
 export interface ConfigSyncStatus {
   update_available: boolean;
   current_hash: string | null;
@@ -24,11 +49,40 @@ export interface ConfigSyncStatus {
   last_checked: number | null;
 }
 
+export type ConflictResolution = 'keep_local' | 'use_remote';
+
+export interface FileConflict {
+  path: string;
+  kind: 'modified' | 'removed';
+  diff: string | null;
+}
+
+export interface SiteConfigSnapshot {
+  id: string;
+  created_at: number;
+  config_hash: string | null;
+  hash_response: {
+    hash: string;
+    components: Record<string, string | boolean>;
+    generated: number;
+  } | null;
+  files: number;
+}
+
+export interface ConfigRefreshPlan {
+  added: string[];
+  updated: string[];
+  deleted: string[];
+  kept_local: string[];
+  conflicts: FileConflict[];
+}
+
 export interface DeepLinkPayload {
   url: string;
-  site: string;
+  site_url: string;
   token: string;
   name: string;
+  reauthenticate: boolean;
 }
 
 // WordPress REST API response types
@@ -203,3 +257,38 @@ export interface OpenCodePlugin {
     [key: string]: ProviderConfig;
   };
 }
+
+export interface HealthCheck {
+  name:
+    | 'dns'
+    | 'http'
+    | 'rest'
+    | 'auth'
+    | 'mcp'
+    | 'abilities'
+    | 'config_hash'
+    | 'local_settings';
+  target: string;
+  status: 'pass' | 'fail' | 'skipped';
+  latency_ms: number | null;
+  http_status: number | null;
+  cause: string | null;
+}
+
+export interface SiteHealthReport {
+  site_id: string;
+  checked_at: number;
+  healthy: boolean;
+  checks: HealthCheck[];
+}
+
+export interface SiteDevice {
+  device_id: string;
+  name: string | null;
+  platform: string | null;
+  port: number | null;
+  enabled: boolean;
+  online: boolean;
+  last_seen: number | null;
+  current: boolean;
+}
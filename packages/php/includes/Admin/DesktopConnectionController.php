@@ -6,6 +6,7 @@ namespace WordForge\Admin;
 use WordForge\OpenCode\AgentConfig;
 use WordForge\OpenCode\ConfigChangeDetector;
 use WordForge\OpenCode\ContextProvider;
+use WordForge\OpenCode\LocalServerConfig;
 use WordForge\OpenCode\ProviderConfig;
 use WP_REST_Request;
 use WP_REST_Response;
@@ -28,6 +29,13 @@ class DesktopConnectionController {
 				'methods'             => 'POST',
 				'callback'            => array( $this, 'generate_connect_token' ),
 				'permission_callback' => array( $this, 'check_admin_permission' ),
+				'args'                => array(
+					'reauth' => array(
+						'required' => false,
+						'type'     => 'boolean',
+						'default'  => false,
+					),
+				),
 			)
 		);
 
@@ -77,6 +85,57 @@ class DesktopConnectionController {
 				'permission_callback' => array( $this, 'check_app_password_permission' ),
 			)
 		);
+
+		register_rest_route(
+			self::NAMESPACE,
+			'/desktop/devices',
+			array(
+				'methods'             => 'GET',
+				'callback'            => array( $this, 'list_devices' ),
+				'permission_callback' => array( $this, 'check_app_password_permission' ),
+			)
+		);
+
+		register_rest_route(
+			self::NAMESPACE,
+			'/desktop/devices/(?P<device_id>[A-Za-z0-9_-]+)',
+			array(
+				array(
+					'methods'             => 'POST',
+					'callback'            => array( $this, 'rename_device' ),
+					'permission_callback' => array( $this, 'check_app_password_permission' ),
+					'args'                => array(
+						'name' => array(
+							'required'          => true,
+							'type'              => 'string',
+							'sanitize_callback' => 'sanitize_text_field',
+						),
+					),
+				),
+				array(
+					'methods'             => 'DELETE',
+					'callback'            => array( $this, 'revoke_device' ),
+					'permission_callback' => array( $this, 'check_app_password_permission' ),
+				),
+			)
+		);
+
+		register_rest_route(
+			self::NAMESPACE,
+			'/desktop/disconnect',
+			array(
+				'methods'             => 'POST',
+				'callback'            => array( $this, 'disconnect' ),
+				'permission_callback' => array( $this, 'check_app_password_permission' ),
+				'args'                => array(
+					'device_id' => array(
+						'required'          => false,
+						'type'              => 'string',
+						'sanitize_callback' => 'sanitize_text_field',
+					),
+				),
+			)
+		);
 	}
 
 	public function check_admin_permission(): bool {
@@ -87,7 +146,11 @@ class DesktopConnectionController {
 		return is_user_logged_in() && current_user_can( 'manage_options' );
 	}
 
-	public function generate_connect_token(): WP_REST_Response {
+	/**
+	 * With `reauth`, the link tells the desktop app to replace the
+	 * credentials of a site it already has instead of adding a new one.
+	 */
+	public function generate_connect_token( ?WP_REST_Request $request = null ): WP_REST_Response {
 		$user = wp_get_current_user();
 		if ( ! $user || ! $user->ID ) {
 			return new WP_REST_Response(
@@ -106,7 +169,8 @@ class DesktopConnectionController {
 
 		set_transient( self::TRANSIENT_PREFIX . $token, $token_data, self::TOKEN_EXPIRY );
 
-		$connect_url = $this->build_connect_url( $token );
+		$reauth      = $request && (bool) $request->get_param( 'reauth' );
+		$connect_url = $this->build_connect_url( $token, $reauth );
 
 		return new WP_REST_Response(
 			array(
@@ -210,13 +274,17 @@ class DesktopConnectionController {
 		);
 	}
 
-	private function build_connect_url( string $token ): string {
+	private function build_connect_url( string $token, bool $reauth = false ): string {
 		$params = array(
 			'token' => $token,
 			'site'  => get_site_url(),
 			'name'  => rawurlencode( get_bloginfo( 'name' ) ),
 		);
 
+		if ( $reauth ) {
+			$params['reauth'] = '1';
+		}
+
 		return 'wordforge://connect?' . http_build_query( $params );
 	}
 
@@ -250,6 +318,107 @@ class DesktopConnectionController {
 		);
 	}
 
+	/**
+	 * Called by the desktop app when a site is removed: revokes the
+	 * application password the request was authenticated with and forgets
+	 * the device's local server settings.
+	 */
+	public function disconnect( WP_REST_Request $request ): WP_REST_Response {
+		$user_id = get_current_user_id();
+		$uuid    = rest_get_authenticated_app_password();
+
+		if ( ! $uuid ) {
+			return new WP_REST_Response(
+				array( 'error' => 'Disconnect must be authenticated with the application password to revoke' ),
+				400
+			);
+		}
+
+		$device_id      = $request->get_param( 'device_id' );
+		$device_removed = ! empty( $device_id ) && LocalServerConfig::remove_device( $device_id, $user_id );
+
+		$revoked = \WP_Application_Passwords::delete_application_password( $user_id, $uuid );
+		if ( is_wp_error( $revoked ) ) {
+			return new WP_REST_Response(
+				array( 'error' => $revoked->get_error_message() ),
+				500
+			);
+		}
+
+		return new WP_REST_Response(
+			array(
+				'success'        => true,
+				'revoked'        => true,
+				'device_removed' => $device_removed,
+			)
+		);
+	}
+
+	public function list_devices(): WP_REST_Response {
+		return new WP_REST_Response( array( 'devices' => LocalServerConfig::list_devices() ) );
+	}
+
+	public function rename_device( WP_REST_Request $request ): WP_REST_Response {
+		$device_id = $request->get_param( 'device_id' );
+		$name      = trim( (string) $request->get_param( 'name' ) );
+
+		if ( '' === $name ) {
+			return new WP_REST_Response(
+				array( 'error' => 'Name is required' ),
+				400
+			);
+		}
+
+		if ( ! LocalServerConfig::rename_device( $device_id, $name ) ) {
+			return new WP_REST_Response(
+				array( 'error' => 'Device not found' ),
+				404
+			);
+		}
+
+		return new WP_REST_Response( array( 'devices' => LocalServerConfig::list_devices() ) );
+	}
+
+	/**
+	 * Forgets another device and revokes the application password it used.
+	 * A device disconnects itself through `/desktop/disconnect` instead.
+	 */
+	public function revoke_device( WP_REST_Request $request ): WP_REST_Response {
+		$user_id   = get_current_user_id();
+		$device_id = $request->get_param( 'device_id' );
+		$device    = LocalServerConfig::get_device( $device_id, $user_id );
+
+		if ( null === $device ) {
+			return new WP_REST_Response(
+				array( 'error' => 'Device not found' ),
+				404
+			);
+		}
+
+		$uuid = $device['app_password_uuid'] ?? null;
+		if ( $uuid && rest_get_authenticated_app_password() === $uuid ) {
+			return new WP_REST_Response(
+				array( 'error' => 'A device cannot revoke itself; disconnect it instead' ),
+				400
+			);
+		}
+
+		$revoked = false;
+		if ( $uuid ) {
+			$revoked = true === \WP_Application_Passwords::delete_application_password( $user_id, $uuid );
+		}
+
+		LocalServerConfig::remove_device( $device_id, $user_id );
+
+		return new WP_REST_Response(
+			array(
+				'success' => true,
+				'revoked' => $revoked,
+				'devices' => LocalServerConfig::list_devices( $user_id ),
+			)
+		);
+	}
+
 	public function get_config_hash(): WP_REST_Response {
 		$hash_data = ConfigChangeDetector::get_config_hash();
 
@@ -920,6 +920,8 @@ class OpenCodeController {
 				'device_id'   => $settings['device_id'],
 				'project_id'  => $settings['project_id'],
 				'project_dir' => $settings['project_dir'],
+				'online'      => $settings['online'],
+				'last_seen'   => $settings['last_seen'],
 			)
 		);
 	}
@@ -930,9 +932,24 @@ class OpenCodeController {
 		$device_id   = $request->get_param( 'device_id' );
 		$project_id  = $request->get_param( 'project_id' );
 		$project_dir = $request->get_param( 'project_dir' );
+		$name        = $request->get_param( 'name' );
+		$platform    = $request->get_param( 'platform' );
 
 		$settings = array();
 
+		$app_password_uuid = rest_get_authenticated_app_password();
+		if ( $app_password_uuid ) {
+			$settings['app_password_uuid'] = $app_password_uuid;
+		}
+
+		if ( null !== $name ) {
+			$settings['name'] = sanitize_text_field( $name );
+		}
+
+		if ( null !== $platform ) {
+			$settings['platform'] = sanitize_text_field( $platform );
+		}
+
 		if ( null !== $port ) {
 			$settings['port'] = absint( $port );
 		}
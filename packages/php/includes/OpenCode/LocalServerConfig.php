@@ -173,19 +173,23 @@ class LocalServerConfig {
 
 	private const USER_META_KEY = 'wordforge_local_devices';
 	private const DEVICE_TTL    = 86400 * 7;
+	// The desktop app reports every 5 minutes; allow a couple of misses.
+	private const PRESENCE_TIMEOUT = 900;
 
 	public static function get_settings( ?int $user_id = null ): array {
 		$user_id = $user_id ?? \get_current_user_id();
-		$devices = self::get_user_devices( $user_id );
+		$devices = self::with_presence( self::get_user_devices( $user_id ) );
 		$latest  = self::get_latest_device( $devices );
 
 		return array(
 			'port'        => $latest['port'] ?? 4096,
-			'enabled'     => ! empty( $latest ),
+			'enabled'     => ! empty( $latest ) && $latest['enabled'] && $latest['online'],
 			'runtime'     => self::RUNTIME_NONE,
 			'device_id'   => $latest['device_id'] ?? null,
 			'project_id'  => $latest['project_id'] ?? null,
 			'project_dir' => $latest['project_dir'] ?? null,
+			'online'      => $latest['online'] ?? false,
+			'last_seen'   => $latest['last_seen'] ?? null,
 			'devices'     => $devices,
 		);
 	}
@@ -198,7 +202,10 @@ class LocalServerConfig {
 			return false;
 		}
 
-		$port = isset( $settings['port'] ) ? \absint( $settings['port'] ) : 4096;
+		$devices = self::get_user_devices( $user_id );
+
+		// A stop report carries no port; keep the last one for display.
+		$port = isset( $settings['port'] ) ? \absint( $settings['port'] ) : ( $devices[ $device_id ]['port'] ?? 4096 );
 		$port = max( 1024, min( 65535, $port ) );
 
 		$device_data = array(
@@ -207,6 +214,15 @@ class LocalServerConfig {
 			'last_seen' => time(),
 		);
 
+		// Identity fields are only sent now and then; carry them over.
+		foreach ( array( 'name', 'platform', 'app_password_uuid' ) as $key ) {
+			if ( isset( $settings[ $key ] ) ) {
+				$device_data[ $key ] = \sanitize_text_field( $settings[ $key ] );
+			} elseif ( isset( $devices[ $device_id ][ $key ] ) ) {
+				$device_data[ $key ] = $devices[ $device_id ][ $key ];
+			}
+		}
+
 		if ( isset( $settings['project_id'] ) ) {
 			$device_data['project_id'] = \sanitize_text_field( $settings['project_id'] );
 		}
@@ -215,13 +231,71 @@ class LocalServerConfig {
 			$device_data['project_dir'] = \sanitize_text_field( $settings['project_dir'] );
 		}
 
-		$devices               = self::get_user_devices( $user_id );
 		$devices[ $device_id ] = $device_data;
 		$devices               = self::cleanup_stale_devices( $devices );
 
 		return (bool) \update_user_meta( $user_id, self::USER_META_KEY, $devices );
 	}
 
+	/**
+	 * The user's devices as shown to clients, without credential references.
+	 *
+	 * @return array<int, array<string, mixed>>
+	 */
+	public static function list_devices( ?int $user_id = null ): array {
+		$user_id = $user_id ?? \get_current_user_id();
+		$devices = self::with_presence( self::get_user_devices( $user_id ) );
+
+		$list = array();
+		foreach ( $devices as $device_id => $device ) {
+			$list[] = array(
+				'device_id' => (string) $device_id,
+				'name'      => $device['name'] ?? null,
+				'platform'  => $device['platform'] ?? null,
+				'port'      => $device['port'] ?? null,
+				'enabled'   => $device['enabled'],
+				'online'    => $device['online'],
+				'last_seen' => $device['last_seen'] ?? null,
+			);
+		}
+
+		return $list;
+	}
+
+	/**
+	 * @return array<string, mixed>|null
+	 */
+	public static function get_device( string $device_id, ?int $user_id = null ): ?array {
+		$user_id = $user_id ?? \get_current_user_id();
+		$devices = self::get_user_devices( $user_id );
+
+		return $devices[ $device_id ] ?? null;
+	}
+
+	public static function rename_device( string $device_id, string $name, ?int $user_id = null ): bool {
+		$user_id = $user_id ?? \get_current_user_id();
+		$devices = self::get_user_devices( $user_id );
+
+		if ( ! isset( $devices[ $device_id ] ) ) {
+			return false;
+		}
+
+		$devices[ $device_id ]['name'] = \sanitize_text_field( $name );
+		return (bool) \update_user_meta( $user_id, self::USER_META_KEY, $devices );
+	}
+
+	public static function remove_device( string $device_id, ?int $user_id = null ): bool {
+		$user_id = $user_id ?? \get_current_user_id();
+		$devices = self::get_user_devices( $user_id );
+
+		if ( ! isset( $devices[ $device_id ] ) ) {
+			return false;
+		}
+
+		unset( $devices[ $device_id ] );
+		return (bool) \update_user_meta( $user_id, self::USER_META_KEY, $devices );
+	}
+
 	public static function get_port_for_device( string $device_id, ?int $user_id = null ): ?int {
 		$user_id = $user_id ?? \get_current_user_id();
 		$devices = self::get_user_devices( $user_id );
@@ -238,11 +312,39 @@ class LocalServerConfig {
 		return is_array( $devices ) ? $devices : array();
 	}
 
+	/**
+	 * Adds whether each device has reported within the presence timeout.
+	 */
+	private static function with_presence( array $devices ): array {
+		$cutoff = time() - self::PRESENCE_TIMEOUT;
+
+		foreach ( $devices as $device_id => $device ) {
+			$devices[ $device_id ]['enabled'] = $device['enabled'] ?? true;
+			$devices[ $device_id ]['online']  = ( $device['last_seen'] ?? 0 ) > $cutoff;
+		}
+
+		return $devices;
+	}
+
+	/**
+	 * The most recently seen device, preferring ones that are online with a
+	 * running server over ones that only reported in.
+	 */
 	private static function get_latest_device( array $devices ): array {
 		if ( empty( $devices ) ) {
 			return array();
 		}
 
+		$serving = array_filter(
+			$devices,
+			function ( $device ) {
+				return ! empty( $device['enabled'] ) && ! empty( $device['online'] );
+			}
+		);
+		if ( ! empty( $serving ) ) {
+			$devices = $serving;
+		}
+
 		$latest    = null;
 		$latest_id = null;
 
@@ -74,6 +74,10 @@ export const SettingsApp = () => {
 
   const serverRunning = config.settings.serverRunning;
   const execEnabled = config.settings.execEnabled;
+  // Set by the desktop app when a site's credentials stopped working.
+  const reauthRequested = new URLSearchParams(window.location.search).has(
+    'wordforge_reauth',
+  );
 
   const renderMcpTab = () => (
     <div className={styles.tabContent}>
@@ -124,7 +128,10 @@ export const SettingsApp = () => {
 
   const renderLocalTab = () => (
     <div className={styles.tabContent}>
-      <OpenCodeLocalTab initialPort={config.settings.localServerPort} />
+      <OpenCodeLocalTab
+        initialPort={config.settings.localServerPort}
+        reauth={reauthRequested}
+      />
     </div>
   );
 
@@ -133,7 +140,7 @@ export const SettingsApp = () => {
       <TabPanel
         className={styles.tabPanel}
         tabs={tabs}
-        initialTabName={execEnabled ? 'server' : 'local'}
+        initialTabName={execEnabled && !reauthRequested ? 'server' : 'local'}
       >
         {(tab) => {
           switch (tab.name) {
@@ -13,7 +13,7 @@ import {
   TextControl,
 } from '@wordpress/components';
 import { useCallback, useEffect, useMemo, useState } from '@wordpress/element';
-import { __ } from '@wordpress/i18n';
+import { __, sprintf } from '@wordpress/i18n';
 import { checkLocalServerHealth } from '../../lib/openCodeClient';
 import { useGenerateConnectToken } from '../hooks/useDesktopConnection';
 import {
@@ -64,9 +64,14 @@ const getInitialOS = (): OS => {
 
 interface OpenCodeLocalTabProps {
   initialPort: number;
+  /** Send a re-pairing link to the desktop app as soon as the tab opens. */
+  reauth?: boolean;
 }
 
-export const OpenCodeLocalTab = ({ initialPort }: OpenCodeLocalTabProps) => {
+export const OpenCodeLocalTab = ({
+  initialPort,
+  reauth = false,
+}: OpenCodeLocalTabProps) => {
   const { data: settings } = useLocalSettings();
   const { mutate: saveSettings, isPending: isSaving } = useSaveLocalSettings();
   const { mutate: downloadConfig, isPending: isDownloading } =
@@ -208,6 +213,20 @@ export const OpenCodeLocalTab = ({ initialPort }: OpenCodeLocalTabProps) => {
     });
   }, [generateToken]);
 
+  useEffect(() => {
+    if (!reauth) return;
+    generateToken(
+      { reauth: true },
+      {
+        onSuccess: (data) => {
+          setConnectUrl(data.connectUrl);
+          setTokenExpiresAt(Date.now() + data.expiresIn * 1000);
+          window.location.href = data.connectUrl;
+        },
+      },
+    );
+  }, [reauth, generateToken]);
+
   const handleOpenDesktopApp = useCallback(() => {
     if (!connectUrl) {
       generateToken(undefined, {
@@ -262,6 +281,27 @@ export const OpenCodeLocalTab = ({ initialPort }: OpenCodeLocalTabProps) => {
             )}
           </p>
 
+          {reauth && (
+            <p>
+              {__(
+                'Re-pairing WordForge Desktop. If the app did not open, use the button below.',
+                'wordforge',
+              )}
+            </p>
+          )}
+
+          {settings?.last_seen && (
+            <p>
+              {sprintf(
+                /* translators: %s: date and time the desktop app last reported in */
+                settings.online
+                  ? __('Desktop app online, last seen %s', 'wordforge')
+                  : __('Desktop app offline, last seen %s', 'wordforge'),
+                new Date(settings.last_seen * 1000).toLocaleString(),
+              )}
+            </p>
+          )}
+
           <div className={styles.desktopActions}>
             <Button
               variant="primary"
@@ -18,9 +18,12 @@ export const useDesktopConnectUrl = () =>
 
 export const useGenerateConnectToken = () =>
   useMutation({
-    mutationFn: async (): Promise<ConnectUrlResponse> =>
+    mutationFn: async (
+      options: { reauth?: boolean } = {},
+    ): Promise<ConnectUrlResponse> =>
       apiFetch<ConnectUrlResponse>({
         path: '/wordforge/v1/desktop/connect-token',
         method: 'POST',
+        data: { reauth: options.reauth ?? false },
       }),
   });
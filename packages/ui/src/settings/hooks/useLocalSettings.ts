@@ -9,6 +9,8 @@ interface LocalSettings {
   port: number;
   enabled: boolean;
   runtime: RuntimePreference;
+  online?: boolean;
+  last_seen?: number | null;
 }
 
 interface SaveLocalSettingsParams {